@@ -1,23 +1,55 @@
 //! # FX Simulator and Aggregator - fx_sim_agg_gui
 //!
 //! `aggregator.rs` aggregates simulated FX market data streams into a real-time book of buys and sells.
-use crate::simulator::Config;
-use crate::{AppError, get_params, get_str_field};
+//! Full refreshes replace a liquidity provider's quotes and incremental refreshes add, change or delete
+//! individual quotes by MDEntryID against a per liquidity provider book. `BookManager` holds one `FxBook`
+//! per currency pair and routes each market data message to the book for its pair.
+use crate::AppError;
+use crate::config::{Config, Settings};
+use crate::execution::{self, Execution, Order};
+use crate::feed::FeedMonitor;
+use crate::fix::{
+    ExecutionReport, MarketData, MarketDataIncrementalRefresh, MarketDataSnapshot, MdEntry,
+    MdUpdateAction, OrdStatus,
+};
+use crate::history::PriceHistory;
+use crate::position::PositionKeeper;
+use crate::price::{Price, pip_decimals};
+use crate::risk::{RiskError, RiskManager};
+use crate::router::RoutePlan;
 extern crate chrono;
 use chrono::Utc;
 use chrono::prelude::DateTime;
-use core::f64;
 //use log::{debug, error, info, trace, warn};
 use log::{error, info};
-use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fmt::{Display, Formatter};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-#[derive(Debug)]
+/// Side of the book a quote is on - "Buy" quotes are bids and "Sell" quotes are offers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BookSide {
+    Buy,
+    Sell,
+}
+
+impl Display for BookSide {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Buy => f.write_str("Buy"),
+            Self::Sell => f.write_str("Sell"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct FxAggBookEntry {
-    pub lp_vol: Vec<(String, i32)>,
+    // liquidity provider, volume and key in its lp_book of each quote at this price, in the order the quotes arrived
+    pub lp_vol: Vec<(String, i32, String)>,
     pub volume: i32,
-    pub price: f64,
-    pub side: String,
+    pub price: Price,
+    pub side: BookSide,
 }
 
 impl PartialEq for FxAggBookEntry {
@@ -28,7 +60,7 @@ impl PartialEq for FxAggBookEntry {
 
 impl PartialOrd for FxAggBookEntry {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        self.price.partial_cmp(&other.price)
+        Some(self.price.cmp(&other.price))
     }
 }
 #[derive(Debug, Default)]
@@ -37,32 +69,38 @@ pub struct FxBook {
     pub buy_book: Vec<FxAggBookEntry>,
     pub sell_book: Vec<FxAggBookEntry>,
     pub timestamp: u64,
+    // current quotes of each liquidity provider keyed by MDEntryID
+    pub lp_books: HashMap<String, HashMap<String, MdEntry>>,
+    // volume each quote in lp_books was quoted for, which fills do not change, so the tier of a lifted quote is still known
+    pub lp_quoted_volumes: HashMap<String, HashMap<String, i32>>,
+    // minimum spread kept between the top of the buy and sell books
+    pub min_spread: Price,
 }
 
 impl FxBook {
-    pub fn update(&mut self, market_data: String) -> Result<(), AppError> {
-        // add fxbook entries for all current market data in the order of
-        // 1M buy, 1M sell, 3M buy, 3M sell, 5M buy, 5M sell
-        add_market_data(self, market_data)?;
+    pub fn update(&mut self, market_data: &MarketData) -> Result<(), AppError> {
+        match market_data {
+            // add fxbook entries for all current market data - a buy and a sell
+            // for each volume tier of the liquidity provider
+            MarketData::Snapshot(snapshot) => add_market_data(self, snapshot)?,
+            MarketData::Incremental(refresh) => apply_incremental_refresh(self, refresh)?,
+        }
         sort_books(self);
-        match check_books_crossed(self) {
-            Some(index) => {
-                info!(
-                    "books crossed at sell book index {} with sell price {}",
-                    index.0, index.1
-                );
-                correct_crossed_books(self, index)?;
-            }
-            None => (),
+        if let Some(index) = check_books_crossed(self) {
+            info!(
+                "books crossed at sell book index {} with sell price {}",
+                index.0, index.1
+            );
+            correct_crossed_books(self, index)?;
         }
         maintain_min_spread(self);
         Ok(())
     }
-    pub fn new(config: &Vec<Config>) -> Self {
+    pub fn new(currency_pair: &str, min_spread_pips: f64) -> Self {
         // create a new FxBook with empty buy and sell books
         // and a timestamp of current time
-        // using first config entry to get currency pair
-        let currency_pair = config[0].currency_pair.clone();
+        let min_spread = Price::from_pips(min_spread_pips, pip_decimals(currency_pair));
+        let currency_pair = String::from(currency_pair);
         let buy_book: Vec<FxAggBookEntry> = Vec::new();
         let sell_book: Vec<FxAggBookEntry> = Vec::new();
         //need to catch this possible panic on unwrap when converting u126 to u64
@@ -78,15 +116,175 @@ impl FxBook {
             buy_book,
             sell_book,
             timestamp,
+            lp_books: HashMap::new(),
+            lp_quoted_volumes: HashMap::new(),
+            min_spread,
         }
     }
 }
 
-fn correct_crossed_books(fx_book: &mut FxBook, index: (usize, f64)) -> Result<(), AppError> {
+#[derive(Debug, Default)]
+pub struct BookManager {
+    pub books: BTreeMap<String, FxBook>,
+    pub min_spread_pips: f64,
+    // positions built up by the orders executed against the books
+    pub positions: PositionKeeper,
+    // pre-trade checks of every order executed against the books
+    pub risk: RiskManager,
+    // recent top of book and liquidity provider quotes of each book for the price chart
+    pub histories: BTreeMap<String, PriceHistory>,
+    // statistics of the market data feed from each liquidity provider
+    pub feeds: FeedMonitor,
+}
+
+impl BookManager {
+    pub fn new(config: &[Config], settings: &Settings) -> Self {
+        let min_spread_pips = settings.min_spread_pips;
+        // create an empty FxBook for every currency pair in the config
+        let mut books = BTreeMap::new();
+        let mut feeds = FeedMonitor::default();
+        for i in config {
+            books
+                .entry(i.currency_pair.clone())
+                .or_insert_with(|| FxBook::new(&i.currency_pair, min_spread_pips));
+            feeds.add_feed(&i.currency_pair, &i.liquidity_provider);
+        }
+        BookManager {
+            books,
+            min_spread_pips,
+            positions: PositionKeeper::default(),
+            risk: RiskManager::new(settings.risk.clone()),
+            histories: BTreeMap::new(),
+            feeds,
+        }
+    }
+
+    pub fn update(&mut self, market_data: &MarketData) -> Result<&mut FxBook, AppError> {
+        // route the market data to the book for its currency pair, creating
+        // a new book the first time an unconfigured currency pair is seen
+        let currency_pair = market_data.currency_pair();
+        let min_spread_pips = self.min_spread_pips;
+        let fx_book = self
+            .books
+            .entry(String::from(currency_pair))
+            .or_insert_with(|| {
+                info!("creating FX Book for new currency pair {}", currency_pair);
+                FxBook::new(currency_pair, min_spread_pips)
+            });
+        fx_book.update(market_data)?;
+        self.histories
+            .entry(String::from(currency_pair))
+            .or_default()
+            .record(fx_book);
+        Ok(fx_book)
+    }
+
+    pub fn execute(&mut self, order: &Order) -> Result<Execution, AppError> {
+        // execute the order against the book for its currency pair, once it passes the risk checks, and add
+        // its fills to the positions
+        self.check_order(order)?;
+        let fx_book = self.books.get_mut(&order.currency_pair).ok_or_else(|| {
+            AppError::InvalidOrder(format!("no book for {}", order.currency_pair))
+        })?;
+        let execution = execution::execute_order(fx_book, order)?;
+        self.histories
+            .entry(order.currency_pair.clone())
+            .or_default()
+            .record(fx_book);
+        self.positions.add_execution(&execution);
+        Ok(execution)
+    }
+
+    pub fn add_execution_report(&mut self, report: &ExecutionReport) {
+        // a liquidity provider's fill of a routed child order is added to the positions and its volume taken from the
+        // liquidity provider's quotes in the book, best price first, as its last look found them within its tolerance
+        self.positions.add_execution_report(report);
+        if report.ord_status == OrdStatus::Rejected || report.last_qty == 0 {
+            return;
+        }
+        let Some(fx_book) = self.books.get_mut(&report.currency_pair) else {
+            return;
+        };
+        let book_side = report.side.book_side();
+        let mut remaining = report.last_qty;
+        while remaining > 0 {
+            let Some(price) = get_book_side(fx_book, book_side)
+                .iter()
+                .find(|level| {
+                    level
+                        .lp_vol
+                        .iter()
+                        .any(|lp_vol| lp_vol.0 == report.liquidity_provider)
+                })
+                .map(|level| level.price)
+            else {
+                break;
+            };
+            remaining -= execution::take_lp_volume(
+                fx_book,
+                book_side,
+                &report.liquidity_provider,
+                price,
+                remaining,
+            );
+        }
+        self.histories
+            .entry(report.currency_pair.clone())
+            .or_default()
+            .record(fx_book);
+    }
+
+    pub fn check_order(&self, order: &Order) -> Result<(), AppError> {
+        // every order executed against the book must be valid for the book of its currency pair and pass the
+        // risk checks, including the kill switch, against the fills it would get
+        let fx_book = self.valid_book(order)?;
+        let result = self.risk.check_order(fx_book, &self.positions, order);
+        risk_checked(order, result)
+    }
+
+    pub fn check_route_plan(&self, route_plan: &RoutePlan) -> Result<(), AppError> {
+        // a routed order passes the same checks against the child orders it is split into before any is sent
+        self.valid_book(&route_plan.order)?;
+        let result = self.risk.check_route_plan(&self.positions, route_plan);
+        risk_checked(&route_plan.order, result)
+    }
+
+    fn valid_book(&self, order: &Order) -> Result<&FxBook, AppError> {
+        let fx_book = self.get(&order.currency_pair).ok_or_else(|| {
+            AppError::InvalidOrder(format!("no book for {}", order.currency_pair))
+        })?;
+        execution::validate_order(fx_book, order)?;
+        Ok(fx_book)
+    }
+
+    pub fn get(&self, currency_pair: &str) -> Option<&FxBook> {
+        self.books.get(currency_pair)
+    }
+
+    pub fn history(&self, currency_pair: &str) -> Option<&PriceHistory> {
+        self.histories.get(currency_pair)
+    }
+
+    pub fn currency_pairs(&self) -> Vec<String> {
+        self.books.keys().cloned().collect()
+    }
+}
+
+fn risk_checked(order: &Order, result: Result<(), RiskError>) -> Result<(), AppError> {
+    result.map_err(|e| {
+        error!(
+            "{} {}M {} order rejected - {e}",
+            order.side, order.volume, order.currency_pair
+        );
+        AppError::Risk(e)
+    })
+}
+
+fn correct_crossed_books(fx_book: &mut FxBook, index: (usize, Price)) -> Result<(), AppError> {
     // when books have crossed then need to remove all entries above the cross price from the
     // top of the book that has the highest number of entries
     if fx_book.buy_book.len() > fx_book.sell_book.len() {
-        let fx_book_side = get_book_side(fx_book, "Buy");
+        let fx_book_side = get_book_side(fx_book, BookSide::Buy);
         info!("buy book longer than sell book when books have crossed");
         if let Some(position) = find_buy_index_when_crossed(fx_book_side, index.1) {
             // remove all buy entries >= new sell price
@@ -94,203 +292,221 @@ fn correct_crossed_books(fx_book: &mut FxBook, index: (usize, f64)) -> Result<()
                 "removing all buy entries from top of book to index {}",
                 position
             );
-            remove_range_entries_from_top(fx_book_side, position, "Buy");
+            let removed = remove_range_entries_from_top(fx_book_side, position, BookSide::Buy);
+            lift_lp_quotes(fx_book, &removed);
         }
     } else {
         // remove all sell entries <= new buy price
-        let fx_book_side = get_book_side(fx_book, "Sell");
+        let fx_book_side = get_book_side(fx_book, BookSide::Sell);
         info!(
             "removing all sell entries from top of book to index {}",
             index.0
         );
-        remove_range_entries_from_top(fx_book_side, index.0, "Sell");
+        let removed = remove_range_entries_from_top(fx_book_side, index.0, BookSide::Sell);
+        lift_lp_quotes(fx_book, &removed);
     }
 
     Ok(())
 }
-fn add_market_data(fx_book: &mut FxBook, market_data: String) -> Result<(), AppError> {
-    let mut vol_prices_vec: Vec<(i32, f64, String)> = Vec::new();
-
-    let mut market_data_params = get_params(&market_data, 9)?;
-    let liquidity_provider = get_str_field(market_data_params.next())?;
-    let _currency_pair = get_str_field(market_data_params.next())?;
-    let one_mill_buy_price: f64 = market_data_params.next().unwrap_or("").trim().parse()?;
-    vol_prices_vec.push((1, one_mill_buy_price, String::from("Buy")));
-    let one_mill_sell_price: f64 = market_data_params.next().unwrap_or("").trim().parse()?;
-    vol_prices_vec.push((1, one_mill_sell_price, String::from("Sell")));
-    let three_mill_buy_price: f64 = market_data_params.next().unwrap_or("").trim().parse()?;
-    vol_prices_vec.push((3, three_mill_buy_price, String::from("Buy")));
-    let three_mill_sell_price: f64 = market_data_params.next().unwrap_or("").trim().parse()?;
-    vol_prices_vec.push((3, three_mill_sell_price, String::from("Sell")));
-    let five_mill_buy_price: f64 = market_data_params.next().unwrap_or("").trim().parse()?;
-    vol_prices_vec.push((5, five_mill_buy_price, String::from("Buy")));
-    let five_mill_sell_price: f64 = market_data_params.next().unwrap_or("").trim().parse()?;
-    vol_prices_vec.push((5, five_mill_sell_price, String::from("Sell")));
-    let timestamp: u64 = market_data_params.next().unwrap_or("").trim().parse()?;
-
-    fx_book.timestamp = timestamp;
-
-    let mut i = 0;
-    for val in vol_prices_vec {
-        if i % 2 == 0 {
-            //remove expired quotes before adding any new quotes
-            let fx_book_side = get_book_side(fx_book, "Buy");
-            // match check_expired_quotes(fx_book, liquidity_provider, "Buy", val.0) {
-            match check_expired_quotes(fx_book_side, liquidity_provider, val.0) {
-                Some(entry_to_remove) => remove_single_entry(fx_book, "Buy", entry_to_remove),
-                None => (),
-            }
-            add_agg_book_entry(fx_book, liquidity_provider, val.0, val.1, "Buy");
-        } else {
-            let fx_book_side = get_book_side(fx_book, "Sell");
-            match check_expired_quotes(fx_book_side, liquidity_provider, val.0) {
-                //  match check_expired_quotes(fx_book, liquidity_provider, "Sell", val.0) {
-                Some(entry_to_remove) => remove_single_entry(fx_book, "Sell", entry_to_remove),
-                None => (),
-            }
-            add_agg_book_entry(fx_book, liquidity_provider, val.0, val.1, "Sell");
+fn add_market_data(fx_book: &mut FxBook, market_data: &MarketDataSnapshot) -> Result<(), AppError> {
+    if market_data.entries.is_empty() {
+        return Err(AppError::IsEmpty);
+    }
+    let liquidity_provider = market_data.liquidity_provider.as_str();
+    // express all prices to the precision of the currency pair
+    let decimals = pip_decimals(&fx_book.currency_pair);
+
+    fx_book.timestamp = market_data.timestamp;
+
+    // a full refresh replaces everything from this liquidity provider, including quotes partly filled by an order
+    if let Some(previous_lp_book) = fx_book.lp_books.remove(liquidity_provider) {
+        for entry in previous_lp_book.values() {
+            remove_lp_quote(fx_book, liquidity_provider, entry);
         }
-        i += 1;
     }
+    let mut lp_book: HashMap<String, MdEntry> = HashMap::new();
+    let mut quoted_volumes: HashMap<String, i32> = HashMap::new();
+    for entry in &market_data.entries {
+        let key = lp_book_key(entry);
+        quoted_volumes.insert(key.clone(), entry.volume);
+        let price = entry.price.rescale(decimals)?;
+        add_agg_book_entry(
+            fx_book,
+            liquidity_provider,
+            &key,
+            entry.volume,
+            price,
+            entry.side,
+        );
+        lp_book.insert(
+            key.clone(),
+            MdEntry {
+                entry_id: key,
+                price,
+                ..entry.clone()
+            },
+        );
+    }
+    fx_book
+        .lp_books
+        .insert(String::from(liquidity_provider), lp_book);
+    fx_book
+        .lp_quoted_volumes
+        .insert(String::from(liquidity_provider), quoted_volumes);
 
     Ok(())
 }
 
-pub fn check_expired_quotes(
-    // fx_book: &mut FxBook,
-    fx_book_side: &mut Vec<FxAggBookEntry>,
-    liquidity_provider: &str,
-    // side: &str,
-    volume: i32,
-) -> Option<usize> {
-    // check to see if there is an expired quote from this liquidity provider
-    let mut index = 0;
-    let mut index_to_remove: usize = 0;
-    let mut remove_entry = false;
-    for entry in fx_book_side {
-        let mut total_volume = 0;
-        let lp_vol_vec: &mut Vec<(String, i32)> = &mut entry.lp_vol;
-        // remove expired quote
-        lp_vol_vec.retain(|lp_vol| {
-            (lp_vol.0 != liquidity_provider)
-                || ((lp_vol.0 == liquidity_provider) && (lp_vol.1 != volume))
-        });
-        // check to see if removing expired quote has left behind an fxbook entry with an
-        // empty liquidity provider and volume pair vector. Return index of this entry so
-        // remove_single_entry function can remove this entry
-        if lp_vol_vec.len() == 0 {
-            index_to_remove = index;
-            remove_entry = true;
+fn apply_incremental_refresh(
+    fx_book: &mut FxBook,
+    market_data: &MarketDataIncrementalRefresh,
+) -> Result<(), AppError> {
+    if market_data.entries.is_empty() {
+        return Err(AppError::IsEmpty);
+    }
+    let liquidity_provider = market_data.liquidity_provider.as_str();
+    let decimals = pip_decimals(&fx_book.currency_pair);
+
+    fx_book.timestamp = market_data.timestamp;
+
+    for incremental_entry in &market_data.entries {
+        let action = incremental_entry.action;
+        let entry = MdEntry {
+            price: incremental_entry.entry.price.rescale(decimals)?,
+            ..incremental_entry.entry.clone()
+        };
+
+        // a change or delete first removes the quote currently held for this MDEntryID
+        let previous_entry = fx_book
+            .lp_books
+            .entry(String::from(liquidity_provider))
+            .or_default()
+            .remove(&entry.entry_id);
+        match previous_entry {
+            Some(previous_entry) => remove_lp_quote(fx_book, liquidity_provider, &previous_entry),
+            None if action != MdUpdateAction::New => error!(
+                "{} {:?} for unknown MDEntryID {}",
+                liquidity_provider, action, entry.entry_id
+            ),
+            None => (),
         }
-        // need to re-sum the total volumes here in case an expired quote has been removed
-        for val in lp_vol_vec {
-            total_volume += val.1;
+
+        let quoted_volumes = fx_book
+            .lp_quoted_volumes
+            .entry(String::from(liquidity_provider))
+            .or_default();
+        quoted_volumes.remove(&entry.entry_id);
+
+        if action != MdUpdateAction::Delete {
+            quoted_volumes.insert(entry.entry_id.clone(), entry.volume);
+            add_agg_book_entry(
+                fx_book,
+                liquidity_provider,
+                &entry.entry_id,
+                entry.volume,
+                entry.price,
+                entry.side,
+            );
+            fx_book
+                .lp_books
+                .entry(String::from(liquidity_provider))
+                .or_default()
+                .insert(entry.entry_id.clone(), entry);
         }
-        entry.volume = total_volume;
-        index += 1;
     }
 
-    if remove_entry {
-        return Some(index_to_remove);
+    Ok(())
+}
+
+fn lp_book_key(entry: &MdEntry) -> String {
+    // snapshot entries without an MDEntryID are given one from their side and volume
+    if entry.entry_id.is_empty() {
+        format!("{}{}", entry.side, entry.volume)
     } else {
-        return None;
+        entry.entry_id.clone()
+    }
+}
+
+pub fn remove_lp_quote(fx_book: &mut FxBook, liquidity_provider: &str, entry: &MdEntry) {
+    // remove a single quote of the liquidity provider's book from the aggregated book by its MDEntryID, dropping
+    // its price level once no quotes are left. The quote may already have gone if it was lifted or its price level
+    // was removed when correcting crossed books or the spread
+    let fx_book_side = get_book_side(fx_book, entry.side);
+    let Some(index) = fx_book_side
+        .iter()
+        .position(|agg_entry| agg_entry.price == entry.price)
+    else {
+        return;
+    };
+    let agg_entry = &mut fx_book_side[index];
+    agg_entry
+        .lp_vol
+        .retain(|lp_vol| lp_vol.0 != liquidity_provider || lp_vol.2 != entry.entry_id);
+    agg_entry.volume = agg_entry.lp_vol.iter().map(|lp_vol| lp_vol.1).sum();
+    if agg_entry.lp_vol.is_empty() {
+        remove_single_entry(fx_book, entry.side, index);
     }
 }
 
 pub fn add_agg_book_entry(
     fx_book: &mut FxBook,
     liquidity_provider: &str,
+    key: &str,
     volume: i32,
-    price: f64,
-    side: &str,
+    price: Price,
+    side: BookSide,
 ) {
-    let mut lp_vol_vec: Vec<(String, i32)> = Vec::new();
-    lp_vol_vec.push((String::from(liquidity_provider), volume));
-
-    // if first entry then just add it to book
-    // and using fact that first entry is always a Buy in current config
-    if fx_book.buy_book.len() == 0 && side == "Buy" {
-        let new_agg_book_entry = FxAggBookEntry {
-            lp_vol: lp_vol_vec,
-            volume,
-            price,
-            side: String::from(side),
-        };
-        fx_book.buy_book.push(new_agg_book_entry);
-        println!("{fx_book:?}");
-        return;
-    } else if fx_book.buy_book.len() == 0 && fx_book.sell_book.len() == 0 {
-        error!("first entry should not be on sell side in current configuration");
-        return;
-    } else {
-        let fx_book_side = get_book_side(fx_book, side);
-
-        //search to see if current price already in aggregated book
-        for entry in fx_book_side {
-            if entry.price == price {
-                let lp_tup = (String::from(liquidity_provider), volume);
-                entry.lp_vol.push(lp_tup);
-                entry.volume += volume;
-                return;
-            }
-        }
+    let fx_book_side = get_book_side(fx_book, side);
 
-        // this is new entry
-        let new_agg_book_entry = FxAggBookEntry {
-            lp_vol: lp_vol_vec,
-            volume,
-            price,
-            side: String::from(side),
-        };
-        let fx_book_side = get_book_side(fx_book, side);
-        fx_book_side.push(new_agg_book_entry);
+    //search to see if current price already in aggregated book
+    if let Some(entry) = fx_book_side.iter_mut().find(|entry| entry.price == price) {
+        let lp_tup = (String::from(liquidity_provider), volume, String::from(key));
+        entry.lp_vol.push(lp_tup);
+        entry.volume += volume;
         return;
     }
+
+    // this is new entry
+    let new_agg_book_entry = FxAggBookEntry {
+        lp_vol: vec![(String::from(liquidity_provider), volume, String::from(key))],
+        volume,
+        price,
+        side,
+    };
+    fx_book_side.push(new_agg_book_entry);
 }
 
 fn sort_books(fx_book: &mut FxBook) {
-    let fx_buy_book = get_book_side(fx_book, "Buy");
+    let fx_buy_book = get_book_side(fx_book, BookSide::Buy);
     sort_buy_book(fx_buy_book);
-    let fx_sell_book = get_book_side(fx_book, "Sell");
+    let fx_sell_book = get_book_side(fx_book, BookSide::Sell);
     sort_sell_book(fx_sell_book);
-
-    /*   fx_book
-    .sell_book
-    .sort_by(|a, b| a.price.partial_cmp(&b.price).unwrap()); */
 }
 
-pub fn sort_buy_book(fx_buy_book: &mut Vec<FxAggBookEntry>) {
+pub fn sort_buy_book(fx_buy_book: &mut [FxAggBookEntry]) {
     // need to do a reverse sort on price for buy side
-    fx_buy_book.sort_by(|a, b| match a.price.partial_cmp(&b.price).unwrap() {
-        Ordering::Less => Ordering::Greater,
-        Ordering::Equal => Ordering::Equal,
-        Ordering::Greater => Ordering::Less,
-    });
+    fx_buy_book.sort_by_key(|entry| std::cmp::Reverse(entry.price));
 }
 
-pub fn sort_sell_book(fx_sell_book: &mut Vec<FxAggBookEntry>) {
-    fx_sell_book.sort_by(|a, b| a.price.partial_cmp(&b.price).unwrap());
+pub fn sort_sell_book(fx_sell_book: &mut [FxAggBookEntry]) {
+    fx_sell_book.sort_by_key(|entry| entry.price);
 }
 
 pub fn find_buy_index_when_crossed(
-    fx_buy_book: &mut Vec<FxAggBookEntry>,
-    sell_price: f64,
+    fx_buy_book: &mut [FxAggBookEntry],
+    sell_price: Price,
 ) -> Option<usize> {
     // when books have crossed and buy book is longer than sell book
     // then need to find where buy price crosses on sell side and remove
     // all buy entries >= new sell price
-
-    for i in (0..fx_buy_book.len()).rev() {
-        if fx_buy_book[i].price >= sell_price {
-            return Some(i);
-        }
-    }
-    return None;
+    (0..fx_buy_book.len())
+        .rev()
+        .find(|&i| fx_buy_book[i].price >= sell_price)
 }
 
-pub fn check_books_crossed(fx_book: &mut FxBook) -> Option<(usize, f64)> {
-    let top_of_buy_book_price = fx_book.buy_book[0].price;
-    let fx_book_side = get_book_side(fx_book, "Sell");
+pub fn check_books_crossed(fx_book: &mut FxBook) -> Option<(usize, Price)> {
+    let top_of_buy_book_price = fx_book.buy_book.first()?.price;
+    let fx_book_side = get_book_side(fx_book, BookSide::Sell);
 
     // if buy book top of book price >= any fx_book.sell_book price then books have crossed
     // so find where buy price crosses on sell side and remove all sell entries <= new buy price
@@ -299,65 +515,151 @@ pub fn check_books_crossed(fx_book: &mut FxBook) -> Option<(usize, f64)> {
             return Some((i, fx_book_side[i].price));
         }
     }
-    return None;
+    None
 }
 
 pub fn maintain_min_spread(fx_book: &mut FxBook) {
-    // if spread is less than 6 pips (arbitrary) then delete top of book entries
+    // if spread is less than the configured minimum spread then delete top of book entries
     // until get this minimum spread
+    let min_spread = fx_book.min_spread;
 
-    while fx_book.sell_book[0].price - fx_book.buy_book[0].price <= 0.0006 {
-        if fx_book.buy_book.len() >= fx_book.sell_book.len() {
+    while let (Some(sell_entry), Some(buy_entry)) =
+        (fx_book.sell_book.first(), fx_book.buy_book.first())
+    {
+        if sell_entry.price - buy_entry.price > min_spread {
+            break;
+        }
+        let removed = if fx_book.buy_book.len() >= fx_book.sell_book.len() {
             // remove top entry from buy side
             info!("removing top of buy book to maintain spread");
-            remove_single_entry(fx_book, "Buy", 0);
+            remove_single_entry(fx_book, BookSide::Buy, 0)
         } else {
             info!("removing top of sell book to maintain spread");
-            remove_single_entry(fx_book, "Sell", 0)
-        }
+            remove_single_entry(fx_book, BookSide::Sell, 0)
+        };
+        lift_lp_quotes(fx_book, &[removed]);
     }
 }
 
 pub fn remove_range_entries_from_top(
     fx_book_side: &mut Vec<FxAggBookEntry>,
     index: usize,
-    side: &str,
-) {
-    //  let fx_book_side = get_book_side(fx_book, side);
-    for i in 0..index + 1 {
-        // because of removal of [0] entry then entry to remove is always the top one [0]
-        fx_book_side.remove(0);
+    side: BookSide,
+) -> Vec<FxAggBookEntry> {
+    let removed: Vec<FxAggBookEntry> = fx_book_side.drain(0..index + 1).collect();
+    for i in 0..removed.len() {
         info!("removing entry {} from {} book", i, side);
     }
+    removed
 }
 
-fn remove_single_entry(fx_book: &mut FxBook, side: &str, index_to_remove: usize) {
-    // removing an expired quote can leave behind an fxbook entry with an empty
-    // liquidity provider and volume vector. This funtion removes this hanging entry
+fn remove_single_entry(
+    fx_book: &mut FxBook,
+    side: BookSide,
+    index_to_remove: usize,
+) -> FxAggBookEntry {
+    // removes a price level, either one left with no liquidity provider quotes or one
+    // taken off the top of the book
 
     let fx_book_side = get_book_side(fx_book, side);
-    fx_book_side.remove(index_to_remove);
+    fx_book_side.remove(index_to_remove)
 }
 
-pub fn get_book_side<'a>(fx_book: &'a mut FxBook, side: &str) -> &'a mut Vec<FxAggBookEntry> {
-    // Because fx_book is the argument that contains the returned vector of book entries
-    // then this fx_book argument is the argument that must be connected to the return
-    // value using the lifetime syntax
-    if String::from(side) == String::from("Buy") {
-        &mut fx_book.buy_book
-    } else {
-        &mut fx_book.sell_book
+fn lift_lp_quotes(fx_book: &mut FxBook, levels: &[FxAggBookEntry]) {
+    // quotes of price levels removed to uncross the books or keep the spread are no longer available, so they are
+    // taken off the liquidity providers' books as if lifted and kept with no volume until the liquidity provider updates them
+    for (liquidity_provider, _, key) in levels.iter().flat_map(|level| &level.lp_vol) {
+        if let Some(entry) = fx_book
+            .lp_books
+            .get_mut(liquidity_provider)
+            .and_then(|lp_book| lp_book.get_mut(key))
+        {
+            entry.volume = 0;
+        }
+    }
+}
+
+pub fn get_book_side(fx_book: &mut FxBook, side: BookSide) -> &mut Vec<FxAggBookEntry> {
+    // fx_book is the only reference argument so the returned vector of book entries
+    // borrows from it without needing an explicit lifetime
+    match side {
+        BookSide::Buy => &mut fx_book.buy_book,
+        BookSide::Sell => &mut fx_book.sell_book,
     }
 }
 
-pub fn print_fxbook_as_ladder(fx_book: &mut FxBook) {
+#[derive(Debug, Clone, PartialEq)]
+pub struct DepthLevel {
+    pub price: Price,
+    // volume (M) of this level and every better priced level
+    pub cumulative_volume: i32,
+    pub lp_vol: Vec<(String, i32)>,
+}
+
+pub fn market_depth(fx_book_side: &[FxAggBookEntry]) -> Vec<DepthLevel> {
+    // the levels of a side of the book from the top with the volume available at each price or better
+    let mut cumulative_volume = 0;
+    fx_book_side
+        .iter()
+        .map(|level| {
+            cumulative_volume += level.volume;
+            DepthLevel {
+                price: level.price,
+                cumulative_volume,
+                lp_vol: level
+                    .lp_vol
+                    .iter()
+                    .map(|lp_vol| (lp_vol.0.clone(), lp_vol.1))
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LevelChange {
+    Inserted,
+    // the volume or liquidity providers of the level changed
+    Updated,
+    Removed,
+}
+
+pub fn level_changes(
+    previous: &[FxAggBookEntry],
+    current: &[FxAggBookEntry],
+) -> Vec<(Price, LevelChange)> {
+    // the price levels of a side of the book inserted, updated or removed since its previous levels
+    let mut changes: Vec<(Price, LevelChange)> = Vec::new();
+    for level in current {
+        match previous
+            .iter()
+            .find(|previous| previous.price == level.price)
+        {
+            None => changes.push((level.price, LevelChange::Inserted)),
+            Some(previous)
+                if previous.volume != level.volume || previous.lp_vol != level.lp_vol =>
+            {
+                changes.push((level.price, LevelChange::Updated))
+            }
+            Some(_) => {}
+        }
+    }
+    for previous in previous {
+        if !current.iter().any(|level| level.price == previous.price) {
+            changes.push((previous.price, LevelChange::Removed));
+        }
+    }
+    changes
+}
+
+pub fn print_fxbook_as_ladder(fx_book: &FxBook) {
     let d = UNIX_EPOCH + Duration::from_nanos(fx_book.timestamp);
     let datetime = DateTime::<Utc>::from(d);
 
     println!(
         "\nCurrent state of FX Book for {} at timestamp {}:\n",
         fx_book.currency_pair,
-        datetime.format("%Y-%m-%d %H:%M:%S.%f").to_string()
+        datetime.format("%Y-%m-%d %H:%M:%S.%f")
     );
     println!("Side\t Price\t Volume(M)\t (Liquidity Providers : Volumes(M))");
     println!("===================================================================");
@@ -366,60 +668,24 @@ pub fn print_fxbook_as_ladder(fx_book: &mut FxBook) {
     print_buy_side(fx_book);
 }
 
-fn print_buy_side(fx_book: &mut FxBook) {
-    let fx_book_side = get_book_side(fx_book, "Buy");
-    for entry in fx_book_side {
+fn print_buy_side(fx_book: &FxBook) {
+    for entry in &fx_book.buy_book {
         print!("{}:\t {}\t   {}", entry.side, entry.price, entry.volume);
-        let lp_vol_vec: &mut Vec<(String, i32)> = &mut entry.lp_vol;
-        let len = lp_vol_vec.len() - 1;
-        let mut index = 0;
-        for val in lp_vol_vec {
-            if index == 0 && len == 0 {
-                let lp_vol = format!("\t\t ({}: {})", val.0, val.1);
-                print!("{}", lp_vol);
-            } else if index == 0 {
-                let lp_vol = format!("\t\t ({}: {},", val.0, val.1);
-                print!("{}", lp_vol);
-            } else if index == len {
-                let lp_vol = format!(" {}: {})", val.0, val.1);
-                print!("{}", lp_vol);
-            } else {
-                let lp_vol = format!(" {}: {},", val.0, val.1);
-                print!("{}", lp_vol);
-            }
-            index += 1;
-        }
-        print!("\n");
+        print_lp_vol(&entry.lp_vol);
     }
 }
 
-fn print_sell_side(fx_book: &mut FxBook) {
-    let fx_book_side = get_book_side(fx_book, "Sell");
-
-    for i in (0..fx_book_side.len()).rev() {
-        print!(
-            "{}:\t {}\t   {}",
-            fx_book_side[i].side, fx_book_side[i].price, fx_book_side[i].volume
-        );
-        let lp_vol_vec: &mut Vec<(String, i32)> = &mut fx_book_side[i].lp_vol;
-        let len = lp_vol_vec.len() - 1;
-        let mut index = 0;
-        for val in lp_vol_vec {
-            if index == 0 && len == 0 {
-                let lp_vol = format!("\t\t ({}: {})", val.0, val.1);
-                print!("{}", lp_vol);
-            } else if index == 0 {
-                let lp_vol = format!("\t\t ({}: {},", val.0, val.1);
-                print!("{}", lp_vol);
-            } else if index == len {
-                let lp_vol = format!(" {}: {})", val.0, val.1);
-                print!("{}", lp_vol);
-            } else {
-                let lp_vol = format!(" {}: {},", val.0, val.1);
-                print!("{}", lp_vol);
-            }
-            index += 1;
-        }
-        print!("\n");
+fn print_sell_side(fx_book: &FxBook) {
+    for entry in fx_book.sell_book.iter().rev() {
+        print!("{}:\t {}\t   {}", entry.side, entry.price, entry.volume);
+        print_lp_vol(&entry.lp_vol);
     }
 }
+
+fn print_lp_vol(lp_vol_vec: &[(String, i32, String)]) {
+    let lp_vols: Vec<String> = lp_vol_vec
+        .iter()
+        .map(|lp_vol| format!("{}: {}", lp_vol.0, lp_vol.1))
+        .collect();
+    println!("\t\t ({})", lp_vols.join(", "));
+}
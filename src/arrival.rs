@@ -0,0 +1,182 @@
+//! # FX Simulator and Aggregator - fx_sim_agg_gui
+//!
+//! `arrival.rs` defines the quote arrival models which set the simulated time between a liquidity provider's quotes - uniform
+//! random intervals, a fixed rate, a Poisson process, bursty self-exciting (Hawkes) clustering, with optional quiet periods
+//! when the liquidity provider stops quoting altogether.
+use rand::Rng;
+use rand_distr::{Distribution, Exp};
+use serde::Deserialize;
+
+const NANOS_PER_SEC: f64 = 1_000_000_000.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ArrivalModel {
+    // a random interval between min_ms and max_ms milliseconds
+    Uniform {
+        min_ms: f64,
+        max_ms: f64,
+    },
+    // exactly rate_per_sec quotes a second
+    Fixed {
+        rate_per_sec: f64,
+    },
+    // exponentially distributed intervals averaging rate_per_sec quotes a second
+    Poisson {
+        rate_per_sec: f64,
+    },
+    // self-exciting arrivals - each quote raises the rate by excitation_per_sec which then decays
+    // away at decay_per_sec, so quotes come in bursts around a base rate of base_rate_per_sec
+    Hawkes {
+        base_rate_per_sec: f64,
+        excitation_per_sec: f64,
+        decay_per_sec: f64,
+    },
+}
+
+impl Default for ArrivalModel {
+    fn default() -> Self {
+        ArrivalModel::Uniform {
+            min_ms: 1000.0,
+            max_ms: 5000.0,
+        }
+    }
+}
+
+// the liquidity provider goes quiet for on average mean_duration_secs after on average every mean_gap_secs of quoting
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QuietPeriods {
+    pub mean_gap_secs: f64,
+    pub mean_duration_secs: f64,
+}
+
+impl ArrivalModel {
+    pub fn validate(&self) -> Result<(), String> {
+        match *self {
+            ArrivalModel::Uniform { min_ms, max_ms } => {
+                if !(min_ms >= 0.0 && min_ms <= max_ms) {
+                    return Err(format!(
+                        "uniform min_ms {min_ms} must be between 0 and max_ms {max_ms}"
+                    ));
+                }
+                Ok(())
+            }
+            ArrivalModel::Fixed { rate_per_sec } | ArrivalModel::Poisson { rate_per_sec } => {
+                positive("rate_per_sec", rate_per_sec)
+            }
+            ArrivalModel::Hawkes {
+                base_rate_per_sec,
+                excitation_per_sec,
+                decay_per_sec,
+            } => {
+                positive("base_rate_per_sec", base_rate_per_sec)?;
+                positive("decay_per_sec", decay_per_sec)?;
+                // each quote must on average trigger less than one more quote or the rate grows without limit
+                if !(excitation_per_sec >= 0.0 && excitation_per_sec < decay_per_sec) {
+                    return Err(format!(
+                        "hawkes excitation_per_sec {excitation_per_sec} must be at least 0 and less than decay_per_sec {decay_per_sec}"
+                    ));
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl QuietPeriods {
+    pub fn validate(&self) -> Result<(), String> {
+        positive("mean_gap_secs", self.mean_gap_secs)?;
+        positive("mean_duration_secs", self.mean_duration_secs)
+    }
+}
+
+// the state of a liquidity provider's arrivals between quotes
+#[derive(Debug, Clone)]
+pub struct ArrivalProcess {
+    arrival: ArrivalModel,
+    quiet_periods: Option<QuietPeriods>,
+    // rate above the base rate (per second) straight after the previous quote of a Hawkes process
+    excitation: f64,
+    // quoting time (nanoseconds) left before the next quiet period
+    until_quiet: Option<f64>,
+}
+
+impl ArrivalProcess {
+    pub fn new(arrival: ArrivalModel, quiet_periods: Option<QuietPeriods>) -> Self {
+        ArrivalProcess {
+            arrival,
+            quiet_periods,
+            excitation: 0.0,
+            until_quiet: None,
+        }
+    }
+
+    pub fn next_interval(&mut self, rng: &mut impl Rng) -> u64 {
+        // nanoseconds of simulated time until the next quote, at least 1
+        let mut interval = match self.arrival {
+            ArrivalModel::Uniform { min_ms, max_ms } => {
+                rng.random_range(min_ms..=max_ms) * 1_000_000.0
+            }
+            ArrivalModel::Fixed { rate_per_sec } => NANOS_PER_SEC / rate_per_sec,
+            ArrivalModel::Poisson { rate_per_sec } => exponential(rate_per_sec, rng),
+            ArrivalModel::Hawkes {
+                base_rate_per_sec,
+                excitation_per_sec,
+                decay_per_sec,
+            } => self.hawkes_interval(base_rate_per_sec, excitation_per_sec, decay_per_sec, rng),
+        };
+
+        if let Some(quiet_periods) = self.quiet_periods {
+            let until_quiet = *self
+                .until_quiet
+                .get_or_insert_with(|| exponential(1.0 / quiet_periods.mean_gap_secs, rng));
+            if interval >= until_quiet {
+                // the quote is held back until the end of the quiet period
+                interval += exponential(1.0 / quiet_periods.mean_duration_secs, rng);
+                self.until_quiet = None;
+            } else {
+                self.until_quiet = Some(until_quiet - interval);
+            }
+        }
+
+        (interval as u64).max(1)
+    }
+
+    fn hawkes_interval(
+        &mut self,
+        base_rate: f64,
+        excitation: f64,
+        decay: f64,
+        rng: &mut impl Rng,
+    ) -> f64 {
+        // Ogata thinning - the rate only falls between quotes so the rate now is an upper bound
+        // for proposing the next quote, which is then accepted with the ratio of the rates
+        let mut elapsed = 0.0;
+        loop {
+            let upper_rate = base_rate + self.excitation * (-decay * elapsed).exp();
+            elapsed += exponential(upper_rate, rng) / NANOS_PER_SEC;
+            let rate = base_rate + self.excitation * (-decay * elapsed).exp();
+            if rng.random_bool((rate / upper_rate).min(1.0)) {
+                self.excitation = self.excitation * (-decay * elapsed).exp() + excitation;
+                return elapsed * NANOS_PER_SEC;
+            }
+        }
+    }
+}
+
+fn positive(name: &str, value: f64) -> Result<(), String> {
+    if value > 0.0 && value.is_finite() {
+        Ok(())
+    } else {
+        Err(format!("{name} {value} must be greater than 0"))
+    }
+}
+
+fn exponential(rate_per_sec: f64, rng: &mut impl Rng) -> f64 {
+    // nanoseconds until an event of a Poisson process with this rate
+    match Exp::new(rate_per_sec) {
+        Ok(exp) => exp.sample(rng) * NANOS_PER_SEC,
+        Err(_) => 0.0,
+    }
+}
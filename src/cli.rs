@@ -0,0 +1,164 @@
+//! # FX Simulator and Aggregator - fx_sim_agg_gui
+//!
+//! `cli.rs` defines the command line interface. The `gui` subcommand (the default) shows the aggregated books in a window,
+//! `headless` runs the simulator and aggregator without a display, `replay` feeds a recorded log into the aggregator
+//! and `validate-config` only checks the config file.
+use crate::clock::ClockMode;
+use crate::config::{DEFAULT_CONFIG_PATH, Settings};
+use crate::replay::ReplaySettings;
+use clap::{Args, Parser, Subcommand};
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "fx_sim_agg_gui",
+    about = "Simulates FX market data streams and aggregates them into real-time books of buys and sells"
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run the simulator and show the aggregated books in a window (default)
+    Gui(RunArgs),
+    /// Run the simulator and aggregator without a window, printing the books to the terminal
+    Headless(RunArgs),
+    /// Replay a recorded FIX or pipe delimited market data log into the aggregator
+    Replay(ReplayArgs),
+    /// Check the config file and report any errors with their line and column
+    ValidateConfig(ConfigArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct ConfigArgs {
+    /// Path of the YAML config file
+    #[arg(short, long, default_value = DEFAULT_CONFIG_PATH)]
+    pub config: String,
+}
+
+#[derive(Debug, Default, Args)]
+pub struct RunArgs {
+    #[command(flatten)]
+    pub config_args: ConfigArgs,
+    /// Path of the FIX log, overriding the config file
+    #[arg(long)]
+    pub fix_log: Option<String>,
+    /// Path of the log4rs config, overriding the config file
+    #[arg(long)]
+    pub log_config: Option<String>,
+    /// Path of a file to record every aggregated book update to, overriding the config file
+    #[arg(long)]
+    pub book_log: Option<String>,
+    /// Stop after this many seconds of simulated time, overriding the config file
+    #[arg(short, long)]
+    pub duration: Option<u64>,
+    /// Run simulated time this many times faster than real time, overriding the config file
+    #[arg(long, value_parser = parse_speed, conflicts_with = "afap")]
+    pub speed: Option<f64>,
+    /// Simulate without waiting between quotes, overriding the config file
+    #[arg(long)]
+    pub afap: bool,
+    /// Seed for a reproducible simulation in virtual time, overriding the config file
+    #[arg(short, long)]
+    pub seed: Option<u64>,
+}
+
+#[derive(Debug, Args)]
+pub struct ReplayArgs {
+    /// Path of the recorded log to replay
+    pub file: String,
+    #[command(flatten)]
+    pub config_args: ConfigArgs,
+    /// Path of the log4rs config, overriding the config file
+    #[arg(long)]
+    pub log_config: Option<String>,
+    /// Path of a file to record every aggregated book update to, overriding the config file
+    #[arg(long)]
+    pub book_log: Option<String>,
+    /// Replay this many times faster than the original timing
+    #[arg(long, value_parser = parse_speed, conflicts_with_all = ["afap", "step"])]
+    pub speed: Option<f64>,
+    /// Replay without waiting between messages
+    #[arg(long, conflicts_with = "step")]
+    pub afap: bool,
+    /// Wait for Enter in the terminal before replaying each message
+    #[arg(long)]
+    pub step: bool,
+    /// Show the aggregated books in a window rather than only in the terminal
+    #[arg(long)]
+    pub gui: bool,
+}
+
+impl Default for ConfigArgs {
+    fn default() -> Self {
+        ConfigArgs {
+            config: String::from(DEFAULT_CONFIG_PATH),
+        }
+    }
+}
+
+impl Cli {
+    pub fn command(self) -> Command {
+        // no subcommand starts the GUI with the default config
+        self.command
+            .unwrap_or_else(|| Command::Gui(RunArgs::default()))
+    }
+}
+
+impl RunArgs {
+    pub fn apply(&self, settings: &mut Settings) {
+        // command line flags take precedence over the config file settings
+        if let Some(fix_log) = &self.fix_log {
+            settings.logs.fix_log = fix_log.clone();
+        }
+        if let Some(log_config) = &self.log_config {
+            settings.logs.log_config = log_config.clone();
+        }
+        if let Some(book_log) = &self.book_log {
+            settings.logs.book_log = Some(book_log.clone());
+        }
+        if let Some(duration) = self.duration {
+            settings.run_duration_secs = Some(duration);
+        }
+        if let Some(seed) = self.seed {
+            settings.seed = Some(seed);
+        }
+        if let Some(speed) = self.speed {
+            settings.clock = ClockMode::Accelerated { speed };
+        }
+        if self.afap {
+            settings.clock = ClockMode::AsFastAsPossible;
+        }
+    }
+}
+
+impl ReplayArgs {
+    pub fn apply(&self, settings: &mut Settings) {
+        // messages are replayed with their original timing unless asked otherwise, whatever the config file's clock
+        if let Some(log_config) = &self.log_config {
+            settings.logs.log_config = log_config.clone();
+        }
+        if let Some(book_log) = &self.book_log {
+            settings.logs.book_log = Some(book_log.clone());
+        }
+        settings.clock = match self.speed {
+            Some(speed) => ClockMode::Accelerated { speed },
+            None if self.afap => ClockMode::AsFastAsPossible,
+            None => ClockMode::RealTime,
+        };
+        // the replay ends with the log
+        settings.run_duration_secs = None;
+        settings.replay = Some(ReplaySettings {
+            file: self.file.clone(),
+            step: self.step,
+        });
+    }
+}
+
+fn parse_speed(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(speed) if speed > 0.0 && speed.is_finite() => Ok(speed),
+        _ => Err(format!("{value} is not a speed greater than 0")),
+    }
+}
@@ -0,0 +1,114 @@
+//! # FX Simulator and Aggregator - fx_sim_agg_gui
+//!
+//! `clock.rs` provides the simulation clock. Simulated (virtual) time only moves forward when the simulator advances it to
+//! the time of the next quote, and the clock mode sets how long to wait in real time for each step - in real time,
+//! N times faster than real time or not at all. Seeded simulations start from a fixed instant so their timestamps are reproducible.
+use serde::Deserialize;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::task::yield_now;
+use tokio::time::{Instant, sleep_until};
+
+// 2025-01-01 00:00:00 UTC in nanoseconds since the unix epoch
+pub const VIRTUAL_START_TIMESTAMP: u64 = 1_735_689_600_000_000_000;
+
+pub const NANOS_PER_MILLI: u64 = 1_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ClockMode {
+    // one simulated second takes one real second
+    #[default]
+    RealTime,
+    // simulated time runs speed times faster than real time
+    Accelerated {
+        speed: f64,
+    },
+    // no waiting between quotes at all
+    AsFastAsPossible,
+}
+
+impl ClockMode {
+    pub fn real_duration(&self, virtual_duration: u64) -> Option<Duration> {
+        // real time to wait for a step of virtual_duration nanoseconds of simulated time
+        match *self {
+            ClockMode::RealTime => Some(Duration::from_nanos(virtual_duration)),
+            ClockMode::Accelerated { speed } => {
+                Some(Duration::from_nanos(virtual_duration).div_f64(speed))
+            }
+            ClockMode::AsFastAsPossible => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VirtualClock {
+    // nanoseconds since the unix epoch
+    now: u64,
+}
+
+impl Default for VirtualClock {
+    fn default() -> Self {
+        VirtualClock::new(VIRTUAL_START_TIMESTAMP)
+    }
+}
+
+impl VirtualClock {
+    pub fn new(start: u64) -> Self {
+        VirtualClock { now: start }
+    }
+
+    pub fn now(&self) -> u64 {
+        self.now
+    }
+
+    pub fn advance_to(&mut self, timestamp: u64) {
+        // virtual time never runs backwards
+        self.now = self.now.max(timestamp);
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SimClock {
+    mode: ClockMode,
+    virtual_clock: VirtualClock,
+    // virtual and real time the simulation started so waits are measured from the start rather than
+    // the previous step, otherwise timer resolution slows down thousands of quotes a second
+    start: u64,
+    real_start: Instant,
+}
+
+impl SimClock {
+    pub fn new(mode: ClockMode, start: u64) -> Self {
+        SimClock {
+            mode,
+            virtual_clock: VirtualClock::new(start),
+            start,
+            real_start: Instant::now(),
+        }
+    }
+
+    pub fn now(&self) -> u64 {
+        self.virtual_clock.now()
+    }
+
+    pub async fn sleep_until(&mut self, timestamp: u64) {
+        // wait until the real time equivalent of timestamp in simulated time
+        self.virtual_clock.advance_to(timestamp);
+        let virtual_duration = self.virtual_clock.now() - self.start;
+        match self.mode.real_duration(virtual_duration) {
+            Some(duration) => sleep_until(self.real_start + duration).await,
+            // still hand control back to the runtime so other tasks can run
+            None => yield_now().await,
+        }
+    }
+}
+
+pub fn system_timestamp() -> u64 {
+    // wall clock time in nanoseconds since the unix epoch
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos()
+        .try_into()
+        .unwrap()
+}
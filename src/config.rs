@@ -0,0 +1,639 @@
+//! # FX Simulator and Aggregator - fx_sim_agg_gui
+//!
+//! `config.rs` reads the YAML configuration file of global settings, currency pair and liquidity provider sections.
+//! The file is validated as it is read so any error is reported with its line and column.
+use crate::AppError;
+pub use crate::arrival::{ArrivalModel, QuietPeriods};
+use crate::clock::ClockMode;
+use crate::price::{Price, pip_decimals};
+pub use crate::price_model::PriceModel;
+use crate::replay::ReplaySettings;
+pub use crate::risk::RiskSettings;
+use crate::router::{RouterSettings, RoutingPolicy};
+pub use crate::venue::VenueConfig;
+use serde::de::{DeserializeSeed, Error, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+pub const DEFAULT_CONFIG_PATH: &str = "resources/config.yaml";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tier {
+    // volume in millions
+    pub volume: i32,
+    pub markup: Price,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdateType {
+    // a MarketDataSnapshotFullRefresh (35=W) on every tick
+    #[default]
+    Snapshot,
+    // a full refresh followed by MarketDataIncrementalRefresh (35=X) updates
+    Incremental,
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub liquidity_provider: String,
+    pub currency_pair: String,
+    pub buy_price: Price,
+    pub spread: Price,
+    pub tiers: Vec<Tier>,
+    pub run_iterations: i32,
+    pub update_type: UpdateType,
+    pub price_model: PriceModel,
+    // the shared mid price of the currency pair, when it has one, which the liquidity provider quotes around
+    pub market: Option<MarketConfig>,
+    // standard deviation (pips) of the random noise added to every quote around the shared mid price
+    pub noise_pips: f64,
+    // how far (milliseconds) behind the shared mid price the liquidity provider's quotes are
+    pub latency_ms: u64,
+    // constant offset (pips) of the liquidity provider's quotes from the shared mid price
+    pub skew_pips: f64,
+    // how often the liquidity provider quotes
+    pub arrival: ArrivalModel,
+    pub quiet_periods: Option<QuietPeriods>,
+    // how the liquidity provider fills or rejects child orders
+    pub venue: VenueConfig,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarketConfig {
+    pub mid_price: f64,
+    // the mid price moves by the price model every step_ms of simulated time
+    pub step_ms: u64,
+    pub price_model: PriceModel,
+    // chance of a liquidity provider quote being mispriced by its spread plus crossing_pips so it crosses the book
+    pub crossing_probability: f64,
+    pub crossing_pips: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct LogSettings {
+    pub fix_log: String,
+    pub log_config: String,
+    // records every aggregated book update when set
+    pub book_log: Option<String>,
+}
+
+impl Default for LogSettings {
+    fn default() -> Self {
+        LogSettings {
+            fix_log: String::from("logs/fix.log"),
+            log_config: String::from("logging_config.yaml"),
+            book_log: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct Settings {
+    #[serde(deserialize_with = "whole_pips")]
+    pub min_spread_pips: f64,
+    #[serde(deserialize_with = "price_model")]
+    pub price_model: PriceModel,
+    #[serde(deserialize_with = "arrival_model")]
+    pub arrival: ArrivalModel,
+    #[serde(deserialize_with = "optional_quiet_periods")]
+    pub quiet_periods: Option<QuietPeriods>,
+    #[serde(deserialize_with = "clock_mode")]
+    pub clock: ClockMode,
+    pub logs: LogSettings,
+    #[serde(deserialize_with = "router_settings")]
+    pub router: RouterSettings,
+    #[serde(deserialize_with = "venue_config")]
+    pub venue: VenueConfig,
+    #[serde(deserialize_with = "risk_settings")]
+    pub risk: RiskSettings,
+    // stop the simulation after this many seconds of simulated time rather than when every stream completes
+    pub run_duration_secs: Option<u64>,
+    // seed of a reproducible simulation in virtual time
+    pub seed: Option<u64>,
+    // replay a recorded log instead of simulating, set from the command line only
+    #[serde(skip)]
+    pub replay: Option<ReplaySettings>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            min_spread_pips: 6.0,
+            price_model: PriceModel::default(),
+            arrival: ArrivalModel::default(),
+            quiet_periods: None,
+            clock: ClockMode::default(),
+            logs: LogSettings::default(),
+            router: RouterSettings::default(),
+            venue: VenueConfig::default(),
+            risk: RiskSettings::default(),
+            run_duration_secs: None,
+            seed: None,
+            replay: None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TierSection {
+    #[serde(deserialize_with = "positive_volume")]
+    volume: i32,
+    #[serde(deserialize_with = "whole_pips")]
+    markup_pips: f64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LiquidityProviderSection {
+    #[serde(deserialize_with = "non_empty")]
+    name: String,
+    #[serde(deserialize_with = "currency_pair")]
+    currency_pair: String,
+    // optional when quoting around the shared mid price of the currency pair
+    #[serde(default, deserialize_with = "optional_positive")]
+    buy_price: Option<f64>,
+    #[serde(deserialize_with = "whole_pips")]
+    spread_pips: f64,
+    #[serde(deserialize_with = "tiers")]
+    tiers: Vec<TierSection>,
+    #[serde(deserialize_with = "positive_volume")]
+    run_iterations: i32,
+    #[serde(default)]
+    update_type: UpdateType,
+    // overrides the price model of the global settings
+    #[serde(default, deserialize_with = "optional_price_model")]
+    price_model: Option<PriceModel>,
+    #[serde(default, deserialize_with = "non_negative")]
+    noise_pips: f64,
+    #[serde(default)]
+    latency_ms: u64,
+    #[serde(default)]
+    skew_pips: f64,
+    // override the arrival model and quiet periods of the global settings
+    #[serde(default, deserialize_with = "optional_arrival_model")]
+    arrival: Option<ArrivalModel>,
+    #[serde(default, deserialize_with = "optional_quiet_periods")]
+    quiet_periods: Option<QuietPeriods>,
+    // overrides the venue of the global settings
+    #[serde(default, deserialize_with = "optional_venue_config")]
+    venue: Option<VenueConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CurrencyPairSection {
+    #[serde(deserialize_with = "currency_pair")]
+    currency_pair: String,
+    #[serde(deserialize_with = "positive")]
+    mid_price: f64,
+    #[serde(default = "default_step_ms", deserialize_with = "positive_millis")]
+    step_ms: u64,
+    // defaults to the price model of the global settings
+    #[serde(default, deserialize_with = "optional_price_model")]
+    price_model: Option<PriceModel>,
+    #[serde(default, deserialize_with = "probability")]
+    crossing_probability: f64,
+    #[serde(default, deserialize_with = "non_negative")]
+    crossing_pips: f64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    #[serde(default)]
+    settings: Settings,
+    #[serde(default, deserialize_with = "currency_pairs")]
+    currency_pairs: Vec<CurrencyPairSection>,
+    #[serde(deserialize_with = "liquidity_providers")]
+    liquidity_providers: Vec<LiquidityProviderSection>,
+}
+
+pub fn get_configs<P: AsRef<Path>>(
+    file_path: P,
+    configs: &mut Vec<Config>,
+) -> Result<Settings, AppError> {
+    let contents: String = fs::read_to_string(file_path)?;
+    parse_configs(&contents, configs)
+}
+
+pub fn parse_configs(contents: &str, configs: &mut Vec<Config>) -> Result<Settings, AppError> {
+    let config_file: ConfigFile = serde_yaml::from_str(contents)?;
+    let settings = config_file.settings;
+
+    for (index, section) in config_file.currency_pairs.iter().enumerate() {
+        // the mid price is held to the pip precision of the currency pair, so a finer mid price would be rounded away
+        if !is_whole_pips(section.mid_price, &section.currency_pair) {
+            return Err(section_error(
+                contents,
+                "currency_pairs",
+                index,
+                format!(
+                    "mid_price {} has more decimal places than a {} pip",
+                    section.mid_price, section.currency_pair
+                ),
+            ));
+        }
+    }
+
+    for (index, section) in config_file.liquidity_providers.into_iter().enumerate() {
+        // prices, spreads and markups are held to the pip precision of the currency pair
+        let decimals = pip_decimals(&section.currency_pair);
+        let spread = Price::from_pips(section.spread_pips, decimals);
+        let market = config_file
+            .currency_pairs
+            .iter()
+            .find(|currency_pair| currency_pair.currency_pair == section.currency_pair)
+            .map(|currency_pair| MarketConfig {
+                mid_price: currency_pair.mid_price,
+                step_ms: currency_pair.step_ms,
+                price_model: currency_pair.price_model.unwrap_or(settings.price_model),
+                crossing_probability: currency_pair.crossing_probability,
+                crossing_pips: currency_pair.crossing_pips,
+            });
+        // errors between a liquidity provider and its currency pair are reported at the liquidity provider's section
+        let invalid_section =
+            |reason: String| section_error(contents, "liquidity_providers", index, reason);
+        if market.is_some() && section.price_model.is_some() {
+            return Err(invalid_section(format!(
+                "{} has a price_model but quotes around the {} mid price",
+                section.name, section.currency_pair
+            )));
+        }
+        // a liquidity provider quoting around a shared mid price starts with its buy price half a spread below it
+        let buy_price = match (section.buy_price, &market) {
+            (Some(buy_price), _) if !is_whole_pips(buy_price, &section.currency_pair) => {
+                return Err(invalid_section(format!(
+                    "buy_price {buy_price} has more decimal places than a {} pip",
+                    section.currency_pair
+                )));
+            }
+            (Some(buy_price), _) => Price::from_f64(buy_price, decimals),
+            (None, Some(market)) => {
+                Price::from_f64(market.mid_price, decimals)
+                    - Price::new(spread.ticks() / 2, decimals)
+            }
+            (None, None) => {
+                return Err(invalid_section(format!(
+                    "{} has no buy_price and {} has no mid_price",
+                    section.name, section.currency_pair
+                )));
+            }
+        };
+        let tiers = section
+            .tiers
+            .iter()
+            .map(|tier| Tier {
+                volume: tier.volume,
+                markup: Price::from_pips(tier.markup_pips, decimals),
+            })
+            .collect();
+
+        configs.push(Config {
+            liquidity_provider: section.name,
+            currency_pair: section.currency_pair,
+            buy_price,
+            spread,
+            tiers,
+            run_iterations: section.run_iterations,
+            update_type: section.update_type,
+            price_model: section.price_model.unwrap_or(settings.price_model),
+            market,
+            noise_pips: section.noise_pips,
+            latency_ms: section.latency_ms,
+            skew_pips: section.skew_pips,
+            arrival: section.arrival.unwrap_or(settings.arrival),
+            quiet_periods: section.quiet_periods.or(settings.quiet_periods),
+            venue: section.venue.unwrap_or(settings.venue),
+        });
+    }
+
+    Ok(settings)
+}
+
+fn is_whole_pips(price: f64, currency_pair: &str) -> bool {
+    // a price from the config file is exact at the pip precision of its currency pair
+    Price::from_f64(price, pip_decimals(currency_pair)).to_f64() == price
+}
+
+fn section_error(contents: &str, list: &'static str, index: usize, reason: String) -> AppError {
+    // an error found between sections once the file has been read, reported with the line and column of the
+    // index'th section of the list by reading the file again up to that section and failing there
+    let section_error = SectionError {
+        list,
+        index,
+        reason: reason.clone(),
+    };
+    match section_error.deserialize(serde_yaml::Deserializer::from_str(contents)) {
+        Err(e) => AppError::ConfigFile(e),
+        Ok(()) => AppError::InvalidConfig(reason),
+    }
+}
+
+// finds the list of sections in the config file, then the section, and fails with the reason
+struct SectionError {
+    list: &'static str,
+    index: usize,
+    reason: String,
+}
+
+struct SectionErrorList(SectionError);
+
+struct SectionErrorAt(String);
+
+impl<'de> DeserializeSeed<'de> for SectionError {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for SectionError {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a config file")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        while let Some(key) = map.next_key::<String>()? {
+            if key == self.list {
+                return map.next_value_seed(SectionErrorList(self));
+            }
+            map.next_value::<IgnoredAny>()?;
+        }
+        Ok(())
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for SectionErrorList {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for SectionErrorList {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a list of {}", self.0.list)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        for _ in 0..self.0.index {
+            seq.next_element::<IgnoredAny>()?;
+        }
+        seq.next_element_seed(SectionErrorAt(self.0.reason))?;
+        Ok(())
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for SectionErrorAt {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        // failing inside the section's own mapping gives the error the section's position
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for SectionErrorAt {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a config section")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, _map: A) -> Result<(), A::Error> {
+        Err(A::Error::custom(self.0))
+    }
+}
+
+// validation of individual fields while deserializing so errors carry the line and column of the field
+
+fn non_empty<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    if value.trim().is_empty() {
+        return Err(D::Error::custom("must not be empty"));
+    }
+    Ok(String::from(value.trim()))
+}
+
+fn currency_pair<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    let value = value.trim();
+    let is_currency =
+        |currency: &str| currency.len() == 3 && currency.chars().all(|c| c.is_ascii_uppercase());
+    match value.split_once("/") {
+        Some((base, quote)) if is_currency(base) && is_currency(quote) => Ok(String::from(value)),
+        _ => Err(D::Error::custom(format!(
+            "currency pair {value} is not of the form USD/EUR"
+        ))),
+    }
+}
+
+fn positive<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    let value = f64::deserialize(deserializer)?;
+    if value <= 0.0 {
+        return Err(D::Error::custom(format!("{value} must be greater than 0")));
+    }
+    Ok(value)
+}
+
+fn optional_positive<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<f64>, D::Error> {
+    positive(deserializer).map(Some)
+}
+
+fn probability<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    let value = f64::deserialize(deserializer)?;
+    if !(0.0..=1.0).contains(&value) {
+        return Err(D::Error::custom(format!(
+            "{value} is not a probability between 0 and 1"
+        )));
+    }
+    Ok(value)
+}
+
+fn default_step_ms() -> u64 {
+    1000
+}
+
+fn positive_millis<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    let value = u64::deserialize(deserializer)?;
+    if value == 0 {
+        return Err(D::Error::custom("0 must be greater than 0"));
+    }
+    Ok(value)
+}
+
+fn non_negative<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    let value = f64::deserialize(deserializer)?;
+    if value < 0.0 {
+        return Err(D::Error::custom(format!("{value} must not be negative")));
+    }
+    Ok(value)
+}
+
+fn whole_pips<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    // spreads and markups are whole ticks at the pip precision of the currency pair
+    let value = non_negative(deserializer)?;
+    if value.fract() != 0.0 {
+        return Err(D::Error::custom(format!(
+            "{value} must be a whole number of pips"
+        )));
+    }
+    Ok(value)
+}
+
+fn positive_volume<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i32, D::Error> {
+    let value = i32::deserialize(deserializer)?;
+    if value <= 0 {
+        return Err(D::Error::custom(format!("{value} must be greater than 0")));
+    }
+    Ok(value)
+}
+
+fn tiers<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<TierSection>, D::Error> {
+    let tiers = Vec::<TierSection>::deserialize(deserializer)?;
+    if tiers.is_empty() {
+        return Err(D::Error::custom("at least one volume tier is required"));
+    }
+    for (index, tier) in tiers.iter().enumerate() {
+        if tiers[..index]
+            .iter()
+            .any(|other| other.volume == tier.volume)
+        {
+            return Err(D::Error::custom(format!(
+                "tier volume {} is listed more than once",
+                tier.volume
+            )));
+        }
+    }
+    Ok(tiers)
+}
+
+fn liquidity_providers<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<LiquidityProviderSection>, D::Error> {
+    let sections = Vec::<LiquidityProviderSection>::deserialize(deserializer)?;
+    if sections.is_empty() {
+        return Err(D::Error::custom(
+            "at least one liquidity provider is required",
+        ));
+    }
+    for (index, section) in sections.iter().enumerate() {
+        if sections[..index]
+            .iter()
+            .any(|other| other.name == section.name && other.currency_pair == section.currency_pair)
+        {
+            return Err(D::Error::custom(format!(
+                "liquidity provider {} is listed more than once for {}",
+                section.name, section.currency_pair
+            )));
+        }
+    }
+    Ok(sections)
+}
+
+fn currency_pairs<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<CurrencyPairSection>, D::Error> {
+    let sections = Vec::<CurrencyPairSection>::deserialize(deserializer)?;
+    for (index, section) in sections.iter().enumerate() {
+        if sections[..index]
+            .iter()
+            .any(|other| other.currency_pair == section.currency_pair)
+        {
+            return Err(D::Error::custom(format!(
+                "currency pair {} is listed more than once",
+                section.currency_pair
+            )));
+        }
+    }
+    Ok(sections)
+}
+
+fn price_model<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PriceModel, D::Error> {
+    let price_model = PriceModel::deserialize(deserializer)?;
+    price_model.validate().map_err(D::Error::custom)?;
+    Ok(price_model)
+}
+
+fn optional_price_model<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<PriceModel>, D::Error> {
+    price_model(deserializer).map(Some)
+}
+
+fn arrival_model<'de, D: Deserializer<'de>>(deserializer: D) -> Result<ArrivalModel, D::Error> {
+    let arrival_model = ArrivalModel::deserialize(deserializer)?;
+    arrival_model.validate().map_err(D::Error::custom)?;
+    Ok(arrival_model)
+}
+
+fn optional_arrival_model<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<ArrivalModel>, D::Error> {
+    arrival_model(deserializer).map(Some)
+}
+
+fn optional_quiet_periods<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<QuietPeriods>, D::Error> {
+    let quiet_periods = QuietPeriods::deserialize(deserializer)?;
+    quiet_periods.validate().map_err(D::Error::custom)?;
+    Ok(Some(quiet_periods))
+}
+
+fn router_settings<'de, D: Deserializer<'de>>(deserializer: D) -> Result<RouterSettings, D::Error> {
+    let router_settings = RouterSettings::deserialize(deserializer)?;
+    if let Some(min_fill_volume) = router_settings.min_fill_volume
+        && min_fill_volume <= 0
+    {
+        return Err(D::Error::custom(format!(
+            "min_fill_volume {min_fill_volume} must be greater than 0"
+        )));
+    }
+    if let RoutingPolicy::Preference { ranking } = &router_settings.policy
+        && ranking.is_empty()
+    {
+        return Err(D::Error::custom("preference ranking must not be empty"));
+    }
+    Ok(router_settings)
+}
+
+fn venue_config<'de, D: Deserializer<'de>>(deserializer: D) -> Result<VenueConfig, D::Error> {
+    let venue_config = VenueConfig::deserialize(deserializer)?;
+    venue_config.validate().map_err(D::Error::custom)?;
+    Ok(venue_config)
+}
+
+fn optional_venue_config<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<VenueConfig>, D::Error> {
+    venue_config(deserializer).map(Some)
+}
+
+fn risk_settings<'de, D: Deserializer<'de>>(deserializer: D) -> Result<RiskSettings, D::Error> {
+    let risk_settings = RiskSettings::deserialize(deserializer)?;
+    risk_settings.validate().map_err(D::Error::custom)?;
+    Ok(risk_settings)
+}
+
+fn clock_mode<'de, D: Deserializer<'de>>(deserializer: D) -> Result<ClockMode, D::Error> {
+    let clock_mode = ClockMode::deserialize(deserializer)?;
+    match clock_mode {
+        ClockMode::Accelerated { speed } if !(speed > 0.0 && speed.is_finite()) => Err(
+            D::Error::custom(format!("clock speed {speed} must be greater than 0")),
+        ),
+        _ => Ok(clock_mode),
+    }
+}
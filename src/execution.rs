@@ -0,0 +1,240 @@
+//! # FX Simulator and Aggregator - fx_sim_agg_gui
+//!
+//! `execution.rs` executes market and limit orders against an aggregated `FxBook`. An order sweeps the price levels of the
+//! opposite side of the book from the top, filling the liquidity providers at each level in the order their quotes arrived,
+//! and the volume it takes is removed from the book. Orders are immediate-or-cancel - anything not filled straight away is cancelled.
+use crate::AppError;
+use crate::aggregator::{BookSide, FxBook, get_book_side};
+use crate::price::Price;
+use log::info;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderSide {
+    Buy,
+    Sell,
+}
+
+impl OrderSide {
+    pub fn book_side(&self) -> BookSide {
+        // a buy order lifts the sell quotes (offers) and a sell order hits the buy quotes (bids)
+        match self {
+            Self::Buy => BookSide::Sell,
+            Self::Sell => BookSide::Buy,
+        }
+    }
+}
+
+impl Display for OrderSide {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Buy => f.write_str("Buy"),
+            Self::Sell => f.write_str("Sell"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OrderType {
+    // fill at any price
+    Market,
+    // fill at the limit price or better - no higher for a buy and no lower for a sell
+    Limit(Price),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Order {
+    pub currency_pair: String,
+    pub side: OrderSide,
+    // volume in millions
+    pub volume: i32,
+    pub order_type: OrderType,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fill {
+    pub liquidity_provider: String,
+    pub price: Price,
+    pub volume: i32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Execution {
+    pub order: Order,
+    pub fills: Vec<Fill>,
+    // time of the book the order executed against
+    pub timestamp: u64,
+}
+
+impl Execution {
+    pub fn filled_volume(&self) -> i32 {
+        self.fills.iter().map(|fill| fill.volume).sum()
+    }
+
+    pub fn unfilled_volume(&self) -> i32 {
+        self.order.volume - self.filled_volume()
+    }
+
+    pub fn vwap(&self) -> Option<f64> {
+        // volume weighted average price of the fills, None when nothing filled
+        let filled_volume = self.filled_volume();
+        if filled_volume == 0 {
+            return None;
+        }
+        let notional: f64 = self
+            .fills
+            .iter()
+            .map(|fill| fill.price.to_f64() * f64::from(fill.volume))
+            .sum();
+        Some(notional / f64::from(filled_volume))
+    }
+}
+
+impl Display for Execution {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}M of {}M {}",
+            self.order.side,
+            self.filled_volume(),
+            self.order.volume,
+            self.order.currency_pair
+        )?;
+        if let Some(vwap) = self.vwap() {
+            write!(f, " at VWAP {vwap:.6}")?;
+        }
+        for fill in &self.fills {
+            write!(
+                f,
+                " | {}: {}M @ {}",
+                fill.liquidity_provider, fill.volume, fill.price
+            )?;
+        }
+        Ok(())
+    }
+}
+
+pub fn validate_order(fx_book: &FxBook, order: &Order) -> Result<(), AppError> {
+    if order.volume <= 0 {
+        return Err(AppError::InvalidOrder(format!(
+            "volume {}M must be greater than 0",
+            order.volume
+        )));
+    }
+    if order.currency_pair != fx_book.currency_pair {
+        return Err(AppError::InvalidOrder(format!(
+            "{} order sent to the {} book",
+            order.currency_pair, fx_book.currency_pair
+        )));
+    }
+    Ok(())
+}
+
+pub fn match_order(fx_book: &FxBook, order: &Order) -> Vec<Fill> {
+    // the fills an order would get, without changing the book, sweeping the levels from the top within any limit
+    // price and filling the liquidity providers at each level in the order their quotes arrived
+    let levels = match order.side {
+        OrderSide::Buy => &fx_book.sell_book,
+        OrderSide::Sell => &fx_book.buy_book,
+    };
+    let mut fills: Vec<Fill> = Vec::new();
+    let mut remaining = order.volume;
+    for level in levels {
+        if remaining <= 0 {
+            break;
+        }
+        let within_limit = match order.order_type {
+            OrderType::Market => true,
+            OrderType::Limit(limit) => match order.side {
+                OrderSide::Buy => level.price <= limit,
+                OrderSide::Sell => level.price >= limit,
+            },
+        };
+        if !within_limit {
+            break;
+        }
+        for lp_vol in &level.lp_vol {
+            if remaining == 0 {
+                break;
+            }
+            let volume = remaining.min(lp_vol.1);
+            fills.push(Fill {
+                liquidity_provider: lp_vol.0.clone(),
+                price: level.price,
+                volume,
+            });
+            remaining -= volume;
+        }
+    }
+    fills
+}
+
+pub fn execute_order(fx_book: &mut FxBook, order: &Order) -> Result<Execution, AppError> {
+    validate_order(fx_book, order)?;
+    let fills = match_order(fx_book, order);
+    let book_side = order.side.book_side();
+    for fill in &fills {
+        take_lp_volume(
+            fx_book,
+            book_side,
+            &fill.liquidity_provider,
+            fill.price,
+            fill.volume,
+        );
+    }
+    let execution = Execution {
+        order: order.clone(),
+        fills,
+        timestamp: fx_book.timestamp,
+    };
+    info!("executed {execution}");
+    Ok(execution)
+}
+
+pub fn take_lp_volume(
+    fx_book: &mut FxBook,
+    book_side: BookSide,
+    liquidity_provider: &str,
+    price: Price,
+    volume: i32,
+) -> i32 {
+    // take up to volume from the liquidity provider's quotes at the price in the order they arrived, removing
+    // fully lifted quotes and any price level left empty, and return the volume taken. The filled volume comes off
+    // the liquidity provider's own book too, by MDEntryID, so its next refresh replaces what is left of each quote.
+    // Fully lifted quotes are kept there with no volume until the liquidity provider updates them, so an
+    // incremental change or delete still finds its MDEntryID
+    let fx_book_side = get_book_side(fx_book, book_side);
+    let mut taken: Vec<(String, i32)> = Vec::new();
+    let mut remaining = volume;
+    if let Some(index) = fx_book_side.iter().position(|level| level.price == price) {
+        let level = &mut fx_book_side[index];
+        for lp_vol in level
+            .lp_vol
+            .iter_mut()
+            .filter(|lp_vol| lp_vol.0 == liquidity_provider)
+        {
+            if remaining == 0 {
+                break;
+            }
+            let quote_volume = remaining.min(lp_vol.1);
+            lp_vol.1 -= quote_volume;
+            remaining -= quote_volume;
+            taken.push((lp_vol.2.clone(), quote_volume));
+        }
+        level.lp_vol.retain(|lp_vol| lp_vol.1 > 0);
+        level.volume = level.lp_vol.iter().map(|lp_vol| lp_vol.1).sum();
+        if level.lp_vol.is_empty() {
+            fx_book_side.remove(index);
+        }
+    }
+
+    if let Some(lp_book) = fx_book.lp_books.get_mut(liquidity_provider) {
+        for (entry_id, quote_volume) in &taken {
+            if let Some(entry) = lp_book.get_mut(entry_id) {
+                entry.volume -= quote_volume;
+            }
+        }
+    }
+    volume - remaining
+}
@@ -0,0 +1,163 @@
+//! # FX Simulator and Aggregator - fx_sim_agg_gui
+//!
+//! `feed.rs` keeps statistics on the market data feed of each liquidity provider and currency pair - how many messages have
+//! arrived, when the last one arrived, how fast they are arriving and whether its stream has completed - so a feed that has
+//! stalled can be told apart from a quiet market. Arrival times are wall clock nanoseconds since the unix epoch.
+use crate::fix::MarketData;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+// message rates are averaged over the last RATE_WINDOW_SECS
+pub const RATE_WINDOW_SECS: u64 = 10;
+// a feed is stalled when silent for longer than STALL_INTERVALS of its average interval between messages,
+// and at least MIN_STALL_SECS, so a liquidity provider that is slow but regular is not reported as stalled
+pub const STALL_INTERVALS: f64 = 10.0;
+pub const MIN_STALL_SECS: f64 = 5.0;
+
+const NANOS_PER_SEC: u64 = 1_000_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeedStatus {
+    // no message yet
+    Waiting,
+    Live,
+    Stalled,
+    Completed,
+}
+
+impl Display for FeedStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Waiting => f.write_str("Waiting"),
+            Self::Live => f.write_str("Live"),
+            Self::Stalled => f.write_str("Stalled"),
+            Self::Completed => f.write_str("Completed"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FeedStats {
+    pub messages: u64,
+    // timestamp of the last message and when it arrived
+    pub last_timestamp: Option<u64>,
+    pub first_received: Option<u64>,
+    pub last_received: Option<u64>,
+    // arrival times within the rate window
+    recent: VecDeque<u64>,
+    pub completed: bool,
+}
+
+impl FeedStats {
+    pub fn record(&mut self, timestamp: u64, received: u64) {
+        self.messages += 1;
+        self.last_timestamp = Some(timestamp);
+        self.first_received.get_or_insert(received);
+        self.last_received = Some(received);
+        self.recent.push_back(received);
+        let window_start = received.saturating_sub(RATE_WINDOW_SECS * NANOS_PER_SEC);
+        while self
+            .recent
+            .front()
+            .is_some_and(|received| *received < window_start)
+        {
+            self.recent.pop_front();
+        }
+    }
+
+    pub fn message_rate(&self, now: u64) -> f64 {
+        // messages a second over the rate window ending now
+        let window_start = now.saturating_sub(RATE_WINDOW_SECS * NANOS_PER_SEC);
+        let messages = self
+            .recent
+            .iter()
+            .filter(|received| **received >= window_start)
+            .count();
+        messages as f64 / RATE_WINDOW_SECS as f64
+    }
+
+    pub fn secs_since_last(&self, now: u64) -> Option<f64> {
+        Some(now.saturating_sub(self.last_received?) as f64 / NANOS_PER_SEC as f64)
+    }
+
+    pub fn average_interval_secs(&self) -> Option<f64> {
+        // average time between messages since the first one arrived
+        let (first, last) = (self.first_received?, self.last_received?);
+        (self.messages > 1)
+            .then(|| (last - first) as f64 / NANOS_PER_SEC as f64 / (self.messages - 1) as f64)
+    }
+
+    pub fn status(&self, now: u64) -> FeedStatus {
+        if self.completed {
+            return FeedStatus::Completed;
+        }
+        let Some(secs_since_last) = self.secs_since_last(now) else {
+            return FeedStatus::Waiting;
+        };
+        let stall_secs = self
+            .average_interval_secs()
+            .map_or(MIN_STALL_SECS, |interval| {
+                (interval * STALL_INTERVALS).max(MIN_STALL_SECS)
+            });
+        if secs_since_last > stall_secs {
+            FeedStatus::Stalled
+        } else {
+            FeedStatus::Live
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FeedMonitor {
+    // statistics of each currency pair's feed from each liquidity provider
+    pub feeds: BTreeMap<String, BTreeMap<String, FeedStats>>,
+}
+
+impl FeedMonitor {
+    pub fn add_feed(&mut self, currency_pair: &str, liquidity_provider: &str) {
+        // a configured feed is shown as waiting before its first message
+        self.feed_mut(currency_pair, liquidity_provider);
+    }
+
+    pub fn record(&mut self, market_data: &MarketData, received: u64) {
+        self.feed_mut(
+            market_data.currency_pair(),
+            market_data.liquidity_provider(),
+        )
+        .record(market_data.timestamp(), received);
+    }
+
+    pub fn complete(&mut self, currency_pair: &str, liquidity_provider: &str) {
+        self.feed_mut(currency_pair, liquidity_provider).completed = true;
+    }
+
+    pub fn complete_all(&mut self) {
+        for feed in self
+            .feeds
+            .values_mut()
+            .flat_map(|pair_feeds| pair_feeds.values_mut())
+        {
+            feed.completed = true;
+        }
+    }
+
+    pub fn pair_feeds(&self, currency_pair: &str) -> Option<&BTreeMap<String, FeedStats>> {
+        self.feeds.get(currency_pair)
+    }
+
+    pub fn message_rate(&self, currency_pair: &str, now: u64) -> f64 {
+        // messages a second from every liquidity provider of the currency pair
+        self.pair_feeds(currency_pair).map_or(0.0, |pair_feeds| {
+            pair_feeds.values().map(|feed| feed.message_rate(now)).sum()
+        })
+    }
+
+    fn feed_mut(&mut self, currency_pair: &str, liquidity_provider: &str) -> &mut FeedStats {
+        self.feeds
+            .entry(String::from(currency_pair))
+            .or_default()
+            .entry(String::from(liquidity_provider))
+            .or_default()
+    }
+}
@@ -0,0 +1,586 @@
+//! # FX Simulator and Aggregator - fx_sim_agg_gui
+//!
+//! `fix.rs` encodes simulated market data as FIX 4.4 MarketDataSnapshotFullRefresh (35=W) and
+//! MarketDataIncrementalRefresh (35=X) messages so the "FIX" log can be consumed directly by FIX tooling,
+//! and decodes venue messages back into typed market data for the aggregator, rejecting malformed
+//! messages with a specific `AppError`. Liquidity provider fills and rejects of child orders are encoded as
+//! ExecutionReport (35=8) messages.
+use crate::AppError;
+use crate::aggregator::BookSide;
+use crate::execution::OrderSide;
+use crate::price::Price;
+use chrono::Utc;
+use chrono::prelude::{DateTime, NaiveDateTime};
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::time::{Duration, UNIX_EPOCH};
+
+pub const SOH: char = '\x01';
+pub const BEGIN_STRING: &str = "FIX.4.4";
+pub const TARGET_COMP_ID: &str = "FXAGG";
+// volumes are held in millions in the book but sent as units in MDEntrySize(271)
+pub const VOLUME_UNIT: i64 = 1_000_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MdEntry {
+    pub entry_id: String,
+    pub side: BookSide,
+    pub price: Price,
+    pub volume: i32,
+}
+
+/// MDUpdateAction(279) of an incremental refresh entry
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MdUpdateAction {
+    New,
+    Change,
+    Delete,
+}
+
+impl MdUpdateAction {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::New => "0",
+            Self::Change => "1",
+            Self::Delete => "2",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Result<Self, AppError> {
+        match code {
+            "0" => Ok(Self::New),
+            "1" => Ok(Self::Change),
+            "2" => Ok(Self::Delete),
+            _ => Err(AppError::InvalidTagValue(279)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MdIncrementalEntry {
+    pub action: MdUpdateAction,
+    pub entry: MdEntry,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarketDataSnapshot {
+    pub liquidity_provider: String,
+    pub currency_pair: String,
+    pub msg_seq_num: u32,
+    pub entries: Vec<MdEntry>,
+    pub timestamp: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarketDataIncrementalRefresh {
+    pub liquidity_provider: String,
+    pub currency_pair: String,
+    pub msg_seq_num: u32,
+    pub entries: Vec<MdIncrementalEntry>,
+    pub timestamp: u64,
+}
+
+/// A decoded market data message from a liquidity provider
+#[derive(Debug, Clone, PartialEq)]
+pub enum MarketData {
+    Snapshot(MarketDataSnapshot),
+    Incremental(MarketDataIncrementalRefresh),
+}
+
+impl MarketData {
+    pub fn liquidity_provider(&self) -> &str {
+        match self {
+            Self::Snapshot(snapshot) => &snapshot.liquidity_provider,
+            Self::Incremental(refresh) => &refresh.liquidity_provider,
+        }
+    }
+
+    pub fn currency_pair(&self) -> &str {
+        match self {
+            Self::Snapshot(snapshot) => &snapshot.currency_pair,
+            Self::Incremental(refresh) => &refresh.currency_pair,
+        }
+    }
+
+    pub fn timestamp(&self) -> u64 {
+        match self {
+            Self::Snapshot(snapshot) => snapshot.timestamp,
+            Self::Incremental(refresh) => refresh.timestamp,
+        }
+    }
+}
+
+impl Display for MarketDataSnapshot {
+    // legacy pipe delimited format "LP | pair | price | ... | timestamp" with prices in entry order
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} | {}", self.liquidity_provider, self.currency_pair)?;
+        for entry in &self.entries {
+            write!(f, " | {}", entry.price)?;
+        }
+        write!(f, " | {}", self.timestamp)
+    }
+}
+
+/// OrdStatus(39) of an execution report
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OrdStatus {
+    PartiallyFilled,
+    Filled,
+    Rejected,
+}
+
+impl OrdStatus {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::PartiallyFilled => "1",
+            Self::Filled => "2",
+            Self::Rejected => "8",
+        }
+    }
+
+    /// ExecType(150) - a trade (F) for a fill or rejected (8)
+    pub fn exec_type(&self) -> &'static str {
+        match self {
+            Self::PartiallyFilled | Self::Filled => "F",
+            Self::Rejected => "8",
+        }
+    }
+}
+
+impl Display for OrdStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PartiallyFilled => f.write_str("partially filled"),
+            Self::Filled => f.write_str("filled"),
+            Self::Rejected => f.write_str("rejected"),
+        }
+    }
+}
+
+/// A liquidity provider's fill or reject of a child order
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExecutionReport {
+    pub liquidity_provider: String,
+    pub currency_pair: String,
+    pub msg_seq_num: u32,
+    pub cl_ord_id: String,
+    pub exec_id: String,
+    pub side: OrderSide,
+    pub ord_status: OrdStatus,
+    // volumes in millions
+    pub order_qty: i32,
+    pub last_qty: i32,
+    pub last_px: Price,
+    pub cum_qty: i32,
+    pub leaves_qty: i32,
+    // volume weighted average price of the child order's fills so far
+    pub avg_px: f64,
+    // reason for a reject
+    pub text: Option<String>,
+    pub timestamp: u64,
+}
+
+impl Display for ExecutionReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} {}M {}",
+            self.liquidity_provider,
+            self.cl_ord_id,
+            self.ord_status,
+            self.side,
+            self.order_qty,
+            self.currency_pair
+        )?;
+        if self.ord_status != OrdStatus::Rejected {
+            write!(
+                f,
+                " | {}M @ {} ({}M filled, {}M left)",
+                self.last_qty, self.last_px, self.cum_qty, self.leaves_qty
+            )?;
+        }
+        if let Some(text) = &self.text {
+            write!(f, " - {text}")?;
+        }
+        Ok(())
+    }
+}
+
+/// MDEntryType(269) for a book side - "Buy" quotes are bids (0) and "Sell" quotes are offers (1)
+pub fn md_entry_type(side: BookSide) -> &'static str {
+    match side {
+        BookSide::Buy => "0",
+        BookSide::Sell => "1",
+    }
+}
+
+/// SendingTime(52) in the FIX UTCTimestamp format YYYYMMDD-HH:MM:SS.sss
+pub fn format_sending_time(timestamp: u64) -> String {
+    let d = UNIX_EPOCH + Duration::from_nanos(timestamp);
+    let datetime = DateTime::<Utc>::from(d);
+    datetime.format("%Y%m%d-%H:%M:%S%.3f").to_string()
+}
+
+/// CheckSum(10) - sum of all bytes up to and including the SOH before the checksum field, modulo 256
+pub fn checksum(data: &str) -> u8 {
+    data.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte))
+}
+
+pub fn encode_market_data_snapshot(snapshot: &MarketDataSnapshot) -> String {
+    let mut body = String::new();
+    push_field(&mut body, 35, "W");
+    push_field(&mut body, 49, &snapshot.liquidity_provider);
+    push_field(&mut body, 56, TARGET_COMP_ID);
+    push_field(&mut body, 34, &snapshot.msg_seq_num.to_string());
+    push_field(&mut body, 52, &format_sending_time(snapshot.timestamp));
+    push_field(&mut body, 55, &snapshot.currency_pair);
+    push_field(&mut body, 268, &snapshot.entries.len().to_string());
+    for entry in &snapshot.entries {
+        push_field(&mut body, 269, md_entry_type(entry.side));
+        push_field(&mut body, 270, &entry.price.to_string());
+        push_field(
+            &mut body,
+            271,
+            &(entry.volume as i64 * VOLUME_UNIT).to_string(),
+        );
+        if !entry.entry_id.is_empty() {
+            push_field(&mut body, 278, &entry.entry_id);
+        }
+    }
+
+    finish_message(&body)
+}
+
+pub fn encode_market_data_incremental_refresh(refresh: &MarketDataIncrementalRefresh) -> String {
+    let mut body = String::new();
+    push_field(&mut body, 35, "X");
+    push_field(&mut body, 49, &refresh.liquidity_provider);
+    push_field(&mut body, 56, TARGET_COMP_ID);
+    push_field(&mut body, 34, &refresh.msg_seq_num.to_string());
+    push_field(&mut body, 52, &format_sending_time(refresh.timestamp));
+    push_field(&mut body, 268, &refresh.entries.len().to_string());
+    for incremental_entry in &refresh.entries {
+        let entry = &incremental_entry.entry;
+        push_field(&mut body, 279, incremental_entry.action.code());
+        push_field(&mut body, 269, md_entry_type(entry.side));
+        push_field(&mut body, 278, &entry.entry_id);
+        push_field(&mut body, 55, &refresh.currency_pair);
+        // a delete only needs to identify the entry being removed
+        if incremental_entry.action != MdUpdateAction::Delete {
+            push_field(&mut body, 270, &entry.price.to_string());
+            push_field(
+                &mut body,
+                271,
+                &(entry.volume as i64 * VOLUME_UNIT).to_string(),
+            );
+        }
+    }
+
+    finish_message(&body)
+}
+
+pub fn encode_execution_report(report: &ExecutionReport) -> String {
+    let mut body = String::new();
+    push_field(&mut body, 35, "8");
+    push_field(&mut body, 49, &report.liquidity_provider);
+    push_field(&mut body, 56, TARGET_COMP_ID);
+    push_field(&mut body, 34, &report.msg_seq_num.to_string());
+    push_field(&mut body, 52, &format_sending_time(report.timestamp));
+    // the liquidity provider's OrderID(37) is the ClOrdID(11) of the child order it was sent
+    push_field(&mut body, 37, &report.cl_ord_id);
+    push_field(&mut body, 11, &report.cl_ord_id);
+    push_field(&mut body, 17, &report.exec_id);
+    push_field(&mut body, 150, report.ord_status.exec_type());
+    push_field(&mut body, 39, report.ord_status.code());
+    push_field(&mut body, 55, &report.currency_pair);
+    push_field(&mut body, 54, side_code(report.side));
+    push_field(
+        &mut body,
+        38,
+        &(report.order_qty as i64 * VOLUME_UNIT).to_string(),
+    );
+    if report.ord_status != OrdStatus::Rejected {
+        push_field(
+            &mut body,
+            32,
+            &(report.last_qty as i64 * VOLUME_UNIT).to_string(),
+        );
+        push_field(&mut body, 31, &report.last_px.to_string());
+    }
+    push_field(
+        &mut body,
+        151,
+        &(report.leaves_qty as i64 * VOLUME_UNIT).to_string(),
+    );
+    push_field(
+        &mut body,
+        14,
+        &(report.cum_qty as i64 * VOLUME_UNIT).to_string(),
+    );
+    push_field(&mut body, 6, &format!("{:.6}", report.avg_px));
+    if let Some(text) = &report.text {
+        push_field(&mut body, 58, text);
+    }
+    push_field(&mut body, 60, &format_sending_time(report.timestamp));
+
+    finish_message(&body)
+}
+
+/// Side(54) of an order - buy (1) or sell (2)
+pub fn side_code(side: OrderSide) -> &'static str {
+    match side {
+        OrderSide::Buy => "1",
+        OrderSide::Sell => "2",
+    }
+}
+
+fn push_field(message: &mut String, tag: u32, value: &str) {
+    message.push_str(&format!("{}={}{}", tag, value, SOH));
+}
+
+fn finish_message(body: &str) -> String {
+    // prepend BeginString(8) and BodyLength(9) and append CheckSum(10)
+    let mut message = String::new();
+    push_field(&mut message, 8, BEGIN_STRING);
+    push_field(&mut message, 9, &body.len().to_string());
+    message.push_str(body);
+    let checksum = checksum(&message);
+    push_field(&mut message, 10, &format!("{:03}", checksum));
+    message
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FixMessage {
+    pub fields: Vec<(u32, String)>,
+}
+
+impl FixMessage {
+    /// Value of the first occurrence of a tag
+    pub fn get(&self, tag: u32) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|field| field.0 == tag)
+            .map(|field| field.1.as_str())
+    }
+
+    /// Value of a tag that must be present and non-empty
+    pub fn required(&self, tag: u32) -> Result<&str, AppError> {
+        match self.get(tag) {
+            Some(value) if !value.is_empty() => Ok(value),
+            _ => Err(AppError::MissingTag(tag)),
+        }
+    }
+
+    pub fn msg_type(&self) -> Result<&str, AppError> {
+        self.required(35)
+    }
+}
+
+/// Split a raw message into tag=value fields and validate the standard header and trailer:
+/// BeginString(8) first, BodyLength(9) second and matching the body, CheckSum(10) last and correct
+pub fn decode(message: &str) -> Result<FixMessage, AppError> {
+    let message = message.trim_end_matches(['\r', '\n']);
+    let mut fields: Vec<(u32, String)> = Vec::new();
+    for field in message.split_terminator(SOH) {
+        let (tag, value) = field
+            .split_once('=')
+            .ok_or_else(|| AppError::MalformedField(String::from(field)))?;
+        let tag: u32 = tag
+            .parse()
+            .map_err(|_| AppError::MalformedField(String::from(field)))?;
+        fields.push((tag, String::from(value)));
+    }
+
+    match fields.first() {
+        Some((8, value)) if value == BEGIN_STRING => (),
+        Some((8, value)) => return Err(AppError::BadBeginString(value.clone())),
+        _ => return Err(AppError::MissingTag(8)),
+    }
+    let body_length: usize = match fields.get(1) {
+        Some((9, value)) => value.parse().map_err(|_| AppError::InvalidTagValue(9))?,
+        _ => return Err(AppError::MissingTag(9)),
+    };
+    let expected_checksum: u8 = match fields.last() {
+        Some((10, value)) if value.len() == 3 => {
+            value.parse().map_err(|_| AppError::InvalidTagValue(10))?
+        }
+        Some((10, _)) => return Err(AppError::InvalidTagValue(10)),
+        _ => return Err(AppError::MissingTag(10)),
+    };
+
+    // CheckSum(10) is terminated by an SOH like every other field, so without it the body and checksum can't be found
+    if !message.ends_with(SOH) {
+        return Err(AppError::MissingTrailingSoh);
+    }
+    // body runs from the field after BodyLength(9) up to and including the SOH before CheckSum(10)
+    let body_start = format!("8={}{}9={}{}", fields[0].1, SOH, fields[1].1, SOH).len();
+    let checksum_start = message.len() - format!("10={}{}", fields[fields.len() - 1].1, SOH).len();
+    let actual_body_length = checksum_start.saturating_sub(body_start);
+    if actual_body_length != body_length {
+        return Err(AppError::BadBodyLength(body_length, actual_body_length));
+    }
+    let actual_checksum = checksum(&message[..checksum_start]);
+    if actual_checksum != expected_checksum {
+        return Err(AppError::BadChecksum(expected_checksum, actual_checksum));
+    }
+
+    Ok(FixMessage { fields })
+}
+
+/// Decode a MarketDataSnapshotFullRefresh (35=W) or MarketDataIncrementalRefresh (35=X) message,
+/// validating the required tags and the NoMDEntries(268) repeating group of MDEntryType(269),
+/// MDEntryPx(270), MDEntrySize(271), MDEntryID(278) and, for incremental refreshes, MDUpdateAction(279)
+pub fn decode_market_data(message: &str) -> Result<MarketData, AppError> {
+    let fix_message = decode(message)?;
+    let msg_type = fix_message.msg_type()?;
+    if msg_type != "W" && msg_type != "X" {
+        return Err(AppError::UnknownMsgType(String::from(msg_type)));
+    }
+
+    let liquidity_provider = String::from(fix_message.required(49)?);
+    let msg_seq_num: u32 = parse_tag(&fix_message, 34)?;
+    let timestamp = parse_sending_time(fix_message.required(52)?)?;
+    // Symbol(55) is in the header of a snapshot and in each entry of an incremental refresh
+    let currency_pair = String::from(fix_message.required(55)?);
+    let no_md_entries: usize = parse_tag(&fix_message, 268)?;
+
+    if msg_type == "W" {
+        let groups = decode_md_entries(&fix_message, no_md_entries, 269)?;
+        let entries = groups
+            .into_iter()
+            .map(|group| group.into_md_entry(false))
+            .collect::<Result<Vec<MdEntry>, AppError>>()?;
+        Ok(MarketData::Snapshot(MarketDataSnapshot {
+            liquidity_provider,
+            currency_pair,
+            msg_seq_num,
+            entries,
+            timestamp,
+        }))
+    } else {
+        let groups = decode_md_entries(&fix_message, no_md_entries, 279)?;
+        let mut entries: Vec<MdIncrementalEntry> = Vec::new();
+        for group in groups {
+            let action = group.action.ok_or(AppError::MissingTag(279))?;
+            let entry = group.into_md_entry(action == MdUpdateAction::Delete)?;
+            if entry.entry_id.is_empty() {
+                return Err(AppError::MissingTag(278));
+            }
+            entries.push(MdIncrementalEntry { action, entry });
+        }
+        Ok(MarketData::Incremental(MarketDataIncrementalRefresh {
+            liquidity_provider,
+            currency_pair,
+            msg_seq_num,
+            entries,
+            timestamp,
+        }))
+    }
+}
+
+#[derive(Default)]
+struct MdEntryGroup {
+    action: Option<MdUpdateAction>,
+    entry_id: Option<String>,
+    side: Option<BookSide>,
+    price: Option<Price>,
+    volume: Option<i32>,
+}
+
+impl MdEntryGroup {
+    fn into_md_entry(self, is_delete: bool) -> Result<MdEntry, AppError> {
+        // price and size are not needed to delete an entry
+        let price = match self.price {
+            Some(price) => price,
+            None if is_delete => Price::default(),
+            None => return Err(AppError::MissingTag(270)),
+        };
+        let volume = match self.volume {
+            Some(volume) => volume,
+            None if is_delete => 0,
+            None => return Err(AppError::MissingTag(271)),
+        };
+        Ok(MdEntry {
+            entry_id: self.entry_id.unwrap_or_default(),
+            side: self.side.ok_or(AppError::MissingTag(269))?,
+            price,
+            volume,
+        })
+    }
+}
+
+fn decode_md_entries(
+    fix_message: &FixMessage,
+    no_md_entries: usize,
+    first_group_tag: u32,
+) -> Result<Vec<MdEntryGroup>, AppError> {
+    // each group starts with first_group_tag and the other group fields follow in any order
+    let mut groups: Vec<MdEntryGroup> = Vec::new();
+    let start = fix_message
+        .fields
+        .iter()
+        .position(|field| field.0 == 268)
+        .ok_or(AppError::MissingTag(268))?;
+    for (tag, value) in &fix_message.fields[start + 1..] {
+        if *tag == first_group_tag {
+            groups.push(MdEntryGroup::default());
+        }
+        let group = match tag {
+            269 | 270 | 271 | 278 | 279 => groups
+                .last_mut()
+                .ok_or(AppError::MissingTag(first_group_tag))?,
+            _ => continue,
+        };
+        match tag {
+            269 => group.side = Some(md_entry_side(value)?),
+            270 => {
+                let price: Price = value.parse().map_err(|_| AppError::InvalidTagValue(270))?;
+                group.price = Some(price);
+            }
+            271 => group.volume = Some(md_entry_size(value)?),
+            278 => group.entry_id = Some(value.clone()),
+            _ => group.action = Some(MdUpdateAction::from_code(value)?),
+        }
+    }
+
+    if groups.len() != no_md_entries {
+        return Err(AppError::BadGroupCount(no_md_entries, groups.len()));
+    }
+    Ok(groups)
+}
+
+/// Volume (M) of an MDEntrySize(271) in units, which must be a positive whole number of millions that fits the book
+pub fn md_entry_size(value: &str) -> Result<i32, AppError> {
+    let units: i64 = value.parse().map_err(|_| AppError::InvalidTagValue(271))?;
+    if units <= 0 || units % VOLUME_UNIT != 0 {
+        return Err(AppError::InvalidTagValue(271));
+    }
+    i32::try_from(units / VOLUME_UNIT).map_err(|_| AppError::InvalidTagValue(271))
+}
+
+/// Book side for an MDEntryType(269) - bids (0) are "Buy" quotes and offers (1) are "Sell" quotes
+pub fn md_entry_side(md_entry_type: &str) -> Result<BookSide, AppError> {
+    match md_entry_type {
+        "0" => Ok(BookSide::Buy),
+        "1" => Ok(BookSide::Sell),
+        _ => Err(AppError::InvalidTagValue(269)),
+    }
+}
+
+fn parse_tag<T: std::str::FromStr>(fix_message: &FixMessage, tag: u32) -> Result<T, AppError> {
+    fix_message
+        .required(tag)?
+        .parse()
+        .map_err(|_| AppError::InvalidTagValue(tag))
+}
+
+/// Nanoseconds since the unix epoch for a SendingTime(52) UTCTimestamp
+pub fn parse_sending_time(sending_time: &str) -> Result<u64, AppError> {
+    let datetime = NaiveDateTime::parse_from_str(sending_time, "%Y%m%d-%H:%M:%S%.f")
+        .map_err(|_| AppError::InvalidTagValue(52))?;
+    let timestamp = datetime
+        .and_utc()
+        .timestamp_nanos_opt()
+        .ok_or(AppError::InvalidTagValue(52))?;
+    u64::try_from(timestamp).map_err(|_| AppError::InvalidTagValue(52))
+}
@@ -1,12 +1,93 @@
-use crate::FxViewerApp;
-use crate::aggregator;
+use crate::aggregator::{self, BookSide, FxBook, LevelChange};
+use crate::clock::{self, SimClock};
+use crate::execution::{Execution, Order, OrderSide, OrderType};
+use crate::feed::FeedStatus;
+use crate::history::TimeWindow;
+use crate::listener::BookListener;
+use crate::position::{self, Position};
+use crate::price::{Price, pip_decimals};
+use crate::venue;
+use crate::{AppError, FxViewerApp};
+use chrono::{DateTime, Utc};
 use eframe::egui;
 use egui::{Color32, Label, Layout, RichText};
 use egui_extras::{TableBody, TableBuilder, TableRow};
+use egui_plot::{Legend, Line, Plot, Points};
+use log::info;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::sync::mpsc::{TryRecvError, channel};
+use std::thread;
 
-pub fn render_top_panel(ctx: &egui::Context) {
+// requests a repaint of the GUI whenever a book changes
+pub struct RepaintListener {
+    ctx: egui::Context,
+}
+
+impl RepaintListener {
+    pub fn new(ctx: egui::Context) -> Self {
+        RepaintListener { ctx }
+    }
+}
+
+impl BookListener for RepaintListener {
+    fn on_book_update(&mut self, _fx_book: &FxBook) -> Result<(), AppError> {
+        self.ctx.request_repaint();
+        Ok(())
+    }
+}
+
+pub fn set_window_title(ctx: &egui::Context, currency_pair: &str) {
+    ctx.send_viewport_cmd(egui::ViewportCommand::Title(format!(
+        "{} Aggregated Book",
+        currency_pair
+    )));
+}
+
+pub fn render_top_panel(fx_viewer_app: &mut FxViewerApp, ctx: &egui::Context) {
+    let (currency_pairs, mut kill_switch) = {
+        let fx_books = fx_viewer_app.fx_books_mutex.lock().unwrap(); // panic if can't get lock
+        (fx_books.currency_pairs(), fx_books.risk.kill_switch())
+    };
     egui::TopBottomPanel::top("my_panel").show(ctx, |ui| {
         ctx.set_visuals(egui::Visuals::dark());
+        ui.with_layout(Layout::left_to_right(eframe::emath::Align::Center), |ui| {
+            ui.label("Currency pair");
+            let previous_pair = fx_viewer_app.selected_pair.clone();
+            egui::ComboBox::from_id_salt("currency_pair")
+                .selected_text(&fx_viewer_app.selected_pair)
+                .show_ui(ui, |ui| {
+                    for currency_pair in &currency_pairs {
+                        ui.selectable_value(
+                            &mut fx_viewer_app.selected_pair,
+                            currency_pair.clone(),
+                            currency_pair,
+                        );
+                    }
+                });
+            if fx_viewer_app.selected_pair != previous_pair {
+                set_window_title(ctx, &fx_viewer_app.selected_pair);
+            }
+            // rejects every order while engaged
+            ui.add_space(20.);
+            let kill_switch_text = RichText::new("Kill switch").color(if kill_switch {
+                Color32::RED
+            } else {
+                Color32::GRAY
+            });
+            if ui
+                .toggle_value(&mut kill_switch, kill_switch_text)
+                .changed()
+            {
+                let mut fx_books = fx_viewer_app.fx_books_mutex.lock().unwrap(); // panic if can't get lock
+                fx_books.risk.set_kill_switch(kill_switch);
+                info!(
+                    "kill switch {}",
+                    if kill_switch { "engaged" } else { "released" }
+                );
+            }
+        });
         ui.with_layout(Layout::left_to_right(eframe::emath::Align::Center), |ui| {
             ui.add_space(180.);
             ui.add(Label::new(
@@ -20,8 +101,225 @@ pub fn render_top_panel(ctx: &egui::Context) {
     });
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct TradeTicket {
+    pub currency_pair: String,
+    // side of the price clicked to open the ticket
+    pub side: OrderSide,
+    // amount (M)
+    pub volume: i32,
+    pub limit_price: String,
+    // send a market order rather than a limit order at limit_price
+    pub market: bool,
+    // order waiting for the trader to confirm it
+    pub pending: Option<Order>,
+    // fills or error of the last order sent from the ticket
+    pub result: Option<Result<Execution, String>>,
+}
+
+impl TradeTicket {
+    pub fn new(currency_pair: &str, side: OrderSide, price: Price, volume: i32) -> Self {
+        TradeTicket {
+            currency_pair: String::from(currency_pair),
+            side,
+            volume,
+            limit_price: price.to_string(),
+            market: false,
+            pending: None,
+            result: None,
+        }
+    }
+
+    pub fn order(&self, side: OrderSide) -> Result<Order, String> {
+        let order_type = if self.market {
+            OrderType::Market
+        } else {
+            let limit_price = self
+                .limit_price
+                .trim()
+                .parse::<Price>()
+                .map_err(|e| format!("limit price \"{}\" - {e}", self.limit_price))?;
+            OrderType::Limit(limit_price)
+        };
+        Ok(Order {
+            currency_pair: self.currency_pair.clone(),
+            side,
+            volume: self.volume,
+            order_type,
+        })
+    }
+}
+
+// bid and ask prices, the top of book when it last ticked up or down, and the flash of a changed level as it starts to fade
+const BID_COLOR: Color32 = Color32::from_rgb(90, 160, 255);
+const ASK_COLOR: Color32 = Color32::from_rgb(255, 120, 90);
+const UPTICK_COLOR: Color32 = Color32::GREEN;
+const DOWNTICK_COLOR: Color32 = Color32::RED;
+const INSERTED_FLASH: Color32 = Color32::from_rgb(40, 120, 40);
+const UPDATED_FLASH: Color32 = Color32::from_rgb(120, 110, 30);
+const REMOVED_FLASH: Color32 = Color32::from_rgb(130, 40, 40);
+// seconds a changed level flashes for
+const FLASH_SECS: f64 = 0.6;
+// colours of the liquidity provider badges and chart quotes
+const LP_COLORS: [Color32; 8] = [
+    Color32::from_rgb(255, 179, 71),
+    Color32::from_rgb(119, 221, 119),
+    Color32::from_rgb(132, 182, 244),
+    Color32::from_rgb(253, 253, 150),
+    Color32::from_rgb(203, 153, 201),
+    Color32::from_rgb(150, 222, 209),
+    Color32::from_rgb(255, 150, 180),
+    Color32::from_rgb(200, 200, 200),
+];
+
+pub fn lp_color(liquidity_provider: &str) -> Color32 {
+    // the same colour for a liquidity provider on every run, from a hash of its name
+    let hash = liquidity_provider.bytes().fold(0usize, |hash, byte| {
+        hash.wrapping_mul(31).wrapping_add(usize::from(byte))
+    });
+    LP_COLORS[hash % LP_COLORS.len()]
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Flash {
+    side: BookSide,
+    price: Price,
+    change: LevelChange,
+    // egui time (seconds) of the change
+    time: f64,
+}
+
+// the changes between successive states of the book shown, to flash changed levels and colour the top of book by tick direction
+#[derive(Debug, Clone, Default)]
+pub struct BookHighlighter {
+    currency_pair: String,
+    previous_buy_book: Vec<aggregator::FxAggBookEntry>,
+    previous_sell_book: Vec<aggregator::FxAggBookEntry>,
+    flashes: Vec<Flash>,
+    // removed levels, kept to show until their flash fades
+    removed: Vec<(BookSide, aggregator::FxAggBookEntry)>,
+    buy_tick: Option<Ordering>,
+    sell_tick: Option<Ordering>,
+}
+
+impl BookHighlighter {
+    pub fn update(&mut self, fx_book: &FxBook, now: f64) {
+        // switching currency pair starts again without flashing the whole book
+        if fx_book.currency_pair != self.currency_pair {
+            *self = BookHighlighter {
+                currency_pair: fx_book.currency_pair.clone(),
+                previous_buy_book: fx_book.buy_book.clone(),
+                previous_sell_book: fx_book.sell_book.clone(),
+                ..Default::default()
+            };
+            return;
+        }
+        self.flashes.retain(|flash| now - flash.time < FLASH_SECS);
+        let flashes = &self.flashes;
+        self.removed.retain(|(side, level)| {
+            flashes
+                .iter()
+                .any(|flash| flash.side == *side && flash.price == level.price)
+        });
+        for (side, previous, current) in [
+            (BookSide::Buy, &self.previous_buy_book, &fx_book.buy_book),
+            (BookSide::Sell, &self.previous_sell_book, &fx_book.sell_book),
+        ] {
+            for (price, change) in aggregator::level_changes(previous, current) {
+                // a level changed again flashes again, for its latest change
+                self.flashes
+                    .retain(|flash| !(flash.side == side && flash.price == price));
+                self.removed.retain(|(removed_side, level)| {
+                    !(*removed_side == side && level.price == price)
+                });
+                if change == LevelChange::Removed
+                    && let Some(level) = previous.iter().find(|level| level.price == price)
+                {
+                    self.removed.push((side, level.clone()));
+                }
+                self.flashes.push(Flash {
+                    side,
+                    price,
+                    change,
+                    time: now,
+                });
+            }
+            let tick = match (previous.first(), current.first()) {
+                (Some(previous), Some(current)) if previous.price != current.price => {
+                    Some(current.price.cmp(&previous.price))
+                }
+                _ => None,
+            };
+            if tick.is_some() {
+                match side {
+                    BookSide::Buy => self.buy_tick = tick,
+                    BookSide::Sell => self.sell_tick = tick,
+                }
+            }
+        }
+        self.previous_buy_book = fx_book.buy_book.clone();
+        self.previous_sell_book = fx_book.sell_book.clone();
+    }
+
+    pub fn tick(&self, side: BookSide) -> Option<Ordering> {
+        // the direction the top of the side of the book last moved
+        match side {
+            BookSide::Buy => self.buy_tick,
+            BookSide::Sell => self.sell_tick,
+        }
+    }
+
+    pub fn flash(&self, side: BookSide, price: Price, now: f64) -> Option<Color32> {
+        // the flash colour of a level fades out over FLASH_SECS
+        let flash = self
+            .flashes
+            .iter()
+            .find(|flash| flash.side == side && flash.price == price)?;
+        let remaining = 1.0 - (now - flash.time) / FLASH_SECS;
+        if remaining <= 0.0 {
+            return None;
+        }
+        let color = match flash.change {
+            LevelChange::Inserted => INSERTED_FLASH,
+            LevelChange::Updated => UPDATED_FLASH,
+            LevelChange::Removed => REMOVED_FLASH,
+        };
+        Some(color.gamma_multiply(remaining as f32))
+    }
+
+    pub fn removed_levels(&self, side: BookSide) -> Vec<&aggregator::FxAggBookEntry> {
+        // best price first, like the book
+        let mut levels: Vec<&aggregator::FxAggBookEntry> = self
+            .removed
+            .iter()
+            .filter(|(removed_side, _)| *removed_side == side)
+            .map(|(_, level)| level)
+            .collect();
+        match side {
+            BookSide::Buy => levels.sort_by_key(|level| std::cmp::Reverse(level.price)),
+            BookSide::Sell => levels.sort_by_key(|level| level.price),
+        }
+        levels
+    }
+
+    pub fn is_flashing(&self) -> bool {
+        !self.flashes.is_empty()
+    }
+}
+
 pub fn render_fx_book(fx_viewer_app: &mut FxViewerApp, ctx: &egui::Context) {
-    let fx_book = fx_viewer_app.fx_book_mutex.lock().unwrap(); // panic if can't get lock
+    let fx_books = fx_viewer_app.fx_books_mutex.lock().unwrap(); // panic if can't get lock
+    let Some(fx_book) = fx_books.get(&fx_viewer_app.selected_pair) else {
+        return;
+    };
+    let now = ctx.input(|input| input.time);
+    fx_viewer_app.book_highlighter.update(fx_book, now);
+    if fx_viewer_app.book_highlighter.is_flashing() {
+        // keep repainting while the flashes fade
+        ctx.request_repaint_after(std::time::Duration::from_millis(50));
+    }
+    let mut buy_clicked = None;
+    let mut sell_clicked = None;
     egui::CentralPanel::default().show(ctx, |ui| {
         ui.with_layout(Layout::left_to_right(eframe::emath::Align::Center), |ui| {
             ui.with_layout(Layout::top_down(eframe::emath::Align::Center), |ui| {
@@ -35,7 +333,13 @@ pub fn render_fx_book(fx_viewer_app: &mut FxViewerApp, ctx: &egui::Context) {
                             render_buy_table_header(header);
                         })
                         .body(|body| {
-                            render_buy_table_body(body, &fx_book.buy_book);
+                            buy_clicked = render_book_table_body(
+                                body,
+                                BookSide::Buy,
+                                &fx_book.buy_book,
+                                &fx_viewer_app.book_highlighter,
+                                now,
+                            );
                         });
                 });
             });
@@ -51,14 +355,602 @@ pub fn render_fx_book(fx_viewer_app: &mut FxViewerApp, ctx: &egui::Context) {
                             render_sell_table_header(header);
                         })
                         .body(|body| {
-                            render_sell_table_body(body, &fx_book.sell_book);
+                            sell_clicked = render_book_table_body(
+                                body,
+                                BookSide::Sell,
+                                &fx_book.sell_book,
+                                &fx_viewer_app.book_highlighter,
+                                now,
+                            );
                         });
                 });
             });
         });
     });
+
+    // clicking a bid opens a ticket to sell, and an offer to buy, the volume available at that price or better
+    let clicked = match (buy_clicked, sell_clicked) {
+        (Some(index), _) => Some((OrderSide::Sell, &fx_book.buy_book, index)),
+        (None, Some(index)) => Some((OrderSide::Buy, &fx_book.sell_book, index)),
+        (None, None) => None,
+    };
+    if let Some((side, fx_book_side, index)) = clicked {
+        let depth = aggregator::market_depth(fx_book_side);
+        fx_viewer_app.trade_ticket = Some(TradeTicket::new(
+            &fx_book.currency_pair,
+            side,
+            depth[index].price,
+            depth[index].cumulative_volume,
+        ));
+    }
 } // mutex lock released here
 
+// top of book and liquidity provider quotes as (seconds since the unix epoch, price) points for the price chart
+#[derive(Default)]
+struct PriceChartSeries {
+    bids: Vec<[f64; 2]>,
+    asks: Vec<[f64; 2]>,
+    mids: Vec<[f64; 2]>,
+    lp_quotes: BTreeMap<String, Vec<[f64; 2]>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChartView {
+    // top of book and liquidity provider quotes over time
+    #[default]
+    Price,
+    // cumulative volume against price of the current book
+    Depth,
+}
+
+pub fn render_chart_panel(fx_viewer_app: &mut FxViewerApp, ctx: &egui::Context) {
+    egui::SidePanel::right("chart_panel")
+        .resizable(true)
+        .default_width(600.)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut fx_viewer_app.chart_view, ChartView::Price, "Price");
+                ui.selectable_value(&mut fx_viewer_app.chart_view, ChartView::Depth, "Depth");
+            });
+            match fx_viewer_app.chart_view {
+                ChartView::Price => render_price_chart(fx_viewer_app, ui),
+                ChartView::Depth => render_depth_chart(fx_viewer_app, ui),
+            }
+        });
+}
+
+fn render_price_chart(fx_viewer_app: &mut FxViewerApp, ui: &mut egui::Ui) {
+    // the top of book bid, ask and mid and the top quotes of each liquidity provider not hidden, over the time window
+    let mut series = PriceChartSeries::default();
+    let liquidity_providers = {
+        let fx_books = fx_viewer_app.fx_books_mutex.lock().unwrap(); // panic if can't get lock
+        let Some(history) = fx_books.history(&fx_viewer_app.selected_pair) else {
+            return;
+        };
+        let start = history
+            .latest_timestamp()
+            .map_or(0, |latest| fx_viewer_app.chart_window.start(latest));
+        for point in history.since(start) {
+            let x = point.timestamp as f64 / 1e9;
+            if let Some(bid) = point.bid {
+                series.bids.push([x, bid.to_f64()]);
+            }
+            if let Some(ask) = point.ask {
+                series.asks.push([x, ask.to_f64()]);
+            }
+            if let Some(mid) = point.mid() {
+                series.mids.push([x, mid]);
+            }
+            for (liquidity_provider, (bid, ask)) in &point.lp_quotes {
+                if fx_viewer_app.hidden_lps.contains(liquidity_provider) {
+                    continue;
+                }
+                let lp_quotes = series
+                    .lp_quotes
+                    .entry(liquidity_provider.clone())
+                    .or_default();
+                lp_quotes.extend(bid.iter().chain(ask).map(|price| [x, price.to_f64()]));
+            }
+        }
+        history.liquidity_providers()
+    }; // mutex lock released here
+
+    let previous_window = fx_viewer_app.chart_window;
+    ui.horizontal_wrapped(|ui| {
+        ui.label("Window");
+        egui::ComboBox::from_id_salt("chart_window")
+            .selected_text(fx_viewer_app.chart_window.to_string())
+            .show_ui(ui, |ui| {
+                for time_window in TimeWindow::ALL {
+                    ui.selectable_value(
+                        &mut fx_viewer_app.chart_window,
+                        time_window,
+                        time_window.to_string(),
+                    );
+                }
+            });
+        for liquidity_provider in &liquidity_providers {
+            let mut shown = !fx_viewer_app.hidden_lps.contains(liquidity_provider);
+            if ui.checkbox(&mut shown, liquidity_provider).changed() {
+                if shown {
+                    fx_viewer_app.hidden_lps.remove(liquidity_provider);
+                } else {
+                    fx_viewer_app.hidden_lps.insert(liquidity_provider.clone());
+                }
+            }
+        }
+    });
+    // zoom and pan with the mouse, double click to fit the time window again
+    let mut plot = Plot::new("price_chart")
+        .legend(Legend::default())
+        .x_axis_formatter(|mark, _range| format_chart_time(mark.value))
+        .label_formatter(|name, value| {
+            format!("{name}\n{}\n{:.5}", format_chart_time(value.x), value.y)
+        });
+    if fx_viewer_app.chart_window != previous_window {
+        plot = plot.reset();
+    }
+    plot.show(ui, |plot_ui| {
+        plot_ui.line(Line::new("Bid", series.bids).color(BID_COLOR));
+        plot_ui.line(Line::new("Ask", series.asks).color(ASK_COLOR));
+        plot_ui.line(Line::new("Mid", series.mids).color(Color32::GRAY));
+        for (liquidity_provider, lp_quotes) in series.lp_quotes {
+            let color = lp_color(&liquidity_provider);
+            plot_ui.points(
+                Points::new(liquidity_provider, lp_quotes)
+                    .color(color)
+                    .radius(1.5),
+            );
+        }
+    });
+}
+
+fn render_depth_chart(fx_viewer_app: &mut FxViewerApp, ui: &mut egui::Ui) {
+    // cumulative volume from the top of each side of the book, stepping out at every level
+    let (buy_depth, sell_depth) = {
+        let fx_books = fx_viewer_app.fx_books_mutex.lock().unwrap(); // panic if can't get lock
+        let Some(fx_book) = fx_books.get(&fx_viewer_app.selected_pair) else {
+            return;
+        };
+        (
+            aggregator::market_depth(&fx_book.buy_book),
+            aggregator::market_depth(&fx_book.sell_book),
+        )
+    }; // mutex lock released here
+
+    // hovering shows the level nearest the pointer on the side of the book hovered
+    let label_formatter = |name: &str, value: &egui_plot::PlotPoint| {
+        let depth = match name {
+            "Buy" => &buy_depth,
+            "Sell" => &sell_depth,
+            _ => return String::new(),
+        };
+        depth
+            .iter()
+            .min_by(|a, b| {
+                (a.price.to_f64() - value.x)
+                    .abs()
+                    .total_cmp(&(b.price.to_f64() - value.x).abs())
+            })
+            .map(|level| {
+                format!(
+                    "{name} {}\n{}M cumulative\n{}",
+                    level.price,
+                    level.cumulative_volume,
+                    create_lp_agg_string(&level.lp_vol)
+                )
+            })
+            .unwrap_or_default()
+    };
+    Plot::new("depth_chart")
+        .legend(Legend::default())
+        .x_axis_label("Price")
+        .y_axis_label("Volume (M)")
+        .label_formatter(label_formatter)
+        .show(ui, |plot_ui| {
+            plot_ui.line(
+                Line::new("Buy", depth_steps(&buy_depth))
+                    .color(BID_COLOR)
+                    .fill(0.0),
+            );
+            plot_ui.line(
+                Line::new("Sell", depth_steps(&sell_depth))
+                    .color(ASK_COLOR)
+                    .fill(0.0),
+            );
+        });
+}
+
+fn depth_steps(depth: &[aggregator::DepthLevel]) -> Vec<[f64; 2]> {
+    // each level steps the cumulative volume up from that of the level before it at the level's price
+    let mut steps: Vec<[f64; 2]> = Vec::new();
+    let mut previous_volume = 0;
+    for level in depth {
+        let price = level.price.to_f64();
+        steps.push([price, f64::from(previous_volume)]);
+        steps.push([price, f64::from(level.cumulative_volume)]);
+        previous_volume = level.cumulative_volume;
+    }
+    steps
+}
+
+fn format_chart_time(secs: f64) -> String {
+    DateTime::<Utc>::from_timestamp_millis((secs * 1000.0) as i64)
+        .map(|datetime| datetime.format("%H:%M:%S").to_string())
+        .unwrap_or_default()
+}
+
+pub fn render_trade_ticket(fx_viewer_app: &mut FxViewerApp, ctx: &egui::Context) {
+    // an order is only routed to the liquidity providers' venues once it has been confirmed, and is sent on its own
+    // thread so the GUI keeps drawing while the venues hold it for their latency and last look
+    if let Some(order_result) = &fx_viewer_app.order_result {
+        let result = match order_result.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(String::from("order was not sent"))),
+        };
+        if let Some(result) = result {
+            if let Some(trade_ticket) = &mut fx_viewer_app.trade_ticket {
+                trade_ticket.result = Some(result);
+            }
+            fx_viewer_app.order_result = None;
+        }
+    }
+    let sending = fx_viewer_app.order_result.is_some();
+    let Some(trade_ticket) = &mut fx_viewer_app.trade_ticket else {
+        return;
+    };
+    let mut open = true;
+    let mut confirmed: Option<Order> = None;
+    egui::Window::new(format!("Trade {}", trade_ticket.currency_pair))
+        .open(&mut open)
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Amount (M)");
+                ui.add(egui::DragValue::new(&mut trade_ticket.volume).range(1..=i32::MAX));
+            });
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut trade_ticket.market, "Market");
+                ui.label("Limit");
+                ui.add_enabled(
+                    !trade_ticket.market,
+                    egui::TextEdit::singleline(&mut trade_ticket.limit_price).desired_width(80.),
+                );
+            });
+            match trade_ticket.pending.clone() {
+                None if sending => {
+                    ui.label("Sending to the liquidity providers...");
+                }
+                None => {
+                    ui.horizontal(|ui| {
+                        for side in [OrderSide::Buy, OrderSide::Sell] {
+                            let button =
+                                egui::Button::new(RichText::new(side.to_string()).strong())
+                                    .selected(side == trade_ticket.side);
+                            if ui.add(button).clicked() {
+                                match trade_ticket.order(side) {
+                                    Ok(order) => trade_ticket.pending = Some(order),
+                                    Err(e) => trade_ticket.result = Some(Err(e)),
+                                }
+                            }
+                        }
+                    });
+                }
+                Some(order) => {
+                    let price = match order.order_type {
+                        OrderType::Market => String::from("at market"),
+                        OrderType::Limit(limit_price) => format!("limit {limit_price}"),
+                    };
+                    ui.label(format!(
+                        "{} {}M {} {price}?",
+                        order.side, order.volume, order.currency_pair
+                    ));
+                    ui.horizontal(|ui| {
+                        if ui.button("Confirm").clicked() {
+                            confirmed = trade_ticket.pending.take();
+                        }
+                        if ui.button("Cancel").clicked() {
+                            trade_ticket.pending = None;
+                        }
+                    });
+                }
+            }
+            match &trade_ticket.result {
+                Some(Ok(execution)) => {
+                    ui.separator();
+                    let mut summary = format!(
+                        "{} {}M of {}M",
+                        execution.order.side,
+                        execution.filled_volume(),
+                        execution.order.volume
+                    );
+                    if let Some(vwap) = execution.vwap() {
+                        summary.push_str(&format!(" at VWAP {vwap:.6}"));
+                    }
+                    ui.label(summary);
+                    for fill in &execution.fills {
+                        ui.label(format!(
+                            "{}: {}M @ {}",
+                            fill.liquidity_provider, fill.volume, fill.price
+                        ));
+                    }
+                    if execution.unfilled_volume() > 0 {
+                        ui.label(format!("{}M cancelled", execution.unfilled_volume()));
+                    }
+                }
+                Some(Err(e)) => {
+                    ui.separator();
+                    ui.colored_label(Color32::RED, e);
+                }
+                None => {}
+            }
+        });
+    if let Some(order) = confirmed {
+        trade_ticket.result = None;
+        fx_viewer_app.orders_sent += 1;
+        let order_id = fx_viewer_app.orders_sent.to_string();
+        let fx_books_mutex = Arc::clone(&fx_viewer_app.fx_books_mutex);
+        let venues = Arc::clone(&fx_viewer_app.venues);
+        let router_settings = fx_viewer_app.settings.router.clone();
+        let clock_mode = fx_viewer_app.settings.clock;
+        let (tx, rx) = channel();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            let mut venues = venues.lock().unwrap(); // panic if can't get lock
+            let mut sim_clock = SimClock::new(clock_mode, clock::system_timestamp());
+            let result = crate::run(venue::route_and_send(
+                &mut venues,
+                &order,
+                &router_settings,
+                &order_id,
+                &fx_books_mutex,
+                &mut sim_clock,
+            ))
+            .map_err(|e| e.to_string());
+            // the ticket may have been closed while the order was sent
+            let _ = tx.send(result);
+            ctx.request_repaint();
+        });
+        fx_viewer_app.order_result = Some(rx);
+    }
+    if !open {
+        fx_viewer_app.trade_ticket = None;
+    }
+}
+
+pub fn render_status_bar(fx_viewer_app: &mut FxViewerApp, ctx: &egui::Context) {
+    // spread, mid, message rate and last update of the selected book and how many of its feeds are live, stalled or completed
+    let now = clock::system_timestamp();
+    let fx_books = fx_viewer_app.fx_books_mutex.lock().unwrap(); // panic if can't get lock
+    let fx_book = fx_books.get(&fx_viewer_app.selected_pair);
+    let spread_pips = fx_book.and_then(|fx_book| {
+        let spread = fx_book.sell_book.first()?.price - fx_book.buy_book.first()?.price;
+        Some(spread.to_pips(pip_decimals(&fx_book.currency_pair)))
+    });
+    let mid = fx_book.and_then(position::mid_price);
+    let last_update = fx_book
+        .map(|fx_book| fx_book.timestamp)
+        .filter(|timestamp| *timestamp > 0);
+    let message_rate = fx_books
+        .feeds
+        .message_rate(&fx_viewer_app.selected_pair, now);
+    let mut status_counts: BTreeMap<String, (usize, Color32)> = BTreeMap::new();
+    for feed in fx_books
+        .feeds
+        .pair_feeds(&fx_viewer_app.selected_pair)
+        .into_iter()
+        .flat_map(|pair_feeds| pair_feeds.values())
+    {
+        let status = feed.status(now);
+        status_counts
+            .entry(status.to_string())
+            .or_insert((0, feed_status_color(status)))
+            .0 += 1;
+    }
+    drop(fx_books);
+    egui::TopBottomPanel::bottom("status_bar").show(ctx, |ui| {
+        ui.horizontal(|ui| {
+            ui.label(match spread_pips {
+                Some(spread_pips) => format!("Spread {spread_pips:.1} pips"),
+                None => String::from("Spread -"),
+            });
+            ui.separator();
+            ui.label(match mid {
+                Some(mid) => format!("Mid {mid:.6}"),
+                None => String::from("Mid -"),
+            });
+            ui.separator();
+            ui.label(format!("{message_rate:.1} msg/s"));
+            ui.separator();
+            ui.label(match last_update {
+                Some(timestamp) => format!("Last update {}", format_timestamp(timestamp)),
+                None => String::from("No updates"),
+            });
+            for (status, (count, color)) in &status_counts {
+                ui.separator();
+                ui.colored_label(*color, format!("{count} {status}"));
+            }
+        });
+    });
+}
+
+pub fn render_lp_statistics_panel(fx_viewer_app: &mut FxViewerApp, ctx: &egui::Context) {
+    // the feed of each liquidity provider of the selected currency pair, to tell a stalled liquidity provider from a quiet market
+    let now = clock::system_timestamp();
+    let pair_feeds = {
+        let fx_books = fx_viewer_app.fx_books_mutex.lock().unwrap(); // panic if can't get lock
+        fx_books
+            .feeds
+            .pair_feeds(&fx_viewer_app.selected_pair)
+            .cloned()
+            .unwrap_or_default()
+    }; // mutex lock released here
+    egui::TopBottomPanel::bottom("lp_statistics_panel").show(ctx, |ui| {
+        ui.heading("Liquidity providers");
+        TableBuilder::new(ui)
+            .id_salt("lp_statistics")
+            .striped(true)
+            .columns(egui_extras::Column::auto().resizable(true), 6)
+            .cell_layout(egui::Layout::default().with_cross_align(egui::Align::Center))
+            .header(20.0, |mut header| {
+                for heading in [
+                    "",
+                    "Status",
+                    "Messages",
+                    "Rate (msg/s)",
+                    "Last update",
+                    "Since last (s)",
+                ] {
+                    header.col(|ui| {
+                        ui.strong(heading);
+                    });
+                }
+            })
+            .body(|mut body| {
+                for (liquidity_provider, feed) in &pair_feeds {
+                    let status = feed.status(now);
+                    body.row(20.0, |mut row| {
+                        row.col(|ui| {
+                            ui.label(
+                                RichText::new(format!(" {liquidity_provider} "))
+                                    .color(Color32::BLACK)
+                                    .background_color(lp_color(liquidity_provider)),
+                            );
+                        });
+                        row.col(|ui| {
+                            ui.colored_label(feed_status_color(status), status.to_string());
+                        });
+                        row.col(|ui| {
+                            ui.label(feed.messages.to_string());
+                        });
+                        row.col(|ui| {
+                            ui.label(format!("{:.1}", feed.message_rate(now)));
+                        });
+                        row.col(|ui| {
+                            if let Some(timestamp) = feed.last_timestamp {
+                                ui.label(format_timestamp(timestamp));
+                            }
+                        });
+                        row.col(|ui| {
+                            if let Some(secs) = feed.secs_since_last(now) {
+                                ui.label(format!("{secs:.1}"));
+                            }
+                        });
+                    });
+                }
+            });
+    });
+    // keep the time since each feed's last message ticking when no book changes
+    ctx.request_repaint_after(std::time::Duration::from_secs(1));
+}
+
+fn feed_status_color(status: FeedStatus) -> Color32 {
+    match status {
+        FeedStatus::Waiting => Color32::GRAY,
+        FeedStatus::Live => Color32::GREEN,
+        FeedStatus::Stalled => Color32::YELLOW,
+        FeedStatus::Completed => Color32::LIGHT_BLUE,
+    }
+}
+
+fn format_timestamp(timestamp: u64) -> String {
+    // nanoseconds since the unix epoch as a time of day to the millisecond
+    DateTime::<Utc>::from_timestamp_nanos(timestamp as i64)
+        .format("%H:%M:%S%.3f")
+        .to_string()
+}
+
+pub fn render_positions_panel(fx_viewer_app: &mut FxViewerApp, ctx: &egui::Context) {
+    // the position of the selected currency pair and of each liquidity provider within it, marked to the book mid
+    let fx_books = fx_viewer_app.fx_books_mutex.lock().unwrap(); // panic if can't get lock
+    let mid = fx_books
+        .get(&fx_viewer_app.selected_pair)
+        .and_then(position::mid_price);
+    let pair_position = fx_books
+        .positions
+        .get(&fx_viewer_app.selected_pair)
+        .cloned()
+        .unwrap_or_default();
+    drop(fx_books);
+    egui::TopBottomPanel::bottom("positions_panel").show(ctx, |ui| {
+        ui.heading("Positions");
+        TableBuilder::new(ui)
+            .id_salt("positions")
+            .striped(true)
+            .columns(egui_extras::Column::auto().resizable(true), 6)
+            .cell_layout(egui::Layout::default().with_cross_align(egui::Align::Center))
+            .header(20.0, |mut header| {
+                for heading in [
+                    "",
+                    "Net (M)",
+                    "Avg price",
+                    "Realized P&L",
+                    "Unrealized P&L",
+                    "Exposure",
+                ] {
+                    header.col(|ui| {
+                        ui.strong(heading);
+                    });
+                }
+            })
+            .body(|mut body| {
+                render_position_row(
+                    &mut body,
+                    &fx_viewer_app.selected_pair,
+                    &pair_position.position,
+                    mid,
+                );
+                for (liquidity_provider, lp_position) in &pair_position.lp_positions {
+                    render_position_row(&mut body, liquidity_provider, lp_position, mid);
+                }
+            });
+    });
+}
+
+fn render_position_row(
+    body: &mut TableBody<'_>,
+    name: &str,
+    position: &Position,
+    mid: Option<f64>,
+) {
+    let pnl_text = |pnl: f64| {
+        let color = if pnl < 0.0 {
+            Color32::RED
+        } else {
+            Color32::GREEN
+        };
+        RichText::new(format!("{pnl:.2}")).color(color)
+    };
+    body.row(20.0, |mut row| {
+        row.col(|ui| {
+            ui.label(name);
+        });
+        row.col(|ui| {
+            ui.label(position.net_volume.to_string());
+        });
+        row.col(|ui| {
+            if position.net_volume != 0 {
+                ui.label(format!("{:.6}", position.average_price));
+            }
+        });
+        row.col(|ui| {
+            ui.label(pnl_text(position.realized_pnl));
+        });
+        row.col(|ui| {
+            if let Some(mid) = mid {
+                ui.label(pnl_text(position.unrealized_pnl(mid)));
+            }
+        });
+        row.col(|ui| {
+            if let Some(mid) = mid {
+                ui.label(format!("{:.2}", position.exposure(mid)));
+            }
+        });
+    });
+}
+
 fn render_sell_table_header(mut header: TableRow<'_, '_>) {
     header.col(|ui| {
         ui.heading("Price");
@@ -83,60 +975,123 @@ fn render_buy_table_header(mut header: TableRow<'_, '_>) {
     });
 }
 
-fn render_buy_table_body(mut body: TableBody<'_>, buy_book: &Vec<aggregator::FxAggBookEntry>) {
-    for entry in buy_book {
-        let lp_vol_vec = &entry.lp_vol;
+fn render_book_table_body(
+    mut body: TableBody<'_>,
+    side: BookSide,
+    book: &[aggregator::FxAggBookEntry],
+    book_highlighter: &BookHighlighter,
+    now: f64,
+) -> Option<usize> {
+    // the index of the level whose price was clicked. Levels just removed are shown struck through, in price order,
+    // until their flash fades. The buy book columns mirror the sell book's, with the price nearest the middle
+    let mut clicked = None;
+    let mut removed_levels = book_highlighter.removed_levels(side).into_iter().peekable();
+    let better = |a: Price, b: Price| match side {
+        BookSide::Buy => a > b,
+        BookSide::Sell => a < b,
+    };
+    let side_color = match side {
+        BookSide::Buy => BID_COLOR,
+        BookSide::Sell => ASK_COLOR,
+    };
+    let mut index = 0;
+    loop {
+        let entry = book.get(index);
+        let removed = removed_levels
+            .next_if(|removed| entry.is_none_or(|entry| better(removed.price, entry.price)));
+        let (price, volume, lp_vol_vec) = match (removed, entry) {
+            (Some(removed), _) => (removed.price, removed.volume, &removed.lp_vol),
+            (None, Some(entry)) => (entry.price, entry.volume, &entry.lp_vol),
+            (None, None) => break,
+        };
+        let flash = book_highlighter.flash(side, price, now);
+        // paints the background of a cell while its level flashes
+        let highlight = |ui: &mut egui::Ui| {
+            if let Some(color) = flash {
+                ui.painter().rect_filled(ui.max_rect(), 0.0, color);
+            }
+        };
         body.row(30.0, |mut row| {
-            row.col(|ui| {
-                let lp_agg_text = create_lp_agg_string(lp_vol_vec);
-                ui.label(lp_agg_text);
-            });
-
-            row.col(|ui| {
-                ui.label(format!("{:?}", entry.volume));
-            });
-            row.col(|ui| {
-                ui.label(RichText::new(format!("{:?}", entry.price)).color(Color32::GREEN));
-            });
+            let mut price_col = |row: &mut TableRow<'_, '_>| {
+                row.col(|ui| {
+                    highlight(ui);
+                    if removed.is_some() {
+                        ui.label(RichText::new(price.to_string()).strikethrough().weak());
+                        return;
+                    }
+                    // the top of book is coloured by the direction it last ticked
+                    let color = match (index, book_highlighter.tick(side)) {
+                        (0, Some(Ordering::Greater)) => UPTICK_COLOR,
+                        (0, Some(Ordering::Less)) => DOWNTICK_COLOR,
+                        _ => side_color,
+                    };
+                    if price_label(ui, price, color) {
+                        clicked = Some(index);
+                    }
+                });
+            };
+            let volume_col = |row: &mut TableRow<'_, '_>| {
+                row.col(|ui| {
+                    highlight(ui);
+                    let text = RichText::new(format!("{volume:?}"));
+                    ui.label(if removed.is_some() {
+                        text.strikethrough().weak()
+                    } else {
+                        text
+                    });
+                });
+            };
+            let lp_col = |row: &mut TableRow<'_, '_>| {
+                row.col(|ui| {
+                    highlight(ui);
+                    lp_badges(ui, lp_vol_vec);
+                });
+            };
+            match side {
+                BookSide::Buy => {
+                    lp_col(&mut row);
+                    volume_col(&mut row);
+                    price_col(&mut row);
+                }
+                BookSide::Sell => {
+                    price_col(&mut row);
+                    volume_col(&mut row);
+                    lp_col(&mut row);
+                }
+            }
         });
-    }
-}
-
-fn create_lp_agg_string(lp_vol_vec: &Vec<(String, i32)>) -> String {
-    let mut lp_vol = String::from("(");
-    let len = lp_vol_vec.len() - 1;
-    let mut index = 0;
-    for val in lp_vol_vec {
-        if index == 0 && len == 0 {
-            lp_vol = format!("{}{}: {})", lp_vol, val.0, val.1);
-        } else if index == 0 {
-            lp_vol = format!("{}{}: {},", lp_vol, val.0, val.1);
-        } else if index == len {
-            lp_vol = format!("{} {}: {})", lp_vol, val.0, val.1);
-        } else {
-            lp_vol = format!("{} {}: {},", lp_vol, val.0, val.1);
+        if removed.is_none() {
+            index += 1;
         }
-        index += 1;
     }
-    lp_vol
+    clicked
 }
 
-fn render_sell_table_body(mut body: TableBody<'_>, sell_book: &Vec<aggregator::FxAggBookEntry>) {
-    for entry in sell_book {
-        let lp_vol_vec = &entry.lp_vol;
-        body.row(30.0, |mut row| {
-            row.col(|ui| {
-                ui.label(RichText::new(format!("{:?}", entry.price)).color(Color32::GREEN));
-            });
+fn create_lp_agg_string(lp_vol_vec: &[(String, i32)]) -> String {
+    let lp_vols: Vec<String> = lp_vol_vec
+        .iter()
+        .map(|lp_vol| format!("{}: {}", lp_vol.0, lp_vol.1))
+        .collect();
+    format!("({})", lp_vols.join(", "))
+}
 
-            row.col(|ui| {
-                ui.label(format!("{:?}", entry.volume));
-            });
+fn price_label(ui: &mut egui::Ui, price: Price, color: Color32) -> bool {
+    // prices are clicked to open a trade ticket
+    ui.add(Label::new(RichText::new(price.to_string()).color(color)).sense(egui::Sense::click()))
+        .on_hover_cursor(egui::CursorIcon::PointingHand)
+        .on_hover_text("Click to trade")
+        .clicked()
+}
 
-            row.col(|ui| {
-                let lp_agg_text = create_lp_agg_string(lp_vol_vec);
-                ui.label(lp_agg_text);
-            });
-        });
-    }
+fn lp_badges(ui: &mut egui::Ui, lp_vol_vec: &[(String, i32, String)]) {
+    // a badge in the liquidity provider's colour for each liquidity provider's volume at the level
+    ui.horizontal(|ui| {
+        for (liquidity_provider, volume, _) in lp_vol_vec {
+            ui.label(
+                RichText::new(format!(" {liquidity_provider}: {volume} "))
+                    .color(Color32::BLACK)
+                    .background_color(lp_color(liquidity_provider)),
+            );
+        }
+    });
 }
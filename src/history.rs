@@ -0,0 +1,170 @@
+//! # FX Simulator and Aggregator - fx_sim_agg_gui
+//!
+//! `history.rs` keeps a bounded history of each aggregated `FxBook` - the top of book and every liquidity provider's top quote
+//! at each update - for the real-time price chart. The oldest updates are dropped once the history is full.
+use crate::aggregator::{BookSide, FxBook};
+use crate::fix::MdEntry;
+use crate::price::Price;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+// updates kept for each currency pair
+pub const HISTORY_CAPACITY: usize = 10_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PricePoint {
+    pub timestamp: u64,
+    // top of the aggregated buy and sell books
+    pub bid: Option<Price>,
+    pub ask: Option<Price>,
+    // buy and sell quote of the top (1M) tier of each liquidity provider's ladder, its smallest volume tier, while it has volume
+    pub lp_quotes: BTreeMap<String, (Option<Price>, Option<Price>)>,
+}
+
+impl PricePoint {
+    pub fn from_fx_book(fx_book: &FxBook) -> Self {
+        let lp_quotes = fx_book
+            .lp_books
+            .iter()
+            .map(|(liquidity_provider, lp_book)| {
+                let quoted_volumes = fx_book.lp_quoted_volumes.get(liquidity_provider);
+                let quoted_volume = |entry: &MdEntry| {
+                    quoted_volumes
+                        .and_then(|quoted_volumes| quoted_volumes.get(&entry.entry_id))
+                        .copied()
+                        .unwrap_or(entry.volume)
+                };
+                // the quote of the smallest volume tier, the best price of that tier if there is more than one,
+                // or None once the tier has been lifted so the chart shows a gap rather than a larger tier
+                let top_tier_price = |side: BookSide| {
+                    let quotes = lp_book.values().filter(|entry| entry.side == side);
+                    let top_tier = quotes.clone().map(quoted_volume).min()?;
+                    let prices = quotes
+                        .filter(|entry| quoted_volume(entry) == top_tier && entry.volume > 0)
+                        .map(|entry| entry.price);
+                    match side {
+                        BookSide::Buy => prices.max(),
+                        BookSide::Sell => prices.min(),
+                    }
+                };
+                (
+                    liquidity_provider.clone(),
+                    (
+                        top_tier_price(BookSide::Buy),
+                        top_tier_price(BookSide::Sell),
+                    ),
+                )
+            })
+            .collect();
+        PricePoint {
+            timestamp: fx_book.timestamp,
+            bid: fx_book.buy_book.first().map(|level| level.price),
+            ask: fx_book.sell_book.first().map(|level| level.price),
+            lp_quotes,
+        }
+    }
+
+    pub fn mid(&self) -> Option<f64> {
+        Some((self.bid?.to_f64() + self.ask?.to_f64()) / 2.0)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PriceHistory {
+    capacity: usize,
+    points: VecDeque<PricePoint>,
+}
+
+impl Default for PriceHistory {
+    fn default() -> Self {
+        PriceHistory::new(HISTORY_CAPACITY)
+    }
+}
+
+impl PriceHistory {
+    pub fn new(capacity: usize) -> Self {
+        PriceHistory {
+            capacity,
+            points: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub fn record(&mut self, fx_book: &FxBook) {
+        if self.points.len() == self.capacity {
+            self.points.pop_front();
+        }
+        self.points.push_back(PricePoint::from_fx_book(fx_book));
+    }
+
+    pub fn points(&self) -> &VecDeque<PricePoint> {
+        &self.points
+    }
+
+    pub fn latest_timestamp(&self) -> Option<u64> {
+        self.points.back().map(|point| point.timestamp)
+    }
+
+    pub fn since(&self, timestamp: u64) -> impl Iterator<Item = &PricePoint> {
+        // the points are in the order the book was updated, so oldest first
+        self.points
+            .iter()
+            .skip_while(move |point| point.timestamp < timestamp)
+    }
+
+    pub fn liquidity_providers(&self) -> BTreeSet<String> {
+        self.points
+            .iter()
+            .flat_map(|point| point.lp_quotes.keys().cloned())
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimeWindow {
+    OneMinute,
+    #[default]
+    FiveMinutes,
+    FifteenMinutes,
+    OneHour,
+    All,
+}
+
+impl TimeWindow {
+    pub const ALL: [TimeWindow; 5] = [
+        TimeWindow::OneMinute,
+        TimeWindow::FiveMinutes,
+        TimeWindow::FifteenMinutes,
+        TimeWindow::OneHour,
+        TimeWindow::All,
+    ];
+
+    pub fn secs(&self) -> Option<u64> {
+        match self {
+            Self::OneMinute => Some(60),
+            Self::FiveMinutes => Some(300),
+            Self::FifteenMinutes => Some(900),
+            Self::OneHour => Some(3600),
+            Self::All => None,
+        }
+    }
+
+    pub fn start(&self, latest_timestamp: u64) -> u64 {
+        // the earliest timestamp in the window ending at the latest update
+        self.secs().map_or(0, |secs| {
+            latest_timestamp.saturating_sub(secs * 1_000_000_000)
+        })
+    }
+}
+
+impl Display for TimeWindow {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OneMinute => f.write_str("1 min"),
+            Self::FiveMinutes => f.write_str("5 min"),
+            Self::FifteenMinutes => f.write_str("15 min"),
+            Self::OneHour => f.write_str("1 hour"),
+            Self::All => f.write_str("All"),
+        }
+    }
+}
@@ -7,20 +7,57 @@
 //! - `simulator.rs` generates simulated FX market data and sends the data as asynchronous market data streams
 //! - `aggregator.rs` updates and aggregates the asynchronous data streams into a real-time FX book of buys and sells
 //! - `lib.rs` Includes the thread which combines all the individual asynchronous market data streams from each liquidity provider into a single merged stream
-//! that yields values in the order they arrive from the source market data streams. Also incudes the FxViewerApp structure which initiates and updates the GUI.
-//! Various utilities used by the other modules are also in this library.
+//!   that yields values in the order they arrive from the source market data streams. Also incudes the FxViewerApp structure which initiates and updates the GUI.
+//!   Various utilities used by the other modules are also in this library.
 //! - `gui.rs` Contains the definition of the GUI components and how to render them.
-mod aggregator;
+//! - `feed.rs` Keeps per liquidity provider feed statistics - message counts, rates, last update and stream completion - to spot stalled feeds.
+//! - `history.rs` Keeps a bounded history of each book's top of book and liquidity provider quotes for the real-time price chart.
+//! - `listener.rs` Defines the `BookListener` trait for pluggable subscribers to book changes such as the GUI repaint,
+//!   the terminal ladder and the book recorder.
+//! - `execution.rs` Executes market and limit orders against the aggregated book, sweeping its levels and returning the fills and VWAP.
+//! - `position.rs` Keeps the net position, average cost and realized and unrealized P&L of each currency pair and liquidity provider from fills.
+//! - `mid_price.rs` Provides the shared mid price path of a currency pair that liquidity providers quote around.
+//! - `price.rs` Defines the fixed-point `Price` type used for all prices, spreads and markups.
+//! - `cli.rs` Defines the command line interface of `gui`, `headless`, `replay` and `validate-config` subcommands.
+//! - `clock.rs` Provides the simulation clock for real time, accelerated and as fast as possible simulations in virtual time.
+//! - `config.rs` Reads and validates the YAML config file of global settings and liquidity provider sections.
+//! - `arrival.rs` Defines the uniform, fixed rate, Poisson and bursty Hawkes quote arrival models with optional quiet periods.
+//! - `price_model.rs` Defines the trend, geometric Brownian motion, mean reverting and jump-diffusion price models.
+//! - `router.rs` Splits an order into child orders per liquidity provider by a routing policy and reports the expected slippage.
+//! - `risk.rs` Checks orders against the maximum order size, net position, liquidity provider notional and price collar, with a kill switch.
+//! - `venue.rs` Simulates each liquidity provider filling or rejecting child orders after its latency and last look, returning FIX execution reports.
+//! - `replay.rs` Replays a recorded FIX or pipe delimited market data log into the aggregator with original, accelerated or step-by-step timing.
+//! - `fix.rs` Encodes the simulated market data as FIX 4.4 MarketDataSnapshotFullRefresh (35=W) and MarketDataIncrementalRefresh (35=X)
+//!   messages and decodes them back into typed market data for the aggregator.
+pub mod aggregator;
+pub mod arrival;
+pub mod cli;
+pub mod clock;
+pub mod config;
+pub mod execution;
+pub mod feed;
+pub mod fix;
 mod gui;
+pub mod history;
+pub mod listener;
+pub mod mid_price;
+pub mod position;
+pub mod price;
+pub mod price_model;
+pub mod replay;
+pub mod risk;
+pub mod router;
 mod simulator;
 mod tests;
+pub mod venue;
 use eframe::egui;
-use egui::Context;
-use log::error;
+use listener::{BookListener, BookRecorder, LadderPrinter};
+use log::{error, info};
+use std::collections::BTreeSet;
 use std::fmt;
 use std::fmt::Display;
 use std::fmt::Formatter;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io;
 use std::io::BufWriter;
 use std::io::prelude::*;
@@ -28,10 +65,15 @@ use std::num::ParseFloatError;
 use std::num::ParseIntError;
 use std::path::Path;
 use std::process::exit;
-use std::sync::{Arc, Mutex, mpsc};
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::Duration;
 use tokio::runtime::Runtime;
+use tokio::sync::mpsc::unbounded_channel;
+use tokio::time::{Instant, timeout_at};
 use tokio_stream::StreamExt;
+use tokio_stream::wrappers::UnboundedReceiverStream;
 
 #[derive(Debug)]
 #[non_exhaustive]
@@ -41,6 +83,20 @@ pub enum AppError {
     ParseFloat(ParseFloatError),
     ParseInt(ParseIntError),
     Io(io::Error),
+    MalformedField(String),
+    BadBeginString(String),
+    BadBodyLength(usize, usize),
+    BadChecksum(u8, u8),
+    MissingTrailingSoh,
+    MissingTag(u32),
+    InvalidTagValue(u32),
+    UnknownMsgType(String),
+    BadGroupCount(usize, usize),
+    InvalidConfig(String),
+    ConfigFile(serde_yaml::Error),
+    InvalidOrder(String),
+    InvalidPrice(String),
+    Risk(risk::RiskError),
 }
 
 impl From<ParseFloatError> for AppError {
@@ -61,6 +117,18 @@ impl From<io::Error> for AppError {
     }
 }
 
+impl From<serde_yaml::Error> for AppError {
+    fn from(error: serde_yaml::Error) -> Self {
+        Self::ConfigFile(error)
+    }
+}
+
+impl From<risk::RiskError> for AppError {
+    fn from(error: risk::RiskError) -> Self {
+        Self::Risk(error)
+    }
+}
+
 impl Display for AppError {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
@@ -69,6 +137,32 @@ impl Display for AppError {
             Self::ParseFloat(e) => Display::fmt(e, f),
             Self::ParseInt(e) => Display::fmt(e, f),
             Self::Io(e) => Display::fmt(e, f),
+            Self::MalformedField(field) => write!(f, "malformed FIX field \"{field}\""),
+            Self::BadBeginString(value) => write!(f, "unsupported FIX BeginString(8) {value}"),
+            Self::BadBodyLength(expected, actual) => write!(
+                f,
+                "bad FIX BodyLength(9) - message says {expected} but body is {actual}"
+            ),
+            Self::BadChecksum(expected, actual) => write!(
+                f,
+                "bad FIX CheckSum(10) - message says {expected:03} but calculated {actual:03}"
+            ),
+            Self::MissingTrailingSoh => {
+                f.write_str("FIX message does not end with an SOH after CheckSum(10)")
+            }
+            Self::MissingTag(tag) => write!(f, "missing required FIX tag {tag}"),
+            Self::InvalidTagValue(tag) => write!(f, "invalid value for FIX tag {tag}"),
+            Self::UnknownMsgType(msg_type) => write!(f, "unknown FIX MsgType(35) {msg_type}"),
+            Self::BadGroupCount(expected, actual) => write!(
+                f,
+                "FIX NoMDEntries(268) is {expected} but message has {actual} entries"
+            ),
+            Self::InvalidConfig(reason) => write!(f, "invalid config - {reason}"),
+            // serde_yaml errors include the line and column of the offending field
+            Self::ConfigFile(e) => write!(f, "invalid config file - {e}"),
+            Self::InvalidOrder(reason) => write!(f, "invalid order - {reason}"),
+            Self::InvalidPrice(reason) => write!(f, "invalid price - {reason}"),
+            Self::Risk(e) => write!(f, "order rejected by risk check - {e}"),
         }
     }
 }
@@ -82,62 +176,156 @@ pub fn run<F: Future>(future: F) -> F::Output {
 
 #[derive(Default, Debug)]
 pub struct FxViewerApp {
-    pub fx_book_mutex: Arc<Mutex<aggregator::FxBook>>,
+    pub fx_books_mutex: Arc<Mutex<aggregator::BookManager>>,
+    // currency pair of the book currently shown in the GUI
+    pub selected_pair: String,
+    pub configs: Vec<config::Config>,
+    pub settings: config::Settings,
+    // time window of the price chart and the liquidity providers whose quotes are hidden from it
+    pub chart_window: history::TimeWindow,
+    pub hidden_lps: BTreeSet<String>,
+    // price or depth chart
+    pub chart_view: gui::ChartView,
+    // opened by clicking a price in the book
+    pub trade_ticket: Option<gui::TradeTicket>,
+    // venue of each liquidity provider that confirmed trade ticket orders are routed to, and how many have been sent
+    pub venues: Arc<Mutex<Vec<venue::LpVenue>>>,
+    pub orders_sent: u64,
+    // execution, or error, of the trade ticket order being sent to the venues
+    pub order_result: Option<Receiver<Result<execution::Execution, String>>>,
+    // changes to the book shown since it was last drawn
+    pub book_highlighter: gui::BookHighlighter,
 }
 
 impl FxViewerApp {
+    pub fn new(configs: Vec<config::Config>, settings: config::Settings) -> Self {
+        FxViewerApp {
+            configs,
+            settings,
+            ..Default::default()
+        }
+    }
+
     pub fn init(&mut self, cc: &eframe::CreationContext<'_>) -> Self {
-        let ctx = cc.egui_ctx.clone();
-        let (ctx_tx, ctx_rx) = mpsc::channel();
         // Create "FIX" log file
-        let writer = match create_log_file("logs/fix.log") {
+        let writer = match create_fix_log(&self.settings) {
             Ok(writer) => writer,
             Err(e) => {
                 error!("problem creating log file - {e}");
                 exit(1);
             }
         };
-        // read config file to get configs for each liquidity provider source
-        let mut configs: Vec<simulator::Config> = Vec::new();
-        if let Err(e) = simulator::get_configs(&mut configs) {
-            error!("config input file not processed - {e}");
-            exit(1);
-        }
-        // Create aggregated FX Book
-        let fx_book = aggregator::FxBook::new(&configs);
+        // the GUI is repainted as another subscriber to book changes
+        let mut book_listeners = match create_book_listeners(&self.settings) {
+            Ok(book_listeners) => book_listeners,
+            Err(e) => {
+                error!("problem creating book listeners - {e}");
+                exit(1);
+            }
+        };
+        book_listeners.push(Box::new(gui::RepaintListener::new(cc.egui_ctx.clone())));
+        // configs for each liquidity provider source
+        let configs = self.configs.clone();
+        let settings = self.settings.clone();
+        // Create an aggregated FX Book for each currency pair
+        let fx_books = aggregator::BookManager::new(&configs, &settings);
+        let selected_pair = fx_books
+            .currency_pairs()
+            .first()
+            .cloned()
+            .unwrap_or_default();
 
-        let fx_book_mutex = Arc::new(Mutex::new(fx_book));
-        let fx_book_mutex_ui_clone = Arc::clone(&fx_book_mutex);
-        let fx_book_mutex_fx_clone = Arc::clone(&fx_book_mutex);
+        let fx_books_mutex = Arc::new(Mutex::new(fx_books));
+        let fx_books_mutex_ui_clone = Arc::clone(&fx_books_mutex);
+        let fx_books_mutex_fx_clone = Arc::clone(&fx_books_mutex);
         thread::spawn(move || {
             // start fx thread
-            let rec_ctx: Context = ctx_rx.recv().unwrap();
-            run_async_fx_sim_agg(rec_ctx, writer, fx_book_mutex_fx_clone, &configs);
+            run_async_fx_sim_agg(
+                writer,
+                fx_books_mutex_fx_clone,
+                &configs,
+                &settings,
+                &mut book_listeners,
+            );
         }); // end of fx thread 
 
-        if let Err(e) = ctx_tx.send(ctx) {
-            error!("error sending from ctx channel - {e}");
-            exit(1);
-        }
+        gui::set_window_title(&cc.egui_ctx, &selected_pair);
+        let venues = venue::create_venues(&self.configs, self.settings.seed);
 
         Self {
-            fx_book_mutex: fx_book_mutex_ui_clone,
+            fx_books_mutex: fx_books_mutex_ui_clone,
+            selected_pair,
+            venues: Arc::new(Mutex::new(venues)),
+            configs: self.configs.clone(),
+            settings: self.settings.clone(),
+            ..Default::default()
         }
     }
 }
 
 impl eframe::App for FxViewerApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        gui::render_top_panel(ctx);
+        gui::render_top_panel(self, ctx);
+        gui::render_status_bar(self, ctx);
+        gui::render_positions_panel(self, ctx);
+        gui::render_lp_statistics_panel(self, ctx);
+        gui::render_chart_panel(self, ctx);
         gui::render_fx_book(self, ctx);
+        gui::render_trade_ticket(self, ctx);
     }
 }
 
+pub fn run_headless(
+    configs: &[config::Config],
+    settings: &config::Settings,
+) -> Result<(), AppError> {
+    // run the simulation and aggregation on this thread without a GUI, printing the books to the terminal
+    let writer = create_fix_log(settings)?;
+    let fx_books = aggregator::BookManager::new(configs, settings);
+    let fx_books_mutex = Arc::new(Mutex::new(fx_books));
+    let mut book_listeners = create_book_listeners(settings)?;
+    run_async_fx_sim_agg(
+        writer,
+        fx_books_mutex,
+        configs,
+        settings,
+        &mut book_listeners,
+    );
+    Ok(())
+}
+
+pub fn create_book_listeners(
+    settings: &config::Settings,
+) -> Result<Vec<Box<dyn BookListener>>, AppError> {
+    // every run prints the books as a ladder to the terminal and optionally records them to a file
+    let mut book_listeners: Vec<Box<dyn BookListener>> = vec![Box::new(LadderPrinter)];
+    if let Some(book_log) = &settings.logs.book_log {
+        let writer = create_log_file(book_log)?;
+        book_listeners.push(Box::new(BookRecorder::new(writer)));
+    }
+    Ok(book_listeners)
+}
+
+pub fn create_fix_log(settings: &config::Settings) -> Result<Option<BufWriter<File>>, AppError> {
+    // replayed messages are already recorded, and the FIX log may be the very log being replayed
+    if settings.replay.is_some() {
+        return Ok(None);
+    }
+    create_log_file(&settings.logs.fix_log).map(Some)
+}
+
+// a market data message from the merged streams, or the currency pair and liquidity provider of a stream that has completed
+enum StreamEvent {
+    MarketData(String),
+    Completed(String, String),
+}
+
 pub fn run_async_fx_sim_agg(
-    rec_ctx: Context,
-    mut writer: BufWriter<File>,
-    fx_book_mutex_fx_clone: Arc<Mutex<aggregator::FxBook>>,
-    configs: &Vec<simulator::Config>,
+    mut writer: Option<BufWriter<File>>,
+    fx_books_mutex_fx_clone: Arc<Mutex<aggregator::BookManager>>,
+    configs: &[config::Config],
+    settings: &config::Settings,
+    book_listeners: &mut [Box<dyn BookListener>],
 ) {
     run(async {
         /*  async returns a future rather than blocking current thread
@@ -147,46 +335,122 @@ pub fn run_async_fx_sim_agg(
 
         // Combine all individual market data streams from each liquidity provider into a single merged stream
         // that yields values in the order they arrive from the source market data streams
-        let mut merged_streams_map = simulator::start_streams(configs);
+        let (feed_completions, completed_feeds) = unbounded_channel();
+        let merged_streams_map = match &settings.replay {
+            Some(replay) => match replay::start_replay(configs, settings, replay) {
+                Ok(replay_stream) => replay_stream,
+                Err(e) => {
+                    error!("problem replaying {} - {e}", replay.file);
+                    return;
+                }
+            },
+            None => simulator::start_streams(configs, settings, &feed_completions),
+        };
+        // each simulated liquidity provider's stream reports when it completes, so the feed statistics show a
+        // liquidity provider that has finished quoting even when every liquidity provider shares one stream
+        drop(feed_completions);
+        let mut stream_events = merged_streams_map
+            .map(|(_key, fix_message)| StreamEvent::MarketData(fix_message))
+            .merge(UnboundedReceiverStream::new(completed_feeds).map(
+                |(currency_pair, liquidity_provider)| {
+                    StreamEvent::Completed(currency_pair, liquidity_provider)
+                },
+            ));
+        // stop at the end of the run duration if there is one, otherwise when every stream completes.
+        // Virtual time simulations stop themselves at the end of the run duration in simulated time
+        let deadline = settings
+            .run_duration_secs
+            .filter(|_| !simulator::uses_virtual_time(settings))
+            .map(|secs| Instant::now() + Duration::from_secs(secs));
 
-        while let Some(val) = merged_streams_map.next().await {
+        loop {
             // await polls the future until future returns Ready.
             // If future still pending then control is handed to the runtime
-            let (_key, market_data) = val;
+            let val = match deadline {
+                Some(deadline) => match timeout_at(deadline, stream_events.next()).await {
+                    Ok(val) => val,
+                    Err(_) => {
+                        info!("run duration reached - stopping market data streams");
+                        break;
+                    }
+                },
+                None => stream_events.next().await,
+            };
+            let fix_message = match val {
+                Some(StreamEvent::MarketData(fix_message)) => fix_message,
+                Some(StreamEvent::Completed(currency_pair, liquidity_provider)) => {
+                    let mut fx_books = fx_books_mutex_fx_clone.lock().unwrap(); // panic if can't get lock
+                    fx_books.feeds.complete(&currency_pair, &liquidity_provider);
+                    continue;
+                }
+                None => break,
+            };
 
-            // write market data to a "FIX" log
-            if let Err(e) = write_to_fix_log(&mut writer, &market_data) {
+            // write market data FIX message to the FIX log
+            if let Some(writer) = &mut writer
+                && let Err(e) = write_to_fix_log(writer, &fix_message)
+            {
                 error!("problem writing to FIX log - {e}");
             }
 
-            // Update the Fx Book with the new market data
-            let mut fx_book = fx_book_mutex_fx_clone.lock().unwrap(); // panic if can't get lock
-            if let Err(e) = fx_book.update(market_data) {
-                //print/log error and continuing processing next market data values
-                error!("market data not processed - {e}");
-            } else {
-                // update GUI - send repaint request
-                rec_ctx.request_repaint();
-                // print FX book as ladder to console
-                aggregator::print_fxbook_as_ladder(&mut fx_book);
+            // decode the FIX message, rejecting any malformed market data
+            let market_data = match fix::decode_market_data(&fix_message) {
+                Ok(market_data) => market_data,
+                Err(e) => {
+                    error!("market data rejected - {e}");
+                    continue;
+                }
+            };
+
+            // Update the Fx Book for the currency pair of the new market data
+            let mut fx_books = fx_books_mutex_fx_clone.lock().unwrap(); // panic if can't get lock
+            fx_books
+                .feeds
+                .record(&market_data, clock::system_timestamp());
+            match fx_books.update(&market_data) {
+                Ok(fx_book) => {
+                    // tell every subscriber about the updated book, e.g. print the ladder or repaint the GUI
+                    for book_listener in book_listeners.iter_mut() {
+                        if let Err(e) = book_listener.on_book_update(fx_book) {
+                            error!("book listener failed - {e}");
+                        }
+                    }
+                }
+                Err(e) => {
+                    //print/log error and continuing processing next market data values
+                    error!("market data not processed - {e}");
+                }
             }
         } // mutex lock released here
+        // every stream has ended or been stopped at the end of the run duration
+        fx_books_mutex_fx_clone.lock().unwrap().feeds.complete_all(); // panic if can't get lock
+        if let Some(writer) = &mut writer
+            && let Err(e) = writer.flush()
+        {
+            error!("problem flushing FIX log - {e}");
+        }
+        for book_listener in book_listeners.iter_mut() {
+            if let Err(e) = book_listener.on_finish() {
+                error!("book listener failed to finish - {e}");
+            }
+        }
     });
 }
 
 pub fn create_log_file(file_path: &str) -> Result<BufWriter<File>, AppError> {
     let path = Path::new(file_path);
+    // create the log directory if it doesn't exist yet
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
 
     // Open a file in write-only mode, returns `io::Result<File>`
-    let file = File::create(&path)?;
+    let file = File::create(path)?;
 
     Ok(BufWriter::new(file))
 }
 
-pub fn write_to_fix_log(
-    writer: &mut BufWriter<File>,
-    market_data: &String,
-) -> Result<(), AppError> {
+pub fn write_to_fix_log(writer: &mut BufWriter<File>, market_data: &str) -> Result<(), AppError> {
     writeln!(writer, "{}", market_data)?;
     Ok(())
 }
@@ -194,7 +458,7 @@ pub fn write_to_fix_log(
 pub fn get_params(data: &str, number: usize) -> Result<std::str::Split<'_, &str>, AppError> {
     let value = data.split("|");
     if value.clone().count() < number {
-        return Err(AppError::NumParams);
+        Err(AppError::NumParams)
     } else {
         Ok(data.split("|"))
     }
@@ -203,7 +467,7 @@ pub fn get_params(data: &str, number: usize) -> Result<std::str::Split<'_, &str>
 pub fn get_str_field(field: Option<&str>) -> Result<&str, AppError> {
     let value = field.unwrap_or("");
     if value.trim().is_empty() {
-        return Err(AppError::IsEmpty);
+        Err(AppError::IsEmpty)
     } else {
         Ok(value.trim())
     }
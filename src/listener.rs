@@ -0,0 +1,75 @@
+//! # FX Simulator and Aggregator - fx_sim_agg_gui
+//!
+//! `listener.rs` defines the `BookListener` trait for subscribers to aggregated book changes, together with the
+//! terminal ladder printer and the book recorder. Any number of listeners are passed to `run_async_fx_sim_agg`
+//! and each is called in turn after every successful book update.
+use crate::AppError;
+use crate::aggregator::{self, FxAggBookEntry, FxBook};
+use std::fs::File;
+use std::io::BufWriter;
+use std::io::prelude::*;
+
+pub trait BookListener: Send {
+    // called with the updated book after each market data message is aggregated
+    fn on_book_update(&mut self, fx_book: &FxBook) -> Result<(), AppError>;
+
+    // called once when the market data streams have stopped
+    fn on_finish(&mut self) -> Result<(), AppError> {
+        Ok(())
+    }
+}
+
+// prints every updated book as a ladder to the terminal
+#[derive(Debug, Default)]
+pub struct LadderPrinter;
+
+impl BookListener for LadderPrinter {
+    fn on_book_update(&mut self, fx_book: &FxBook) -> Result<(), AppError> {
+        aggregator::print_fxbook_as_ladder(fx_book);
+        Ok(())
+    }
+}
+
+// records every updated book as one line of a file
+#[derive(Debug)]
+pub struct BookRecorder {
+    writer: BufWriter<File>,
+}
+
+impl BookRecorder {
+    pub fn new(writer: BufWriter<File>) -> Self {
+        BookRecorder { writer }
+    }
+}
+
+impl BookListener for BookRecorder {
+    fn on_book_update(&mut self, fx_book: &FxBook) -> Result<(), AppError> {
+        writeln!(self.writer, "{}", format_book_record(fx_book))?;
+        Ok(())
+    }
+
+    fn on_finish(&mut self) -> Result<(), AppError> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+pub fn format_book_record(fx_book: &FxBook) -> String {
+    // timestamp|currency pair|buy levels|sell levels with each side's levels
+    // from the top of the book as price:volume separated by commas
+    format!(
+        "{}|{}|{}|{}",
+        fx_book.timestamp,
+        fx_book.currency_pair,
+        format_levels(&fx_book.buy_book),
+        format_levels(&fx_book.sell_book)
+    )
+}
+
+fn format_levels(fx_book_side: &[FxAggBookEntry]) -> String {
+    fx_book_side
+        .iter()
+        .map(|entry| format!("{}:{}", entry.price, entry.volume))
+        .collect::<Vec<String>>()
+        .join(",")
+}
@@ -7,29 +7,118 @@
 //! - `simulator.rs` generates simulated FX market data and sends the data as asynchronous market data streams
 //! - `aggregator.rs` updates and aggregates the asynchronous data streams into a real-time FX book of buys and sells
 //! - `lib.rs` Includes the thread which combines all the individual asynchronous market data streams from each liquidity provider into a single merged stream
-//! that yields values in the order they arrive from the source market data streams. Also incudes the FxViewerApp structure which initiates and updates the GUI.
-//! Various utilities used by the other modules are also in this library.
+//!   that yields values in the order they arrive from the source market data streams. Also incudes the FxViewerApp structure which initiates and updates the GUI.
+//!   Various utilities used by the other modules are also in this library.
 //! - `gui.rs` Contains the definition of the GUI components and how to render them.
+//! - `feed.rs` Keeps per liquidity provider feed statistics - message counts, rates, last update and stream completion - to spot stalled feeds.
+//! - `history.rs` Keeps a bounded history of each book's top of book and liquidity provider quotes for the real-time price chart.
+//! - `listener.rs` Defines the `BookListener` trait for pluggable subscribers to book changes such as the GUI repaint,
+//!   the terminal ladder and the book recorder.
+//! - `execution.rs` Executes market and limit orders against the aggregated book, sweeping its levels and returning the fills and VWAP.
+//! - `position.rs` Keeps the net position, average cost and realized and unrealized P&L of each currency pair and liquidity provider from fills.
+//! - `mid_price.rs` Provides the shared mid price path of a currency pair that liquidity providers quote around.
+//! - `price.rs` Defines the fixed-point `Price` type used for all prices, spreads and markups.
+//! - `cli.rs` Defines the command line interface of `gui`, `headless`, `replay` and `validate-config` subcommands.
+//! - `clock.rs` Provides the simulation clock for real time, accelerated and as fast as possible simulations in virtual time.
+//! - `config.rs` Reads and validates the YAML config file of global settings and liquidity provider sections.
+//! - `arrival.rs` Defines the uniform, fixed rate, Poisson and bursty Hawkes quote arrival models with optional quiet periods.
+//! - `price_model.rs` Defines the trend, geometric Brownian motion, mean reverting and jump-diffusion price models.
+//! - `router.rs` Splits an order into child orders per liquidity provider by a routing policy and reports the expected slippage.
+//! - `risk.rs` Checks orders against the maximum order size, net position, liquidity provider notional and price collar, with a kill switch.
+//! - `venue.rs` Simulates each liquidity provider filling or rejecting child orders after its latency and last look, returning FIX execution reports.
+//! - `replay.rs` Replays a recorded FIX or pipe delimited market data log into the aggregator with original, accelerated or step-by-step timing.
+//! - `fix.rs` Encodes the simulated market data as FIX 4.4 MarketDataSnapshotFullRefresh (35=W) and MarketDataIncrementalRefresh (35=X)
+//!   messages and decodes them back into typed market data for the aggregator.
 use std::process::exit;
 //use log::{debug, error, info, trace, warn};
+use clap::Parser;
 use egui::Vec2;
 use fx_sim_agg_gui::FxViewerApp;
+use fx_sim_agg_gui::cli::{Cli, Command};
+use fx_sim_agg_gui::config::{self, Config, Settings};
 use log::error;
 
 fn main() {
+    match Cli::parse().command() {
+        Command::Gui(run_args) => {
+            let (configs, settings) = init(&run_args.config_args.config, |settings| {
+                run_args.apply(settings)
+            });
+            run_gui(configs, settings);
+        }
+        Command::Headless(run_args) => {
+            let (configs, settings) = init(&run_args.config_args.config, |settings| {
+                run_args.apply(settings)
+            });
+            run_headless(&configs, &settings);
+        }
+        Command::Replay(replay_args) => {
+            let (configs, settings) = init(&replay_args.config_args.config, |settings| {
+                replay_args.apply(settings)
+            });
+            if replay_args.gui {
+                run_gui(configs, settings);
+            } else {
+                run_headless(&configs, &settings);
+            }
+        }
+        Command::ValidateConfig(config_args) => {
+            let mut configs: Vec<Config> = Vec::new();
+            match config::get_configs(&config_args.config, &mut configs) {
+                Ok(_) => println!(
+                    "config file {} is valid - {} liquidity provider streams",
+                    config_args.config,
+                    configs.len()
+                ),
+                Err(e) => {
+                    eprintln!("config file {} is not valid - {e}", config_args.config);
+                    exit(1);
+                }
+            }
+        }
+    }
+}
+
+fn init(config_path: &str, apply_args: impl FnOnce(&mut Settings)) -> (Vec<Config>, Settings) {
+    // read config file to get global settings and configs for each liquidity provider source.
+    // Logging is configured by the config file so report any problem with it to stderr
+    let mut configs: Vec<Config> = Vec::new();
+    let mut settings = match config::get_configs(config_path, &mut configs) {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprintln!("config file {config_path} not processed - {e}");
+            exit(1);
+        }
+    };
+    apply_args(&mut settings);
+
     // start log4rs logging framework
-    if let Err(e) = log4rs::init_file("logging_config.yaml", Default::default()) {
-        eprintln!("error initialising log4rs - {e}");
+    if let Err(e) = log4rs::init_file(&settings.logs.log_config, Default::default()) {
+        eprintln!(
+            "error initialising log4rs from {} - {e}",
+            settings.logs.log_config
+        );
         exit(1);
     }
 
-    let mut fx_viewer_app = FxViewerApp::default();
+    (configs, settings)
+}
+
+fn run_headless(configs: &[Config], settings: &Settings) {
+    if let Err(e) = fx_sim_agg_gui::run_headless(configs, settings) {
+        error!("error running headless - {e}");
+        exit(1);
+    }
+}
+
+fn run_gui(configs: Vec<Config>, settings: Settings) {
+    let mut fx_viewer_app = FxViewerApp::new(configs, settings);
     let win_option = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default().with_inner_size(Vec2::new(590., 350.)),
+        viewport: egui::ViewportBuilder::default().with_inner_size(Vec2::new(1200., 800.)),
         ..Default::default()
     };
     if let Err(e) = eframe::run_native(
-        "USD/EUR Aggregated Book",
+        "FX Aggregated Books",
         win_option,
         Box::new(|cc| Ok(Box::new(fx_viewer_app.init(cc)))),
     ) {
@@ -0,0 +1,88 @@
+//! # FX Simulator and Aggregator - fx_sim_agg_gui
+//!
+//! `mid_price.rs` provides the shared "true" mid price of a currency pair. The mid price path is generated by its price model
+//! in fixed steps of simulated time so every liquidity provider quoting the pair sees the same path, whichever order they ask for it.
+//! Liquidity providers then quote around the mid price with their own noise, latency and skew.
+use crate::price_model::PriceModel;
+use rand::rngs::StdRng;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+pub type SharedMidPrice = Arc<Mutex<MidPricePath>>;
+
+#[derive(Debug)]
+pub struct MidPricePath {
+    // nanoseconds since the unix epoch of the first mid price
+    start: u64,
+    step_ns: u64,
+    price_model: PriceModel,
+    pip_size: f64,
+    initial_price: f64,
+    // how far before the latest mid price asked for a liquidity provider can still ask, its largest latency
+    retain_ns: u64,
+    // step of the first mid price held
+    first_step: usize,
+    // mid price at the start of every step from first_step on, dropping steps older than retain_ns
+    prices: VecDeque<f64>,
+    rng: StdRng,
+}
+
+impl MidPricePath {
+    pub fn new(
+        start: u64,
+        step_ns: u64,
+        initial_price: f64,
+        price_model: PriceModel,
+        pip_size: f64,
+        retain_ns: u64,
+        rng: StdRng,
+    ) -> Self {
+        MidPricePath {
+            start,
+            step_ns,
+            price_model,
+            pip_size,
+            initial_price,
+            retain_ns,
+            first_step: 0,
+            prices: VecDeque::from([initial_price]),
+            rng,
+        }
+    }
+
+    pub fn retained_steps(&self) -> usize {
+        self.prices.len()
+    }
+
+    pub fn mid_at(&mut self, timestamp: u64) -> f64 {
+        // the mid price of the step containing timestamp, extending the path as far as needed.
+        // Times before the start of the path have the initial mid price and times before the steps
+        // still held have the oldest mid price held
+        let step = self.step_at(timestamp);
+        let step_secs = self.step_ns as f64 / 1_000_000_000.0;
+        while self.first_step + self.prices.len() <= step {
+            let price = self.prices[self.prices.len() - 1];
+            let next_price = self.price_model.next_price(
+                price,
+                self.initial_price,
+                step_secs,
+                self.pip_size,
+                &mut self.rng,
+            );
+            self.prices.push_back(next_price);
+        }
+        let mid = self.prices[step.saturating_sub(self.first_step)];
+
+        // no liquidity provider will ask for a mid price more than retain_ns before this one
+        let keep_from = self.step_at(timestamp.saturating_sub(self.retain_ns));
+        while self.first_step < keep_from && self.prices.len() > 1 {
+            self.prices.pop_front();
+            self.first_step += 1;
+        }
+        mid
+    }
+
+    fn step_at(&self, timestamp: u64) -> usize {
+        (timestamp.saturating_sub(self.start) / self.step_ns) as usize
+    }
+}
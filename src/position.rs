@@ -0,0 +1,142 @@
+//! # FX Simulator and Aggregator - fx_sim_agg_gui
+//!
+//! `position.rs` keeps the positions built up by fills - the net position of each currency pair and of each liquidity provider
+//! within it, its average cost and realized P&L - and marks them to the mid of the aggregated `FxBook` for unrealized P&L
+//! and exposure. P&L and exposure are in the quote (second) currency of the currency pair.
+use crate::aggregator::FxBook;
+use crate::execution::{Execution, OrderSide};
+use crate::fix::{ExecutionReport, OrdStatus, VOLUME_UNIT};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Position {
+    // net volume (M) of the base currency, long when positive and short when negative
+    pub net_volume: i32,
+    // average price of the open position, 0 when flat
+    pub average_price: f64,
+    pub realized_pnl: f64,
+}
+
+impl Position {
+    pub fn add_fill(&mut self, side: OrderSide, volume: i32, price: f64) {
+        // a fill in the direction of the position adds to it at the average price. A fill against it closes
+        // volume at the average price, realizing the difference, and any volume left over opens a new position
+        let signed_volume = match side {
+            OrderSide::Buy => volume,
+            OrderSide::Sell => -volume,
+        };
+        if self.net_volume == 0 || self.net_volume.signum() == signed_volume.signum() {
+            let open_volume = f64::from(self.net_volume.abs());
+            self.average_price = (self.average_price * open_volume + price * f64::from(volume))
+                / (open_volume + f64::from(volume));
+            self.net_volume += signed_volume;
+            return;
+        }
+        let closed_volume = volume.min(self.net_volume.abs());
+        self.realized_pnl += (price - self.average_price)
+            * f64::from(closed_volume * self.net_volume.signum())
+            * VOLUME_UNIT as f64;
+        self.net_volume += signed_volume;
+        if self.net_volume == 0 {
+            self.average_price = 0.0;
+        } else if volume > closed_volume {
+            self.average_price = price;
+        }
+    }
+
+    pub fn unrealized_pnl(&self, mid: f64) -> f64 {
+        (mid - self.average_price) * f64::from(self.net_volume) * VOLUME_UNIT as f64
+    }
+
+    pub fn exposure(&self, mid: f64) -> f64 {
+        // value of the open position at the mid
+        mid * f64::from(self.net_volume) * VOLUME_UNIT as f64
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PairPosition {
+    pub position: Position,
+    // the part of the position traded with each liquidity provider
+    pub lp_positions: BTreeMap<String, Position>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PositionKeeper {
+    pub positions: BTreeMap<String, PairPosition>,
+}
+
+impl PositionKeeper {
+    pub fn add_fill(
+        &mut self,
+        currency_pair: &str,
+        liquidity_provider: &str,
+        side: OrderSide,
+        volume: i32,
+        price: f64,
+    ) {
+        let pair_position = self
+            .positions
+            .entry(String::from(currency_pair))
+            .or_default();
+        pair_position.position.add_fill(side, volume, price);
+        pair_position
+            .lp_positions
+            .entry(String::from(liquidity_provider))
+            .or_default()
+            .add_fill(side, volume, price);
+    }
+
+    pub fn add_execution(&mut self, execution: &Execution) {
+        for fill in &execution.fills {
+            self.add_fill(
+                &execution.order.currency_pair,
+                &fill.liquidity_provider,
+                execution.order.side,
+                fill.volume,
+                fill.price.to_f64(),
+            );
+        }
+    }
+
+    pub fn add_execution_report(&mut self, report: &ExecutionReport) {
+        // only fills change the position
+        if report.ord_status == OrdStatus::Rejected || report.last_qty == 0 {
+            return;
+        }
+        self.add_fill(
+            &report.currency_pair,
+            &report.liquidity_provider,
+            report.side,
+            report.last_qty,
+            report.last_px.to_f64(),
+        );
+    }
+
+    pub fn get(&self, currency_pair: &str) -> Option<&PairPosition> {
+        self.positions.get(currency_pair)
+    }
+
+    pub fn realized_pnl(&self, currency_pair: &str) -> f64 {
+        self.get(currency_pair)
+            .map_or(0.0, |pair_position| pair_position.position.realized_pnl)
+    }
+
+    pub fn unrealized_pnl(&self, fx_book: &FxBook) -> Option<f64> {
+        // None until the book has a mid to mark the position to
+        let mid = mid_price(fx_book)?;
+        Some(
+            self.get(&fx_book.currency_pair)
+                .map_or(0.0, |pair_position| {
+                    pair_position.position.unrealized_pnl(mid)
+                }),
+        )
+    }
+}
+
+pub fn mid_price(fx_book: &FxBook) -> Option<f64> {
+    // halfway between the top of the buy and sell books
+    let best_buy = fx_book.buy_book.first()?.price.to_f64();
+    let best_sell = fx_book.sell_book.first()?.price.to_f64();
+    Some((best_buy + best_sell) / 2.0)
+}
@@ -0,0 +1,266 @@
+//! # FX Simulator and Aggregator - fx_sim_agg_gui
+//!
+//! `price.rs` defines the fixed-point `Price` type used for all FX prices, spreads and markups.
+//! A price is held as an integer number of ticks together with the number of decimal places a tick
+//! represents, so prices compare exactly and two quotes at the same level always aggregate together.
+use crate::AppError;
+use std::cmp::Ordering;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::ops::{Add, AddAssign, Neg, Sub, SubAssign};
+use std::str::FromStr;
+
+// most decimal places a parsed price may have, so one of its whole units still fits in an i64 of ticks
+pub const MAX_DECIMALS: u32 = 18;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Price {
+    ticks: i64,
+    decimals: u32,
+}
+
+/// Number of decimal places in one pip for a currency pair.
+/// JPY pairs are quoted to 2 decimal places and all other pairs to 4 decimal places.
+pub fn pip_decimals(currency_pair: &str) -> u32 {
+    if currency_pair.to_uppercase().contains("JPY") {
+        2
+    } else {
+        4
+    }
+}
+
+impl Price {
+    pub fn new(ticks: i64, decimals: u32) -> Self {
+        Price { ticks, decimals }
+    }
+
+    /// Convert a floating point value to the nearest tick at the given number of decimal places
+    pub fn from_f64(value: f64, decimals: u32) -> Self {
+        let ticks = (value * 10f64.powi(decimals as i32)).round() as i64;
+        Price { ticks, decimals }
+    }
+
+    /// Convert a number of pips (which may be fractional) to a price difference, where one pip
+    /// is one tick at `decimals` decimal places. Fractional pips are rounded to the nearest pip.
+    pub fn from_pips(pips: f64, decimals: u32) -> Self {
+        Price {
+            ticks: pips.round() as i64,
+            decimals,
+        }
+    }
+
+    pub fn ticks(&self) -> i64 {
+        self.ticks
+    }
+
+    pub fn decimals(&self) -> u32 {
+        self.decimals
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.ticks as f64 / 10f64.powi(self.decimals as i32)
+    }
+
+    /// Number of pips in this price (or price difference) where one pip is at `pip_decimals` decimal places
+    pub fn to_pips(&self, pip_decimals: u32) -> f64 {
+        self.ticks as f64 * 10f64.powi(pip_decimals as i32 - self.decimals as i32)
+    }
+
+    /// Re-express the price at a different number of decimal places, rounding half away from zero
+    pub fn rescale(&self, decimals: u32) -> Result<Self, AppError> {
+        let overflow =
+            || AppError::InvalidPrice(format!("{self} overflows at {decimals} decimal places"));
+        if decimals >= self.decimals {
+            let ticks = 10i64
+                .checked_pow(decimals - self.decimals)
+                .and_then(|factor| self.ticks.checked_mul(factor))
+                .ok_or_else(overflow)?;
+            return Ok(Price { ticks, decimals });
+        }
+        // a factor too large for i64 rounds every price to 0 ticks
+        let Some(factor) = 10i64.checked_pow(self.decimals - decimals) else {
+            return Ok(Price { ticks: 0, decimals });
+        };
+        let half = factor / 2;
+        let ticks = if self.ticks >= 0 {
+            self.ticks.checked_add(half)
+        } else {
+            self.ticks.checked_sub(half)
+        }
+        .ok_or_else(overflow)?
+            / factor;
+        Ok(Price { ticks, decimals })
+    }
+
+    // ticks of both prices expressed at the larger of the two scales, saturating at the limits of i128
+    fn normalised_ticks(&self, other: &Price) -> (i128, i128) {
+        let decimals = self.decimals.max(other.decimals);
+        let scale = |price: &Price| {
+            if price.ticks == 0 {
+                return 0;
+            }
+            10i128
+                .checked_pow(decimals - price.decimals)
+                .and_then(|factor| i128::from(price.ticks).checked_mul(factor))
+                .unwrap_or(if price.ticks < 0 {
+                    i128::MIN
+                } else {
+                    i128::MAX
+                })
+        };
+        (scale(self), scale(other))
+    }
+}
+
+impl PartialEq for Price {
+    fn eq(&self, other: &Self) -> bool {
+        let (lhs, rhs) = self.normalised_ticks(other);
+        lhs == rhs
+    }
+}
+
+impl Eq for Price {}
+
+impl PartialOrd for Price {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Price {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let (lhs, rhs) = self.normalised_ticks(other);
+        lhs.cmp(&rhs)
+    }
+}
+
+impl Hash for Price {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // hash the price with trailing zero ticks removed so equal prices at different scales hash equally
+        let mut ticks = self.ticks;
+        let mut decimals = self.decimals;
+        while decimals > 0 && ticks % 10 == 0 {
+            ticks /= 10;
+            decimals -= 1;
+        }
+        ticks.hash(state);
+        decimals.hash(state);
+    }
+}
+
+impl Add for Price {
+    type Output = Price;
+
+    fn add(self, other: Price) -> Price {
+        // saturates rather than overflowing
+        let (lhs, rhs) = self.normalised_ticks(&other);
+        let ticks = lhs.saturating_add(rhs);
+        Price {
+            ticks: i64::try_from(ticks).unwrap_or(if ticks < 0 { i64::MIN } else { i64::MAX }),
+            decimals: self.decimals.max(other.decimals),
+        }
+    }
+}
+
+impl AddAssign for Price {
+    fn add_assign(&mut self, other: Price) {
+        *self = *self + other;
+    }
+}
+
+impl Sub for Price {
+    type Output = Price;
+
+    fn sub(self, other: Price) -> Price {
+        self + (-other)
+    }
+}
+
+impl SubAssign for Price {
+    fn sub_assign(&mut self, other: Price) {
+        *self = *self - other;
+    }
+}
+
+impl Neg for Price {
+    type Output = Price;
+
+    fn neg(self) -> Price {
+        Price {
+            ticks: -self.ticks,
+            decimals: self.decimals,
+        }
+    }
+}
+
+impl Display for Price {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let sign = if self.ticks < 0 { "-" } else { "" };
+        let abs_ticks = self.ticks.unsigned_abs();
+        if self.decimals == 0 {
+            return write!(f, "{}{}", sign, abs_ticks);
+        }
+        // a price with more decimal places than a u64 factor can hold has no whole part
+        let (whole, fraction) = match 10u64.checked_pow(self.decimals) {
+            Some(factor) => (abs_ticks / factor, abs_ticks % factor),
+            None => (0, abs_ticks),
+        };
+        write!(
+            f,
+            "{}{}.{:0width$}",
+            sign,
+            whole,
+            fraction,
+            width = self.decimals as usize
+        )
+    }
+}
+
+impl FromStr for Price {
+    type Err = AppError;
+
+    /// Parse a decimal string such as "1.5552" exactly, keeping as many decimal places as are given.
+    /// Only an optional leading "-" and ASCII digits either side of an optional "." are accepted, with at most
+    /// MAX_DECIMALS decimal places
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let value = s.trim();
+        if value.is_empty() {
+            return Err(AppError::IsEmpty);
+        }
+        let invalid = || AppError::InvalidPrice(format!("\"{value}\" is not a decimal number"));
+        let (negative, digits) = match value.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, value),
+        };
+        let (int_part, frac_part) = digits.split_once('.').unwrap_or((digits, ""));
+        let is_digits = |part: &str| part.bytes().all(|byte| byte.is_ascii_digit());
+        if (int_part.is_empty() && frac_part.is_empty())
+            || !is_digits(int_part)
+            || !is_digits(frac_part)
+        {
+            return Err(invalid());
+        }
+        let decimals = u32::try_from(frac_part.len()).map_err(|_| invalid())?;
+        if decimals > MAX_DECIMALS {
+            return Err(AppError::InvalidPrice(format!(
+                "\"{value}\" has more than {MAX_DECIMALS} decimal places"
+            )));
+        }
+        let overflow = || AppError::InvalidPrice(format!("\"{value}\" is out of range"));
+        let ticks = int_part
+            .bytes()
+            .chain(frac_part.bytes())
+            .try_fold(0i64, |ticks, digit| {
+                ticks
+                    .checked_mul(10)
+                    .and_then(|ticks| ticks.checked_add(i64::from(digit - b'0')))
+            })
+            .ok_or_else(overflow)?;
+
+        Ok(Price {
+            ticks: if negative { -ticks } else { ticks },
+            decimals,
+        })
+    }
+}
@@ -0,0 +1,156 @@
+//! # FX Simulator and Aggregator - fx_sim_agg_gui
+//!
+//! `price_model.rs` defines the price models that move a liquidity provider's 1M buy price between quotes - a simple trend,
+//! geometric Brownian motion, Ornstein-Uhlenbeck mean reversion and jump-diffusion. Drift and volatility are annualised,
+//! as is usual for FX, and each step is scaled by the simulated time since the previous quote.
+use rand::Rng;
+use rand_distr::{Distribution, Normal, Poisson, StandardNormal};
+use serde::Deserialize;
+
+const SECONDS_PER_YEAR: f64 = 365.25 * 24.0 * 60.0 * 60.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum PriceModel {
+    // the price moves by a random whole number of pips between min_pips and max_pips every quote
+    Trend {
+        min_pips: f64,
+        max_pips: f64,
+    },
+    // geometric Brownian motion - rising (positive drift), falling (negative drift) or random walk markets
+    Gbm {
+        #[serde(default)]
+        drift: f64,
+        volatility: f64,
+    },
+    // mean reverting (in log price) towards mean, or the liquidity provider's buy price if not given,
+    // with half of any move away from the mean gone after half_life_secs - ranging markets
+    OrnsteinUhlenbeck {
+        mean: Option<f64>,
+        half_life_secs: f64,
+        volatility: f64,
+    },
+    // geometric Brownian motion with jumps of a normally distributed number of pips arriving
+    // at random on average jumps_per_hour times an hour - gappy markets
+    JumpDiffusion {
+        #[serde(default)]
+        drift: f64,
+        volatility: f64,
+        jumps_per_hour: f64,
+        #[serde(default)]
+        jump_mean_pips: f64,
+        jump_std_pips: f64,
+    },
+}
+
+impl Default for PriceModel {
+    // a bull market with prices trending up
+    fn default() -> Self {
+        PriceModel::Trend {
+            min_pips: 0.0,
+            max_pips: 2.0,
+        }
+    }
+}
+
+impl PriceModel {
+    pub fn validate(&self) -> Result<(), String> {
+        let non_negative = |name: &str, value: f64| {
+            if value >= 0.0 && value.is_finite() {
+                Ok(())
+            } else {
+                Err(format!("{name} {value} must not be negative"))
+            }
+        };
+        match *self {
+            PriceModel::Trend { min_pips, max_pips } if min_pips > max_pips => Err(format!(
+                "trend min_pips {min_pips} is greater than max_pips {max_pips}"
+            )),
+            PriceModel::Trend { .. } => Ok(()),
+            PriceModel::Gbm { volatility, .. } => non_negative("volatility", volatility),
+            PriceModel::OrnsteinUhlenbeck {
+                mean,
+                half_life_secs,
+                volatility,
+            } => {
+                if mean.is_some_and(|mean| mean <= 0.0) {
+                    return Err(String::from("mean must be greater than 0"));
+                }
+                if half_life_secs <= 0.0 {
+                    return Err(format!(
+                        "half_life_secs {half_life_secs} must be greater than 0"
+                    ));
+                }
+                non_negative("volatility", volatility)
+            }
+            PriceModel::JumpDiffusion {
+                volatility,
+                jumps_per_hour,
+                jump_std_pips,
+                ..
+            } => {
+                non_negative("volatility", volatility)?;
+                non_negative("jumps_per_hour", jumps_per_hour)?;
+                non_negative("jump_std_pips", jump_std_pips)
+            }
+        }
+    }
+
+    pub fn next_price(
+        &self,
+        price: f64,
+        initial_price: f64,
+        interval_secs: f64,
+        pip_size: f64,
+        rng: &mut impl Rng,
+    ) -> f64 {
+        // the price after interval_secs of simulated time, never falling below a single pip
+        let dt = interval_secs / SECONDS_PER_YEAR;
+        let next_price = match *self {
+            PriceModel::Trend { min_pips, max_pips } => {
+                price + rng.random_range(min_pips..=max_pips).round() * pip_size
+            }
+            PriceModel::Gbm { drift, volatility } => gbm_step(price, drift, volatility, dt, rng),
+            PriceModel::OrnsteinUhlenbeck {
+                mean,
+                half_life_secs,
+                volatility,
+            } => {
+                // exact discretisation of the log price process so any step size is stable
+                let mean = mean.unwrap_or(initial_price).ln();
+                let reversion = std::f64::consts::LN_2 / (half_life_secs / SECONDS_PER_YEAR);
+                let decay = (-reversion * dt).exp();
+                let std_dev = volatility * ((1.0 - decay * decay) / (2.0 * reversion)).sqrt();
+                let z: f64 = rng.sample(StandardNormal);
+                (mean + (price.ln() - mean) * decay + std_dev * z).exp()
+            }
+            PriceModel::JumpDiffusion {
+                drift,
+                volatility,
+                jumps_per_hour,
+                jump_mean_pips,
+                jump_std_pips,
+            } => {
+                let mut next_price = gbm_step(price, drift, volatility, dt, rng);
+                let expected_jumps = jumps_per_hour * interval_secs / 3600.0;
+                let jumps = match Poisson::new(expected_jumps) {
+                    Ok(poisson) => poisson.sample(rng) as u64,
+                    // no jumps configured
+                    Err(_) => 0,
+                };
+                if let Ok(jump_size) = Normal::new(jump_mean_pips, jump_std_pips) {
+                    for _ in 0..jumps {
+                        next_price += jump_size.sample(rng) * pip_size;
+                    }
+                }
+                next_price
+            }
+        };
+        next_price.max(pip_size)
+    }
+}
+
+fn gbm_step(price: f64, drift: f64, volatility: f64, dt: f64, rng: &mut impl Rng) -> f64 {
+    let z: f64 = rng.sample(StandardNormal);
+    price * ((drift - 0.5 * volatility * volatility) * dt + volatility * dt.sqrt() * z).exp()
+}
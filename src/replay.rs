@@ -0,0 +1,174 @@
+//! # FX Simulator and Aggregator - fx_sim_agg_gui
+//!
+//! `replay.rs` replays a recorded market data log into the aggregator instead of the simulator, so production-like sessions
+//! can be reproduced and book states debugged offline. Logs of FIX messages and of the original pipe delimited format are both
+//! read, and messages are replayed with their original inter-arrival timing, faster, as fast as possible or one step at a time.
+use log::{error, info};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead};
+use std::thread;
+use tokio::spawn;
+use tokio::sync::mpsc::{Receiver, channel};
+use tokio_stream::{StreamMap, wrappers::ReceiverStream};
+
+use crate::AppError;
+use crate::aggregator::BookSide;
+use crate::clock::SimClock;
+use crate::config::{Config, Settings};
+use crate::fix::{self, MarketDataSnapshot, MdEntry};
+use crate::price::{Price, pip_decimals};
+use crate::simulator::md_entry;
+use crate::{get_params, get_str_field};
+
+// volumes (M) of the buy and sell quotes of the original pipe delimited format
+const PIPE_FORMAT_VOLUMES: [i32; 3] = [1, 3, 5];
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ReplaySettings {
+    // recorded log to replay
+    pub file: String,
+    // wait for Enter in the terminal before each message rather than for its timestamp
+    pub step: bool,
+}
+
+pub fn parse_replay_log(contents: &str, configs: &[Config]) -> Vec<(Option<u64>, String)> {
+    // the FIX message of every line of the log with its timestamp. Pipe delimited lines are converted to FIX snapshots and
+    // FIX messages which can't be decoded are still replayed, without a timestamp, for the aggregator to reject
+    let mut msg_seq_nums: HashMap<String, u32> = HashMap::new();
+    let mut messages: Vec<(Option<u64>, String)> = Vec::new();
+    for (line_number, line) in (1..).zip(contents.lines()) {
+        let line = line.trim_end_matches(['\r', '\n']);
+        if line.trim().is_empty() {
+            continue;
+        }
+        if line.starts_with("8=") {
+            let timestamp = fix::decode_market_data(line)
+                .map(|market_data| market_data.timestamp())
+                .ok();
+            messages.push((timestamp, String::from(line)));
+            continue;
+        }
+        match parse_pipe_line(line, configs) {
+            Ok(mut snapshot) => {
+                let msg_seq_num = msg_seq_nums
+                    .entry(snapshot.liquidity_provider.clone())
+                    .or_default();
+                *msg_seq_num += 1;
+                snapshot.msg_seq_num = *msg_seq_num;
+                messages.push((
+                    Some(snapshot.timestamp),
+                    fix::encode_market_data_snapshot(&snapshot),
+                ));
+            }
+            Err(e) => error!("replay log line {line_number} skipped - {e}"),
+        }
+    }
+    messages
+}
+
+fn parse_pipe_line(line: &str, configs: &[Config]) -> Result<MarketDataSnapshot, AppError> {
+    // "LP | pair | buy price | sell price | ... | timestamp" with a buy and sell price for each volume tier
+    let mut params: Vec<&str> = get_params(line, 5)?.collect();
+    let liquidity_provider = String::from(get_str_field(Some(params.remove(0)))?);
+    let currency_pair = String::from(get_str_field(Some(params.remove(0)))?);
+    let timestamp: u64 = get_str_field(params.pop())?.parse()?;
+
+    // volumes of the liquidity provider's configured tiers, otherwise those of the original format
+    let volumes: Vec<i32> = configs
+        .iter()
+        .find(|config| {
+            config.liquidity_provider == liquidity_provider
+                && config.currency_pair == currency_pair
+                && config.tiers.len() * 2 == params.len()
+        })
+        .map(|config| config.tiers.iter().map(|tier| tier.volume).collect())
+        .unwrap_or_else(|| PIPE_FORMAT_VOLUMES.to_vec());
+    if volumes.len() * 2 != params.len() {
+        return Err(AppError::NumParams);
+    }
+
+    // the original format dropped trailing zeros so prices are rescaled to the pip precision of the currency pair
+    let decimals = pip_decimals(&currency_pair);
+    let mut entries: Vec<MdEntry> = Vec::new();
+    for (prices, volume) in params.chunks(2).zip(volumes) {
+        let buy_price = get_str_field(Some(prices[0]))?
+            .parse::<Price>()?
+            .rescale(decimals)?;
+        let sell_price = get_str_field(Some(prices[1]))?
+            .parse::<Price>()?
+            .rescale(decimals)?;
+        entries.push(md_entry(BookSide::Buy, buy_price, volume));
+        entries.push(md_entry(BookSide::Sell, sell_price, volume));
+    }
+    Ok(MarketDataSnapshot {
+        liquidity_provider,
+        currency_pair,
+        msg_seq_num: 0,
+        entries,
+        timestamp,
+    })
+}
+
+pub fn start_replay(
+    configs: &[Config],
+    settings: &Settings,
+    replay: &ReplaySettings,
+) -> Result<StreamMap<i32, ReceiverStream<String>>, AppError> {
+    // the recorded messages are sent as a single stream paced by the simulation clock from the time of the first message
+    let contents = fs::read_to_string(&replay.file)?;
+    let messages = parse_replay_log(&contents, configs);
+    info!("replaying {} messages from {}", messages.len(), replay.file);
+    let start = messages
+        .iter()
+        .find_map(|(timestamp, _)| *timestamp)
+        .unwrap_or_default();
+    let mut sim_clock = SimClock::new(settings.clock, start);
+    let mut steps = replay.step.then(read_steps);
+
+    let (tx, rx) = channel(1);
+    spawn(async move {
+        for (step, (timestamp, message)) in (1..).zip(messages) {
+            match &mut steps {
+                Some(steps) => {
+                    println!("step {step} - press Enter to replay the next message or q to quit");
+                    if steps.recv().await.is_none() {
+                        break;
+                    }
+                }
+                None => {
+                    if let Some(timestamp) = timestamp {
+                        sim_clock.sleep_until(timestamp).await;
+                    }
+                }
+            }
+            if let Err(send_error) = tx.send(message).await {
+                error!("could not send message {}: {send_error}", send_error.0);
+                break;
+            };
+        }
+        info!("replay completed");
+    });
+
+    let mut map = StreamMap::new();
+    map.insert(0, ReceiverStream::new(rx));
+    Ok(map)
+}
+
+fn read_steps() -> Receiver<()> {
+    // stdin is read on its own thread as reading it blocks. The replay stops at the end of input or when q is entered
+    let (tx, rx) = channel(1);
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            match line {
+                Ok(line) if line.trim() != "q" => {
+                    if tx.blocking_send(()).is_err() {
+                        break;
+                    }
+                }
+                _ => break,
+            }
+        }
+    });
+    rx
+}
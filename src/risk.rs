@@ -0,0 +1,262 @@
+//! # FX Simulator and Aggregator - fx_sim_agg_gui
+//!
+//! `risk.rs` checks every order before it is executed against the limits in the `risk` settings - the largest order, the largest
+//! net position of a currency pair, the largest notional with any one liquidity provider and how far through the top of book an
+//! order may fill - and rejects all orders while the kill switch is engaged, so a fat-fingered order never reaches the book. Routed
+//! orders are checked against the child orders of their `RoutePlan`.
+use crate::aggregator::FxBook;
+use crate::execution::{self, Fill, Order, OrderSide};
+use crate::fix::VOLUME_UNIT;
+use crate::position::PositionKeeper;
+use crate::price::{Price, pip_decimals};
+use crate::router::RoutePlan;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct RiskSettings {
+    // largest volume (M) of an order
+    pub max_order_volume: Option<i32>,
+    // largest net position (M) of a currency pair, long or short
+    pub max_net_position: Option<i32>,
+    // largest notional (quote currency) of the position with any one liquidity provider
+    pub max_lp_notional: Option<f64>,
+    // furthest (pips) through the top of book an order may fill
+    pub price_collar_pips: Option<f64>,
+}
+
+impl RiskSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(max_order_volume) = self.max_order_volume
+            && max_order_volume <= 0
+        {
+            return Err(format!(
+                "max_order_volume {max_order_volume} must be greater than 0"
+            ));
+        }
+        if let Some(max_net_position) = self.max_net_position
+            && max_net_position < 0
+        {
+            return Err(format!(
+                "max_net_position {max_net_position} must be at least 0"
+            ));
+        }
+        if let Some(max_lp_notional) = self.max_lp_notional
+            && !(max_lp_notional >= 0.0 && max_lp_notional.is_finite())
+        {
+            return Err(format!(
+                "max_lp_notional {max_lp_notional} must be at least 0"
+            ));
+        }
+        if let Some(price_collar_pips) = self.price_collar_pips
+            && !(price_collar_pips >= 0.0 && price_collar_pips.is_finite())
+        {
+            return Err(format!(
+                "price_collar_pips {price_collar_pips} must be at least 0"
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RiskError {
+    KillSwitch,
+    MaxOrderVolume {
+        volume: i32,
+        limit: i32,
+    },
+    MaxNetPosition {
+        currency_pair: String,
+        position: i32,
+        limit: i32,
+    },
+    MaxLpNotional {
+        liquidity_provider: String,
+        notional: f64,
+        limit: f64,
+    },
+    PriceCollar {
+        price: Price,
+        top_of_book: Price,
+        limit_pips: f64,
+    },
+}
+
+impl Display for RiskError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::KillSwitch => f.write_str("kill switch is engaged"),
+            Self::MaxOrderVolume { volume, limit } => {
+                write!(f, "order volume {volume}M is over the {limit}M limit")
+            }
+            Self::MaxNetPosition {
+                currency_pair,
+                position,
+                limit,
+            } => write!(
+                f,
+                "{currency_pair} net position would be {position}M, over the {limit}M limit"
+            ),
+            Self::MaxLpNotional {
+                liquidity_provider,
+                notional,
+                limit,
+            } => write!(
+                f,
+                "{liquidity_provider} notional would be {notional:.0}, over the {limit:.0} limit"
+            ),
+            Self::PriceCollar {
+                price,
+                top_of_book,
+                limit_pips,
+            } => write!(
+                f,
+                "fill at {price} is more than {limit_pips} pips through the top of book {top_of_book}"
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RiskManager {
+    pub settings: RiskSettings,
+    kill_switch: bool,
+}
+
+impl RiskManager {
+    pub fn new(settings: RiskSettings) -> Self {
+        RiskManager {
+            settings,
+            kill_switch: false,
+        }
+    }
+
+    pub fn set_kill_switch(&mut self, engaged: bool) {
+        self.kill_switch = engaged;
+    }
+
+    pub fn kill_switch(&self) -> bool {
+        self.kill_switch
+    }
+
+    pub fn check_order(
+        &self,
+        fx_book: &FxBook,
+        positions: &PositionKeeper,
+        order: &Order,
+    ) -> Result<(), RiskError> {
+        // the position and notional limits are checked against the fills the order would get from the book now
+        let fills = execution::match_order(fx_book, order);
+        let top_of_book = fills.first().map(|fill| fill.price);
+        self.check_fills(positions, order, top_of_book, &fills)
+    }
+
+    pub fn check_route_plan(
+        &self,
+        positions: &PositionKeeper,
+        route_plan: &RoutePlan,
+    ) -> Result<(), RiskError> {
+        // a routed order is checked against the volume and prices each liquidity provider is sent, which depend
+        // on the routing policy, rather than the fills the order would get from the book
+        let fills: Vec<Fill> = route_plan
+            .child_orders
+            .iter()
+            .flat_map(|child_order| {
+                child_order.levels.iter().map(|(price, volume)| Fill {
+                    liquidity_provider: child_order.liquidity_provider.clone(),
+                    price: *price,
+                    volume: *volume,
+                })
+            })
+            .collect();
+        self.check_fills(positions, &route_plan.order, route_plan.top_of_book, &fills)
+    }
+
+    fn check_fills(
+        &self,
+        positions: &PositionKeeper,
+        order: &Order,
+        top_of_book: Option<Price>,
+        fills: &[Fill],
+    ) -> Result<(), RiskError> {
+        if self.kill_switch {
+            return Err(RiskError::KillSwitch);
+        }
+        if let Some(limit) = self.settings.max_order_volume
+            && order.volume > limit
+        {
+            return Err(RiskError::MaxOrderVolume {
+                volume: order.volume,
+                limit,
+            });
+        }
+        let sign = match order.side {
+            OrderSide::Buy => 1,
+            OrderSide::Sell => -1,
+        };
+
+        // the collar is checked against the fill furthest through the top of book
+        if let Some(limit_pips) = self.settings.price_collar_pips
+            && let Some(top_of_book) = top_of_book
+            && let Some(worst) = fills
+                .iter()
+                .max_by_key(|fill| (fill.price - top_of_book).ticks().abs())
+        {
+            let through = (worst.price - top_of_book).to_pips(pip_decimals(&order.currency_pair));
+            if through.abs() > limit_pips {
+                return Err(RiskError::PriceCollar {
+                    price: worst.price,
+                    top_of_book,
+                    limit_pips,
+                });
+            }
+        }
+
+        let pair_position = positions.get(&order.currency_pair);
+        if let Some(limit) = self.settings.max_net_position {
+            let net_volume =
+                pair_position.map_or(0, |pair_position| pair_position.position.net_volume);
+            let filled_volume: i32 = fills.iter().map(|fill| fill.volume).sum();
+            let position = net_volume + sign * filled_volume;
+            // an order reducing a position already over the limit is still allowed
+            if position.abs() > limit && position.abs() > net_volume.abs() {
+                return Err(RiskError::MaxNetPosition {
+                    currency_pair: order.currency_pair.clone(),
+                    position,
+                    limit,
+                });
+            }
+        }
+
+        if let Some(limit) = self.settings.max_lp_notional {
+            // the net volume and the price of the last fill with each liquidity provider after the order
+            let mut lp_fills: BTreeMap<&str, (i32, Price)> = BTreeMap::new();
+            for fill in fills {
+                let lp_fill = lp_fills
+                    .entry(fill.liquidity_provider.as_str())
+                    .or_insert((0, fill.price));
+                lp_fill.0 += sign * fill.volume;
+                lp_fill.1 = fill.price;
+            }
+            for (liquidity_provider, (volume, price)) in lp_fills {
+                let net_volume = pair_position
+                    .and_then(|pair_position| pair_position.lp_positions.get(liquidity_provider))
+                    .map_or(0, |position| position.net_volume);
+                let position = net_volume + volume;
+                let notional = f64::from(position.abs()) * price.to_f64() * VOLUME_UNIT as f64;
+                if notional > limit && position.abs() > net_volume.abs() {
+                    return Err(RiskError::MaxLpNotional {
+                        liquidity_provider: String::from(liquidity_provider),
+                        notional,
+                        limit,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+}
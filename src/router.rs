@@ -0,0 +1,292 @@
+//! # FX Simulator and Aggregator - fx_sim_agg_gui
+//!
+//! `router.rs` is the smart order router. It splits an order across the liquidity providers quoting in an aggregated `FxBook`
+//! into one child order per liquidity provider, taking the best prices first and splitting each price level between its
+//! liquidity providers by the selected routing policy, and reports the expected slippage of the order against the top of book.
+use crate::AppError;
+use crate::aggregator::{FxAggBookEntry, FxBook};
+use crate::execution::{self, Order, OrderSide, OrderType};
+use crate::price::{Price, pip_decimals};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum RoutingPolicy {
+    // the liquidity provider quoting the largest size at a price level fills first
+    #[default]
+    BestPrice,
+    // a price level is split between its liquidity providers in proportion to the size they quote
+    ProRata,
+    // liquidity providers at a price level fill in the order of the ranking, then any others in the order their quotes arrived
+    Preference {
+        ranking: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct RouterSettings {
+    pub policy: RoutingPolicy,
+    // liquidity providers which would be sent less than this volume (M) are left out of the route
+    pub min_fill_volume: Option<i32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChildOrder {
+    pub liquidity_provider: String,
+    pub volume: i32,
+    // worst price of the levels the child order takes, so it is also its limit price
+    pub limit_price: Price,
+    // price and volume taken at each level, best price first
+    pub levels: Vec<(Price, i32)>,
+}
+
+impl ChildOrder {
+    pub fn expected_price(&self) -> f64 {
+        // volume weighted average price of the levels the child order takes
+        let notional: f64 = self
+            .levels
+            .iter()
+            .map(|(price, volume)| price.to_f64() * f64::from(*volume))
+            .sum();
+        notional / f64::from(self.volume)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoutePlan {
+    pub order: Order,
+    pub child_orders: Vec<ChildOrder>,
+    // best price on the side of the book the order takes when it was routed
+    pub top_of_book: Option<Price>,
+}
+
+impl RoutePlan {
+    pub fn routed_volume(&self) -> i32 {
+        self.child_orders.iter().map(|child| child.volume).sum()
+    }
+
+    pub fn unrouted_volume(&self) -> i32 {
+        self.order.volume - self.routed_volume()
+    }
+
+    pub fn expected_vwap(&self) -> Option<f64> {
+        let routed_volume = self.routed_volume();
+        if routed_volume == 0 {
+            return None;
+        }
+        let notional: f64 = self
+            .child_orders
+            .iter()
+            .map(|child| child.expected_price() * f64::from(child.volume))
+            .sum();
+        Some(notional / f64::from(routed_volume))
+    }
+
+    pub fn slippage_pips(&self) -> Option<f64> {
+        // how many pips worse than the top of book the order is expected to fill on average
+        let top_of_book = self.top_of_book?.to_f64();
+        let vwap = self.expected_vwap()?;
+        let pip_size = Price::new(1, pip_decimals(&self.order.currency_pair)).to_f64();
+        let slippage = match self.order.side {
+            OrderSide::Buy => vwap - top_of_book,
+            OrderSide::Sell => top_of_book - vwap,
+        };
+        Some(slippage / pip_size)
+    }
+}
+
+impl Display for RoutePlan {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}M of {}M {}",
+            self.order.side,
+            self.routed_volume(),
+            self.order.volume,
+            self.order.currency_pair
+        )?;
+        if let (Some(vwap), Some(slippage)) = (self.expected_vwap(), self.slippage_pips()) {
+            write!(
+                f,
+                " at expected VWAP {vwap:.6} ({slippage:.1} pips slippage)"
+            )?;
+        }
+        for child in &self.child_orders {
+            write!(
+                f,
+                " | {}: {}M limit {}",
+                child.liquidity_provider, child.volume, child.limit_price
+            )?;
+        }
+        Ok(())
+    }
+}
+
+pub fn route_order(
+    fx_book: &FxBook,
+    order: &Order,
+    router_settings: &RouterSettings,
+) -> Result<RoutePlan, AppError> {
+    execution::validate_order(fx_book, order)?;
+    let levels = match order.side {
+        OrderSide::Buy => &fx_book.sell_book,
+        OrderSide::Sell => &fx_book.buy_book,
+    };
+
+    // liquidity providers that would get less than the minimum fill are left out and the order routed again without them
+    let mut excluded: HashSet<String> = HashSet::new();
+    loop {
+        let child_orders = allocate(levels, order, &router_settings.policy, &excluded);
+        let too_small: Vec<String> = child_orders
+            .iter()
+            .filter(|child| {
+                router_settings
+                    .min_fill_volume
+                    .is_some_and(|min_fill_volume| child.volume < min_fill_volume)
+            })
+            .map(|child| child.liquidity_provider.clone())
+            .collect();
+        if too_small.is_empty() {
+            return Ok(RoutePlan {
+                order: order.clone(),
+                child_orders,
+                top_of_book: levels.first().map(|level| level.price),
+            });
+        }
+        excluded.extend(too_small);
+    }
+}
+
+fn allocate(
+    levels: &[FxAggBookEntry],
+    order: &Order,
+    policy: &RoutingPolicy,
+    excluded: &HashSet<String>,
+) -> Vec<ChildOrder> {
+    // take the levels from the top of the book, within any limit price, until the order is fully routed
+    let mut child_orders: Vec<ChildOrder> = Vec::new();
+    let mut remaining = order.volume;
+    for level in levels {
+        if remaining == 0 {
+            break;
+        }
+        let within_limit = match order.order_type {
+            OrderType::Market => true,
+            OrderType::Limit(limit) => match order.side {
+                OrderSide::Buy => level.price <= limit,
+                OrderSide::Sell => level.price >= limit,
+            },
+        };
+        if !within_limit {
+            break;
+        }
+
+        let lp_vols: Vec<(String, i32)> = level
+            .lp_vol
+            .iter()
+            .filter(|lp_vol| !excluded.contains(&lp_vol.0))
+            .map(|lp_vol| (lp_vol.0.clone(), lp_vol.1))
+            .collect();
+        for (liquidity_provider, volume) in split_level(&lp_vols, remaining, policy) {
+            remaining -= volume;
+            add_to_child_order(&mut child_orders, liquidity_provider, level.price, volume);
+        }
+    }
+    child_orders
+}
+
+fn split_level(
+    lp_vols: &[(String, i32)],
+    volume: i32,
+    policy: &RoutingPolicy,
+) -> Vec<(String, i32)> {
+    // the volume taken from each liquidity provider at a price level, up to the volume still to route
+    let level_volume: i32 = lp_vols.iter().map(|lp_vol| lp_vol.1).sum();
+    if volume >= level_volume {
+        return lp_vols.to_vec();
+    }
+    let mut ordered: Vec<(String, i32)> = lp_vols.to_vec();
+    match policy {
+        RoutingPolicy::BestPrice => ordered.sort_by_key(|lp_vol| std::cmp::Reverse(lp_vol.1)),
+        RoutingPolicy::Preference { ranking } => ordered.sort_by_key(|lp_vol| {
+            ranking
+                .iter()
+                .position(|name| *name == lp_vol.0)
+                .unwrap_or(ranking.len())
+        }),
+        RoutingPolicy::ProRata => {
+            // whole millions in proportion to the size quoted, with what is left over going to the largest remainders
+            let shares: Vec<(i32, i32)> = ordered
+                .iter()
+                .map(|lp_vol| {
+                    let share = i64::from(volume) * i64::from(lp_vol.1);
+                    (
+                        (share / i64::from(level_volume)) as i32,
+                        (share % i64::from(level_volume)) as i32,
+                    )
+                })
+                .collect();
+            let mut left_over = volume - shares.iter().map(|share| share.0).sum::<i32>();
+            let mut by_remainder: Vec<usize> = (0..shares.len()).collect();
+            by_remainder.sort_by_key(|&index| std::cmp::Reverse(shares[index].1));
+            let mut allocations: Vec<i32> = shares.iter().map(|share| share.0).collect();
+            for index in by_remainder {
+                if left_over == 0 {
+                    break;
+                }
+                if allocations[index] < ordered[index].1 {
+                    allocations[index] += 1;
+                    left_over -= 1;
+                }
+            }
+            return ordered
+                .into_iter()
+                .zip(allocations)
+                .filter(|(_, allocation)| *allocation > 0)
+                .map(|(lp_vol, allocation)| (lp_vol.0, allocation))
+                .collect();
+        }
+    }
+
+    // fill the liquidity providers in order until the volume is routed
+    let mut remaining = volume;
+    let mut allocations: Vec<(String, i32)> = Vec::new();
+    for (liquidity_provider, lp_volume) in ordered {
+        if remaining == 0 {
+            break;
+        }
+        let allocation = remaining.min(lp_volume);
+        allocations.push((liquidity_provider, allocation));
+        remaining -= allocation;
+    }
+    allocations
+}
+
+fn add_to_child_order(
+    child_orders: &mut Vec<ChildOrder>,
+    liquidity_provider: String,
+    price: Price,
+    volume: i32,
+) {
+    // levels are taken from the best price down so each new level is the child order's new limit price
+    match child_orders
+        .iter_mut()
+        .find(|child| child.liquidity_provider == liquidity_provider)
+    {
+        Some(child) => {
+            child.volume += volume;
+            child.limit_price = price;
+            child.levels.push((price, volume));
+        }
+        None => child_orders.push(ChildOrder {
+            liquidity_provider,
+            volume,
+            limit_price: price,
+            levels: vec![(price, volume)],
+        }),
+    }
+}
@@ -1,154 +1,519 @@
-use core::f64;
 //use log::{debug, error, info, trace, warn};
 use log::{error, info};
-use std::fs;
-use std::path::Path;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
-use tokio::{spawn, sync::mpsc::unbounded_channel, time::sleep};
-use tokio_stream::{Stream, StreamMap, wrappers::UnboundedReceiverStream};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rand_distr::StandardNormal;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::spawn;
+use tokio::sync::mpsc::{UnboundedSender, channel};
+use tokio::time::{Instant, sleep_until};
+use tokio_stream::{StreamMap, wrappers::ReceiverStream};
 
-use crate::{AppError, get_str_field};
+use crate::aggregator::BookSide;
+use crate::arrival::ArrivalProcess;
+use crate::clock::{self, ClockMode, NANOS_PER_MILLI, SimClock, VIRTUAL_START_TIMESTAMP};
+use crate::config::{Config, MarketConfig, PriceModel, Settings, Tier, UpdateType};
+use crate::fix::{
+    self, MarketDataIncrementalRefresh, MarketDataSnapshot, MdEntry, MdIncrementalEntry,
+    MdUpdateAction,
+};
+use crate::mid_price::{MidPricePath, SharedMidPrice};
+use crate::price::{Price, pip_decimals};
 
+// market data messages waiting for the aggregator before the simulator waits for it to catch up
+const CHANNEL_CAPACITY: usize = 1024;
+
+// the currency pair and liquidity provider of each market data stream as it completes
+pub type FeedCompletions = UnboundedSender<(String, String)>;
+
+// the simulated quotes of a single liquidity provider. All randomness comes from its own
+// random number generator so a seeded generator always gives the same sequence of quotes
 #[derive(Debug)]
-pub struct Config {
-    pub liquidity_provider: String,
-    pub currency_pair: String,
-    pub buy_price: f64,
-    pub spread: f64,
-    pub three_mill_markup: f64,
-    pub five_mill_markup: f64,
-    pub run_iterations: i32,
+pub struct LpSimulator {
+    liquidity_provider: String,
+    currency_pair: String,
+    // the 1M buy price is kept unrounded between quotes so small moves add up
+    price: f64,
+    initial_price: f64,
+    spread: Price,
+    tiers: Vec<Tier>,
+    decimals: u32,
+    incremental: bool,
+    price_model: PriceModel,
+    // quotes are made around the shared mid price of the currency pair when there is one
+    mid_price: Option<SharedMidPrice>,
+    market: Option<MarketConfig>,
+    noise_pips: f64,
+    latency_ms: u64,
+    skew_pips: f64,
+    arrival: ArrivalProcess,
+    // nanoseconds since the previous quote
+    interval_ns: u64,
+    iterations_left: i32,
+    msg_seq_num: u32,
+    previous_entries: Vec<MdEntry>,
+    pulled: Vec<bool>,
+    rng: StdRng,
 }
 
-pub fn get_configs(configs: &mut Vec<Config>) -> Result<(), AppError> {
-    let parameters = read_config_file("resources/config.txt")?;
-    let mut index = 0;
-    for i in &parameters {
-        // ignore header line in config file
-        if index > 0 {
-            let mut fx_params = i.split(",");
-            // let mut fx_params = fx_sim_agg_gui::get_params(i, 7)?;
-            let liquidity_provider = get_str_field(fx_params.next())?;
-            let currency_pair = get_str_field(fx_params.next())?;
-            let buy_price: f64 = fx_params.next().unwrap_or("").trim().parse()?;
-            let mut spread: f64 = fx_params.next().unwrap_or("").trim().parse()?;
-            spread = spread / 10000.0;
-            let mut three_mill_markup: f64 = fx_params.next().unwrap_or("").trim().parse()?;
-            three_mill_markup = three_mill_markup / 10000.0;
-            let mut five_mill_markup: f64 = fx_params.next().unwrap_or("").trim().parse()?;
-            five_mill_markup = five_mill_markup / 10000.0;
-            let run_iterations: i32 = fx_params.next().unwrap_or("").trim().parse()?;
-
-            let config = Config {
-                liquidity_provider: String::from(liquidity_provider),
-                currency_pair: String::from(currency_pair),
-                buy_price,
-                spread,
-                three_mill_markup,
-                five_mill_markup,
-                run_iterations,
-            };
+impl LpSimulator {
+    pub fn new(config: &Config, rng: StdRng, mid_price: Option<SharedMidPrice>) -> Self {
+        LpSimulator {
+            liquidity_provider: config.liquidity_provider.clone(),
+            currency_pair: config.currency_pair.clone(),
+            price: config.buy_price.to_f64(),
+            initial_price: config.buy_price.to_f64(),
+            spread: config.spread,
+            tiers: config.tiers.clone(),
+            decimals: pip_decimals(&config.currency_pair),
+            incremental: config.update_type == UpdateType::Incremental,
+            price_model: config.price_model,
+            mid_price,
+            market: config.market.clone(),
+            noise_pips: config.noise_pips,
+            latency_ms: config.latency_ms,
+            skew_pips: config.skew_pips,
+            arrival: ArrivalProcess::new(config.arrival, config.quiet_periods),
+            interval_ns: 0,
+            iterations_left: config.run_iterations,
+            msg_seq_num: 0,
+            previous_entries: Vec::new(),
+            pulled: Vec::new(),
+            rng,
+        }
+    }
 
-            configs.push(config);
+    pub fn next_interval(&mut self) -> Option<u64> {
+        // nanoseconds until the next quote, or None when all the configured iterations are done
+        if self.iterations_left <= 0 {
+            return None;
         }
-        index += 1;
+        self.iterations_left -= 1;
+        self.interval_ns = self.arrival.next_interval(&mut self.rng);
+        Some(self.interval_ns)
     }
 
-    Ok(())
+    pub fn next_message(&mut self, timestamp: u64) -> Option<String> {
+        let pip_size = Price::new(1, self.decimals).to_f64();
+        self.price = match (&self.mid_price, &self.market) {
+            (Some(mid_price), Some(market)) => {
+                self.quote_around_mid(mid_price.clone(), market.clone(), timestamp, pip_size)
+            }
+            // move the price by the configured price model
+            _ => self.price_model.next_price(
+                self.price,
+                self.initial_price,
+                self.interval_ns as f64 / 1_000_000_000.0,
+                pip_size,
+                &mut self.rng,
+            ),
+        };
+
+        let buy_price = Price::from_f64(self.price, self.decimals);
+        let sell_price = buy_price + self.spread;
+
+        // a buy and a sell entry for each volume tier in the order they are configured
+        let entries = get_tier_entries(&self.tiers, buy_price, sell_price);
+
+        if self.incremental && !self.previous_entries.is_empty() {
+            let incremental_entries = get_incremental_entries(
+                &self.previous_entries,
+                &entries,
+                &mut self.pulled,
+                &mut self.rng,
+            );
+            self.previous_entries = entries;
+            // nothing has changed since the last update so there is nothing to send
+            if incremental_entries.is_empty() {
+                return None;
+            }
+            self.msg_seq_num += 1;
+            // send the market data as a FIX MarketDataIncrementalRefresh message
+            Some(fix::encode_market_data_incremental_refresh(
+                &MarketDataIncrementalRefresh {
+                    liquidity_provider: self.liquidity_provider.clone(),
+                    currency_pair: self.currency_pair.clone(),
+                    msg_seq_num: self.msg_seq_num,
+                    entries: incremental_entries,
+                    timestamp,
+                },
+            ))
+        } else {
+            self.pulled = vec![false; entries.len()];
+            self.previous_entries = entries.clone();
+            self.msg_seq_num += 1;
+            // send the market data as a FIX MarketDataSnapshotFullRefresh message
+            Some(fix::encode_market_data_snapshot(&MarketDataSnapshot {
+                liquidity_provider: self.liquidity_provider.clone(),
+                currency_pair: self.currency_pair.clone(),
+                msg_seq_num: self.msg_seq_num,
+                entries,
+                timestamp,
+            }))
+        }
+    }
+
+    fn quote_around_mid(
+        &mut self,
+        mid_price: SharedMidPrice,
+        market: MarketConfig,
+        timestamp: u64,
+        pip_size: f64,
+    ) -> f64 {
+        // the buy price is half a spread below the mid price as the liquidity provider last saw it,
+        // offset by its skew and some noise
+        let seen_at = timestamp.saturating_sub(self.latency_ms * NANOS_PER_MILLI);
+        let mid = mid_price.lock().unwrap().mid_at(seen_at); // panic if can't get lock
+        let noise: f64 = self.rng.sample::<f64, _>(StandardNormal) * self.noise_pips;
+        let spread = self.spread.to_f64();
+        let mut price = mid + (self.skew_pips + noise) * pip_size - spread / 2.0;
+
+        // occasionally misprice the quote by more than the spread so it crosses the other liquidity providers
+        if market.crossing_probability > 0.0 && self.rng.random_bool(market.crossing_probability) {
+            let crossing = spread + market.crossing_pips * pip_size;
+            if self.rng.random_bool(0.5) {
+                price += crossing;
+            } else {
+                price -= crossing;
+            }
+            info!(
+                "{} quoting {} away from the mid price to cross the book",
+                self.liquidity_provider, self.currency_pair
+            );
+        }
+        price.max(pip_size)
+    }
 }
 
-fn read_config_file<P: AsRef<Path>>(file_path: P) -> Result<Vec<String>, AppError> {
-    let contents: String = fs::read_to_string(file_path)?;
-    let results = contents.lines().map(String::from).collect();
-    Ok(results)
+pub fn create_mid_prices(
+    configs: &[Config],
+    seed: Option<u64>,
+    start: u64,
+) -> HashMap<String, SharedMidPrice> {
+    // a shared mid price path for each currency pair with a mid price. Seeded paths have their
+    // own generator so they are the same however many liquidity providers quote the pair. Each path holds
+    // its mid prices back as far as the largest latency of the liquidity providers quoting the pair
+    let mut mid_prices: HashMap<String, SharedMidPrice> = HashMap::new();
+    for config in configs {
+        let Some(market) = &config.market else {
+            continue;
+        };
+        let max_latency_ms = configs
+            .iter()
+            .filter(|other| other.currency_pair == config.currency_pair)
+            .map(|other| other.latency_ms)
+            .max()
+            .unwrap_or(0);
+        mid_prices
+            .entry(config.currency_pair.clone())
+            .or_insert_with(|| {
+                let rng = match seed {
+                    Some(seed) => {
+                        StdRng::seed_from_u64(derive_seed(seed, "mid", &config.currency_pair))
+                    }
+                    None => StdRng::from_os_rng(),
+                };
+                Arc::new(Mutex::new(MidPricePath::new(
+                    start,
+                    market.step_ms * NANOS_PER_MILLI,
+                    market.mid_price,
+                    market.price_model,
+                    Price::new(1, pip_decimals(&config.currency_pair)).to_f64(),
+                    max_latency_ms * NANOS_PER_MILLI,
+                    rng,
+                )))
+            });
+    }
+    mid_prices
 }
 
-pub fn get_marketdata(config: &Config) -> impl Stream<Item = String> {
+pub fn get_marketdata(
+    config: &Config,
+    mid_price: Option<SharedMidPrice>,
+    feed_completions: FeedCompletions,
+) -> ReceiverStream<String> {
     // For this liqudity provider in config, create the new market data values
-    // and send them asynchronously (don't block and wait) every random 1000-5000 milliseconds
-    let (tx, rx) = unbounded_channel();
+    // and send them asynchronously (don't block and wait) at the times of its arrival model
+    let (tx, rx) = channel(CHANNEL_CAPACITY);
 
     // async block may outlive the current function, and the config reference only lives for the current function
     // async blocks are not executed immediately and must either take a reference or ownership of outside variables they use
-    // can't take a reference of config values because config is a shared reference, hence left to take ownership of new variables
-    // from config and use them in the async block below. Also can't use lifetimes because Stream returned from the function can outlive the function
-    let mut buy_price = config.buy_price;
-    let spread = config.spread;
-    let three_mill_markup = config.three_mill_markup;
-    let five_mill_markup = config.five_mill_markup;
-    let number_iterations = config.run_iterations + 1;
-    let liquidity_provider = config.liquidity_provider.clone();
-    let currency_pair = config.currency_pair.clone();
+    // so the simulator takes its own copy of the config values it needs. Also can't use lifetimes because Stream returned
+    // from the function can outlive the function
+    let mut lp_simulator = LpSimulator::new(config, StdRng::from_os_rng(), mid_price);
 
     spawn(async move {
         // spawn a task to handle the async sleep calls
         // async returns a future rather than blocking current thread
         // move is required to move tx into the async block so it gets ownership and
         // tx closes after last message is sent
-        for _number in 1..number_iterations {
-            let random_sleep = rand::random_range(1000..5000);
-            //   println!("random sleep is {random_sleep}");
+        // quotes are due at a running deadline rather than after a sleep from the previous quote so the
+        // quote rate holds up at thousands of quotes a second, when intervals are shorter than the timer resolution
+        let mut deadline = Instant::now();
+        while let Some(interval) = lp_simulator.next_interval() {
             // await polls the future until future returns Ready.
             // If future still pending then control is handed to the runtime
-            sleep(Duration::from_millis(random_sleep)).await;
+            deadline += Duration::from_nanos(interval);
+            sleep_until(deadline).await;
             // now future has returned ready state and so code below is now executed
 
-            // randomly determine whether this is a price rise or fall
-            // let pip_change: f64 = rand::random_range(1.0..5.0) / 10000.0;
-            let pip_change: f64 = rand::random_range(0.0..2.0) / 10000.0;
-
-            // let's say it is a bull market and prices are trending up
-            buy_price = ((buy_price + pip_change) * 10000.0).round() / 10000.0;
-
-            let sell_price = ((buy_price + spread) * 10000.0).round() / 10000.0;
-            let three_mill_buy_price =
-                ((buy_price + three_mill_markup) * 10000.0).round() / 10000.0;
-            let three_mill_sell_price =
-                ((sell_price - three_mill_markup) * 10000.0).round() / 10000.0;
-            let five_mill_buy_price = ((buy_price + five_mill_markup) * 10000.0).round() / 10000.0;
-            let five_mill_sell_price =
-                ((sell_price - five_mill_markup) * 10000.0).round() / 10000.0;
-            let timestamp = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_nanos();
-
-            let marketdata = format!(
-                "{} | {} | {} | {} | {} | {} | {} | {} | {}",
-                liquidity_provider,
-                currency_pair,
-                buy_price,
-                sell_price,
-                three_mill_buy_price,
-                three_mill_sell_price,
-                five_mill_buy_price,
-                five_mill_sell_price,
-                timestamp
-            );
-
-            if let Err(send_error) = tx.send(format!("{marketdata}")) {
-                error!("could not send message {marketdata}: {send_error}");
+            let Some(marketdata) = lp_simulator.next_message(clock::system_timestamp()) else {
+                continue;
+            };
+            if let Err(send_error) = tx.send(marketdata).await {
+                error!("could not send message {}: {send_error}", send_error.0);
                 break;
             };
         }
 
         // number of iterations done so exit the program
-        info!("{} stream completed", liquidity_provider);
+        info!("{} stream completed", lp_simulator.liquidity_provider);
+        // the aggregator has nothing to be told once it has stopped at the end of the run duration
+        feed_completions
+            .send((lp_simulator.currency_pair, lp_simulator.liquidity_provider))
+            .ok();
+    });
+
+    ReceiverStream::new(rx)
+}
+
+// merges the quotes of every liquidity provider in order of their virtual timestamps. Ties go to
+// the liquidity provider listed first in the config so the merged sequence is always the same
+#[derive(Debug)]
+pub struct VirtualTimeScheduler {
+    start: u64,
+    // no quotes are sent after this virtual time
+    end: Option<u64>,
+    // virtual time of the next quote of each liquidity provider, None once its stream has completed
+    lp_simulators: Vec<(Option<u64>, LpSimulator)>,
+    // currency pair and liquidity provider of the streams completed since they were last taken
+    completed: Vec<(String, String)>,
+}
+
+impl VirtualTimeScheduler {
+    pub fn new(configs: &[Config], settings: &Settings, seed: u64, start: u64) -> Self {
+        let end = settings
+            .run_duration_secs
+            .map(|secs| start + secs * 1000 * NANOS_PER_MILLI);
+        let mid_prices = create_mid_prices(configs, Some(seed), start);
+        let lp_simulators = configs
+            .iter()
+            .map(|config| {
+                let rng = StdRng::seed_from_u64(derive_seed(
+                    seed,
+                    &config.liquidity_provider,
+                    &config.currency_pair,
+                ));
+                let mid_price = mid_prices.get(&config.currency_pair).cloned();
+                let mut lp_simulator = LpSimulator::new(config, rng, mid_price);
+                let next_time = lp_simulator
+                    .next_interval()
+                    .map(|interval| start + interval);
+                (next_time, lp_simulator)
+            })
+            .collect();
+        VirtualTimeScheduler {
+            start,
+            end,
+            lp_simulators,
+            completed: Vec::new(),
+        }
+    }
+
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    pub fn take_completed(&mut self) -> Vec<(String, String)> {
+        // each liquidity provider's stream completes with its last quote, so these follow the quote just returned
+        std::mem::take(&mut self.completed)
+    }
+}
+
+impl Iterator for VirtualTimeScheduler {
+    // virtual timestamp and FIX message of the next quote
+    type Item = (u64, String);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (next_time, lp_simulator) = self
+                .lp_simulators
+                .iter_mut()
+                .filter(|(next_time, _)| next_time.is_some())
+                .min_by_key(|(next_time, _)| *next_time)?;
+            let timestamp = next_time.unwrap_or_default();
+            if self.end.is_some_and(|end| timestamp > end) {
+                info!("run duration reached - stopping market data streams");
+                return None;
+            }
+
+            let marketdata = lp_simulator.next_message(timestamp);
+            *next_time = match lp_simulator.next_interval() {
+                Some(interval) => Some(timestamp + interval),
+                None => {
+                    info!("{} stream completed", lp_simulator.liquidity_provider);
+                    self.completed.push((
+                        lp_simulator.currency_pair.clone(),
+                        lp_simulator.liquidity_provider.clone(),
+                    ));
+                    None
+                }
+            };
+            if let Some(marketdata) = marketdata {
+                return Some((timestamp, marketdata));
+            }
+        }
+    }
+}
+
+pub fn uses_virtual_time(settings: &Settings) -> bool {
+    // seeded and faster than real time simulations run in virtual time
+    settings.seed.is_some() || settings.clock != ClockMode::RealTime
+}
+
+pub fn get_virtual_time_marketdata(
+    configs: &[Config],
+    settings: &Settings,
+    feed_completions: FeedCompletions,
+) -> ReceiverStream<String> {
+    // every liquidity provider is simulated by a single task in virtual time with the simulation clock
+    // setting how long to wait in real time between quotes. A seeded simulation starts at a fixed time
+    // so the merged sequence of quotes and their timestamps are the same on every run with that seed
+    let (tx, rx) = channel(CHANNEL_CAPACITY);
+    let (seed, start) = match settings.seed {
+        Some(seed) => (seed, VIRTUAL_START_TIMESTAMP),
+        None => {
+            let seed = rand::random();
+            info!("simulating in virtual time with random seed {seed}");
+            (seed, clock::system_timestamp())
+        }
+    };
+    let mut scheduler = VirtualTimeScheduler::new(configs, settings, seed, start);
+    let mut sim_clock = SimClock::new(settings.clock, scheduler.start());
+
+    spawn(async move {
+        while let Some((timestamp, marketdata)) = scheduler.next() {
+            sim_clock.sleep_until(timestamp).await;
+
+            // waits for the aggregator to catch up when simulating as fast as possible
+            if let Err(send_error) = tx.send(marketdata).await {
+                error!("could not send message {}: {send_error}", send_error.0);
+                break;
+            };
+            // each liquidity provider's completion is reported as soon as its last quote has been sent
+            for feed in scheduler.take_completed() {
+                feed_completions.send(feed).ok();
+            }
+        }
+        info!("virtual time simulation completed");
     });
 
-    UnboundedReceiverStream::new(rx)
+    ReceiverStream::new(rx)
+}
+
+pub fn derive_seed(seed: u64, liquidity_provider: &str, currency_pair: &str) -> u64 {
+    // FNV-1a hash of the liquidity provider and currency pair mixed with the run seed, so each
+    // stream has its own generator which doesn't change when other liquidity providers are added
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325 ^ seed;
+    for byte in liquidity_provider
+        .bytes()
+        .chain([b'/'])
+        .chain(currency_pair.bytes())
+    {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+
+pub fn md_entry(side: BookSide, price: Price, volume: i32) -> MdEntry {
+    // MDEntryID is the side and volume, e.g. "B1" for the 1M buy quote
+    let side_id = match side {
+        BookSide::Buy => "B",
+        BookSide::Sell => "S",
+    };
+    MdEntry {
+        entry_id: format!("{side_id}{volume}"),
+        side,
+        price,
+        volume,
+    }
+}
+
+pub fn get_tier_entries(tiers: &[Tier], buy_price: Price, sell_price: Price) -> Vec<MdEntry> {
+    let mut entries: Vec<MdEntry> = Vec::new();
+    for tier in tiers {
+        entries.push(md_entry(
+            BookSide::Buy,
+            buy_price + tier.markup,
+            tier.volume,
+        ));
+        entries.push(md_entry(
+            BookSide::Sell,
+            sell_price - tier.markup,
+            tier.volume,
+        ));
+    }
+    entries
+}
+
+pub fn get_incremental_entries(
+    previous_entries: &[MdEntry],
+    entries: &[MdEntry],
+    pulled: &mut [bool],
+    rng: &mut impl Rng,
+) -> Vec<MdIncrementalEntry> {
+    // send a change for every quote whose price has moved. Occasionally pull one of the quotes after
+    // the first tier (delete) and put it back (new) on the next update to simulate liquidity coming and going
+    let mut incremental_entries: Vec<MdIncrementalEntry> = Vec::new();
+    for (index, entry) in entries.iter().enumerate() {
+        let action = if pulled[index] {
+            pulled[index] = false;
+            Some(MdUpdateAction::New)
+        } else if index >= 2 && rng.random_bool(0.05) {
+            pulled[index] = true;
+            Some(MdUpdateAction::Delete)
+        } else if entry.price != previous_entries[index].price {
+            Some(MdUpdateAction::Change)
+        } else {
+            None
+        };
+
+        if let Some(action) = action {
+            incremental_entries.push(MdIncrementalEntry {
+                action,
+                entry: entry.clone(),
+            });
+        }
+    }
+    incremental_entries
 }
 
-pub fn start_streams(config: &Vec<Config>) -> StreamMap<i32, impl Stream<Item = String>> {
-    let mut index = 0;
+pub fn start_streams(
+    config: &[Config],
+    settings: &Settings,
+    feed_completions: &FeedCompletions,
+) -> StreamMap<i32, ReceiverStream<String>> {
+    // each liquidity provider's stream reports its completion to feed_completions
     let mut map = StreamMap::new();
+    if uses_virtual_time(settings) {
+        // a virtual time simulation merges the liquidity providers itself
+        map.insert(
+            0,
+            get_virtual_time_marketdata(config, settings, feed_completions.clone()),
+        );
+        return map;
+    }
     // start a market data simulated stream for each config (liquidity provider) value
     // Combine all individual market data streams from each liquidity provider into a single merged stream map
-    for i in config {
-        let marketdata = get_marketdata(i);
+    let mid_prices = create_mid_prices(config, None, clock::system_timestamp());
+    for (index, i) in (0..).zip(config) {
+        let mid_price = mid_prices.get(&i.currency_pair).cloned();
+        let marketdata = get_marketdata(i, mid_price, feed_completions.clone());
 
         map.insert(index, marketdata);
-        index += 1;
     }
     map
 }
@@ -1,49 +1,85 @@
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests {
 
+    use crate::AppError;
     use crate::aggregator::FxAggBookEntry;
-    use crate::aggregator::{self, FxBook};
+    use crate::aggregator::{self, BookManager, BookSide, FxBook, LevelChange};
+    use crate::arrival::ArrivalProcess;
+    use crate::cli::{Cli, Command};
+    use crate::clock::{self, ClockMode, SimClock};
+    use crate::config::{
+        self, ArrivalModel, Config, PriceModel, QuietPeriods, Settings, Tier, UpdateType,
+    };
+    use crate::execution::{Order, OrderSide, OrderType};
+    use crate::feed::{FeedMonitor, FeedStatus};
+    use crate::fix::{
+        self, MarketData, MarketDataIncrementalRefresh, MarketDataSnapshot, MdEntry,
+        MdIncrementalEntry, MdUpdateAction, OrdStatus,
+    };
+    use crate::gui::{BookHighlighter, TradeTicket};
+    use crate::history::{PriceHistory, TimeWindow};
+    use crate::listener::{self, BookListener};
+    use crate::mid_price::MidPricePath;
+    use crate::position::PositionKeeper;
+    use crate::price::Price;
+    use crate::replay;
+    use crate::risk::RiskError;
+    use crate::router::{self, RoutePlan, RouterSettings, RoutingPolicy};
+    use crate::simulator::{self, VirtualTimeScheduler};
+    use crate::venue::{self, LpVenue};
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use std::cmp::Ordering;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use tokio_stream::StreamExt;
+
+    fn quote(liquidity_provider: &str, volume: i32) -> (String, i32, String) {
+        // a liquidity provider quote in a price level of a hand built book, keyed by its liquidity provider and volume
+        (
+            String::from(liquidity_provider),
+            volume,
+            format!("{}{volume}", liquidity_provider.trim()),
+        )
+    }
 
     #[test]
     fn test_sort_by_price_reverse() {
         let mut fx_buy_book: Vec<FxAggBookEntry> = vec![
             FxAggBookEntry {
                 lp_vol: vec![
-                    (String::from("MS "), 1),
-                    (String::from("UBS "), 5),
-                    (String::from("CITI "), 3),
-                    (String::from("BARX "), 3),
+                    quote("MS ", 1),
+                    quote("UBS ", 5),
+                    quote("CITI ", 3),
+                    quote("BARX ", 3),
                 ],
                 volume: 12,
-                price: 1.5555,
-                side: String::from("Buy"),
+                price: Price::new(15555, 4),
+                side: BookSide::Buy,
             },
             FxAggBookEntry {
-                lp_vol: vec![
-                    (String::from("MS "), 3),
-                    (String::from("JPMC "), 1),
-                    (String::from("CITI "), 5),
-                ],
+                lp_vol: vec![quote("MS ", 3), quote("JPMC ", 1), quote("CITI ", 5)],
                 volume: 9,
-                price: 1.5556,
-                side: String::from("Buy"),
+                price: Price::new(15556, 4),
+                side: BookSide::Buy,
             },
             FxAggBookEntry {
-                lp_vol: vec![(String::from("UBS "), 1)],
+                lp_vol: vec![quote("UBS ", 1)],
                 volume: 1,
-                price: 1.5553,
-                side: String::from("Buy"),
+                price: Price::new(15553, 4),
+                side: BookSide::Buy,
             },
             FxAggBookEntry {
                 lp_vol: vec![
-                    (String::from("UBS "), 3),
-                    (String::from("CITI "), 1),
-                    (String::from("BARX "), 1),
-                    (String::from("BARX "), 5),
+                    quote("UBS ", 3),
+                    quote("CITI ", 1),
+                    quote("BARX ", 1),
+                    quote("BARX ", 5),
                 ],
                 volume: 10,
-                price: 1.5554,
-                side: String::from("Buy"),
+                price: Price::new(15554, 4),
+                side: BookSide::Buy,
             },
         ];
 
@@ -53,42 +89,38 @@ mod tests {
             fx_buy_book,
             vec![
                 FxAggBookEntry {
-                    lp_vol: vec![
-                        (String::from("MS "), 3),
-                        (String::from("JPMC "), 1),
-                        (String::from("CITI "), 5),
-                    ],
+                    lp_vol: vec![quote("MS ", 3), quote("JPMC ", 1), quote("CITI ", 5),],
                     volume: 9,
-                    price: 1.5556,
-                    side: String::from("Buy"),
+                    price: Price::new(15556, 4),
+                    side: BookSide::Buy,
                 },
                 FxAggBookEntry {
                     lp_vol: vec![
-                        (String::from("MS "), 1),
-                        (String::from("UBS "), 5),
-                        (String::from("CITI "), 3),
-                        (String::from("BARX "), 3),
+                        quote("MS ", 1),
+                        quote("UBS ", 5),
+                        quote("CITI ", 3),
+                        quote("BARX ", 3),
                     ],
                     volume: 12,
-                    price: 1.5555,
-                    side: String::from("Buy"),
+                    price: Price::new(15555, 4),
+                    side: BookSide::Buy,
                 },
                 FxAggBookEntry {
                     lp_vol: vec![
-                        (String::from("UBS "), 3),
-                        (String::from("CITI "), 1),
-                        (String::from("BARX "), 1),
-                        (String::from("BARX "), 5),
+                        quote("UBS ", 3),
+                        quote("CITI ", 1),
+                        quote("BARX ", 1),
+                        quote("BARX ", 5),
                     ],
                     volume: 10,
-                    price: 1.5554,
-                    side: String::from("Buy"),
+                    price: Price::new(15554, 4),
+                    side: BookSide::Buy,
                 },
                 FxAggBookEntry {
-                    lp_vol: vec![(String::from("UBS "), 1)],
+                    lp_vol: vec![quote("UBS ", 1)],
                     volume: 1,
-                    price: 1.5553,
-                    side: String::from("Buy"),
+                    price: Price::new(15553, 4),
+                    side: BookSide::Buy,
                 },
             ]
         );
@@ -98,44 +130,40 @@ mod tests {
     fn test_sort_by_price() {
         let mut fx_sell_book: Vec<FxAggBookEntry> = vec![
             FxAggBookEntry {
-                lp_vol: vec![(String::from("MS "), 3), (String::from("JPMC "), 5)],
+                lp_vol: vec![quote("MS ", 3), quote("JPMC ", 5)],
                 volume: 8,
-                price: 1.5565,
-                side: String::from("Sell"),
+                price: Price::new(15565, 4),
+                side: BookSide::Sell,
             },
             FxAggBookEntry {
-                lp_vol: vec![
-                    (String::from("UBS "), 3),
-                    (String::from("CITI "), 3),
-                    (String::from("BARX "), 3),
-                ],
+                lp_vol: vec![quote("UBS ", 3), quote("CITI ", 3), quote("BARX ", 3)],
                 volume: 9,
-                price: 1.5563,
-                side: String::from("Sell"),
+                price: Price::new(15563, 4),
+                side: BookSide::Sell,
             },
             FxAggBookEntry {
-                lp_vol: vec![(String::from("JPMC "), 1)],
+                lp_vol: vec![quote("JPMC ", 1)],
                 volume: 1,
-                price: 1.5567,
-                side: String::from("Sell"),
+                price: Price::new(15567, 4),
+                side: BookSide::Sell,
             },
             FxAggBookEntry {
                 lp_vol: vec![
-                    (String::from("MS "), 5),
-                    (String::from("UBS "), 1),
-                    (String::from("CITI "), 1),
-                    (String::from("BARX "), 1),
-                    (String::from("BARX "), 5),
+                    quote("MS ", 5),
+                    quote("UBS ", 1),
+                    quote("CITI ", 1),
+                    quote("BARX ", 1),
+                    quote("BARX ", 5),
                 ],
                 volume: 13,
-                price: 1.5564,
-                side: String::from("Sell"),
+                price: Price::new(15564, 4),
+                side: BookSide::Sell,
             },
             FxAggBookEntry {
-                lp_vol: vec![(String::from("MS "), 1), (String::from("JPMC "), 3)],
+                lp_vol: vec![quote("MS ", 1), quote("JPMC ", 3)],
                 volume: 4,
-                price: 1.5566,
-                side: String::from("Sell"),
+                price: Price::new(15566, 4),
+                side: BookSide::Sell,
             },
         ];
 
@@ -144,44 +172,40 @@ mod tests {
             fx_sell_book,
             vec![
                 FxAggBookEntry {
-                    lp_vol: vec![
-                        (String::from("UBS "), 3),
-                        (String::from("CITI "), 3),
-                        (String::from("BARX "), 3),
-                    ],
+                    lp_vol: vec![quote("UBS ", 3), quote("CITI ", 3), quote("BARX ", 3),],
                     volume: 9,
-                    price: 1.5563,
-                    side: String::from("Sell"),
+                    price: Price::new(15563, 4),
+                    side: BookSide::Sell,
                 },
                 FxAggBookEntry {
                     lp_vol: vec![
-                        (String::from("MS "), 5),
-                        (String::from("UBS "), 1),
-                        (String::from("CITI "), 1),
-                        (String::from("BARX "), 1),
-                        (String::from("BARX "), 5),
+                        quote("MS ", 5),
+                        quote("UBS ", 1),
+                        quote("CITI ", 1),
+                        quote("BARX ", 1),
+                        quote("BARX ", 5),
                     ],
                     volume: 13,
-                    price: 1.5564,
-                    side: String::from("Sell"),
+                    price: Price::new(15564, 4),
+                    side: BookSide::Sell,
                 },
                 FxAggBookEntry {
-                    lp_vol: vec![(String::from("MS "), 3), (String::from("JPMC "), 5)],
+                    lp_vol: vec![quote("MS ", 3), quote("JPMC ", 5)],
                     volume: 8,
-                    price: 1.5565,
-                    side: String::from("Sell"),
+                    price: Price::new(15565, 4),
+                    side: BookSide::Sell,
                 },
                 FxAggBookEntry {
-                    lp_vol: vec![(String::from("MS "), 1), (String::from("JPMC "), 3)],
+                    lp_vol: vec![quote("MS ", 1), quote("JPMC ", 3)],
                     volume: 4,
-                    price: 1.5566,
-                    side: String::from("Sell"),
+                    price: Price::new(15566, 4),
+                    side: BookSide::Sell,
                 },
                 FxAggBookEntry {
-                    lp_vol: vec![(String::from("JPMC "), 1)],
+                    lp_vol: vec![quote("JPMC ", 1)],
                     volume: 1,
-                    price: 1.5567,
-                    side: String::from("Sell"),
+                    price: Price::new(15567, 4),
+                    side: BookSide::Sell,
                 },
             ]
         );
@@ -199,17 +223,25 @@ mod tests {
             buy_book,
             sell_book,
             timestamp,
+            ..Default::default()
         };
 
-        aggregator::add_agg_book_entry(&mut fx_book, "MS", 1, 1.5556, "Buy");
+        aggregator::add_agg_book_entry(
+            &mut fx_book,
+            "MS",
+            "B1",
+            1,
+            Price::new(15556, 4),
+            BookSide::Buy,
+        );
 
         assert_eq!(
             fx_book.buy_book,
             vec![FxAggBookEntry {
-                lp_vol: vec![(String::from("MS "), 1),],
+                lp_vol: vec![quote("MS ", 1),],
                 volume: 1,
-                price: 1.5556,
-                side: String::from("Buy"),
+                price: Price::new(15556, 4),
+                side: BookSide::Buy,
             }]
         )
     }
@@ -221,33 +253,31 @@ mod tests {
             buy_book: vec![
                 FxAggBookEntry {
                     lp_vol: vec![
-                        (String::from("MS "), 1),
-                        (String::from("UBS "), 5),
-                        (String::from("CITI "), 3),
-                        (String::from("BARX "), 3),
+                        quote("MS ", 1),
+                        quote("UBS ", 5),
+                        quote("CITI ", 3),
+                        quote("BARX ", 3),
                     ],
                     volume: 12,
-                    price: 1.5559,
-                    side: String::from("Buy"),
+                    price: Price::new(15559, 4),
+                    side: BookSide::Buy,
                 },
                 FxAggBookEntry {
-                    lp_vol: vec![
-                        (String::from("MS "), 3),
-                        (String::from("JPMC "), 1),
-                        (String::from("CITI "), 5),
-                    ],
+                    lp_vol: vec![quote("MS ", 3), quote("JPMC ", 1), quote("CITI ", 5)],
                     volume: 9,
-                    price: 1.5556,
-                    side: String::from("Buy"),
+                    price: Price::new(15556, 4),
+                    side: BookSide::Buy,
                 },
             ],
             sell_book: vec![FxAggBookEntry {
-                lp_vol: vec![(String::from("MS "), 3), (String::from("JPMC "), 5)],
+                lp_vol: vec![quote("MS ", 3), quote("JPMC ", 5)],
                 volume: 8,
-                price: 1.5564,
-                side: String::from("Sell"),
+                price: Price::new(15564, 4),
+                side: BookSide::Sell,
             }],
             timestamp: 1753430617683973406,
+            min_spread: Price::from_pips(6.0, 4),
+            ..Default::default()
         };
 
         aggregator::maintain_min_spread(&mut fx_book);
@@ -255,14 +285,10 @@ mod tests {
         assert_eq!(
             fx_book.buy_book,
             vec![FxAggBookEntry {
-                lp_vol: vec![
-                    (String::from("MS "), 3),
-                    (String::from("JPMC "), 1),
-                    (String::from("CITI "), 5),
-                ],
+                lp_vol: vec![quote("MS ", 3), quote("JPMC ", 1), quote("CITI ", 5),],
                 volume: 9,
-                price: 1.5556,
-                side: String::from("Buy"),
+                price: Price::new(15556, 4),
+                side: BookSide::Buy,
             }]
         )
     }
@@ -274,38 +300,35 @@ mod tests {
             buy_book: vec![
                 FxAggBookEntry {
                     lp_vol: vec![
-                        (String::from("MS "), 1),
-                        (String::from("UBS "), 5),
-                        (String::from("CITI "), 3),
-                        (String::from("BARX "), 3),
+                        quote("MS ", 1),
+                        quote("UBS ", 5),
+                        quote("CITI ", 3),
+                        quote("BARX ", 3),
                     ],
                     volume: 12,
-                    price: 1.5559,
-                    side: String::from("Buy"),
+                    price: Price::new(15559, 4),
+                    side: BookSide::Buy,
                 },
                 FxAggBookEntry {
-                    lp_vol: vec![
-                        (String::from("MS "), 3),
-                        (String::from("JPMC "), 1),
-                        (String::from("CITI "), 5),
-                    ],
+                    lp_vol: vec![quote("MS ", 3), quote("JPMC ", 1), quote("CITI ", 5)],
                     volume: 9,
-                    price: 1.5556,
-                    side: String::from("Buy"),
+                    price: Price::new(15556, 4),
+                    side: BookSide::Buy,
                 },
             ],
             sell_book: vec![FxAggBookEntry {
-                lp_vol: vec![(String::from("MS "), 3), (String::from("JPMC "), 5)],
+                lp_vol: vec![quote("MS ", 3), quote("JPMC ", 5)],
                 volume: 8,
-                price: 1.5558,
-                side: String::from("Sell"),
+                price: Price::new(15558, 4),
+                side: BookSide::Sell,
             }],
             timestamp: 1753430617683973406,
+            ..Default::default()
         };
 
         assert_eq!(
             aggregator::check_books_crossed(&mut fx_book),
-            Some((0, 1.5558))
+            Some((0, Price::new(15558, 4)))
         );
     }
 
@@ -314,160 +337,64 @@ mod tests {
         let mut fx_buy_book: Vec<FxAggBookEntry> = vec![
             FxAggBookEntry {
                 lp_vol: vec![
-                    (String::from("MS "), 1),
-                    (String::from("UBS "), 5),
-                    (String::from("CITI "), 3),
-                    (String::from("BARX "), 3),
+                    quote("MS ", 1),
+                    quote("UBS ", 5),
+                    quote("CITI ", 3),
+                    quote("BARX ", 3),
                 ],
                 volume: 12,
-                price: 1.5555,
-                side: String::from("Buy"),
+                price: Price::new(15555, 4),
+                side: BookSide::Buy,
             },
             FxAggBookEntry {
-                lp_vol: vec![
-                    (String::from("MS "), 3),
-                    (String::from("JPMC "), 1),
-                    (String::from("CITI "), 5),
-                ],
+                lp_vol: vec![quote("MS ", 3), quote("JPMC ", 1), quote("CITI ", 5)],
                 volume: 9,
-                price: 1.5556,
-                side: String::from("Buy"),
+                price: Price::new(15556, 4),
+                side: BookSide::Buy,
             },
             FxAggBookEntry {
-                lp_vol: vec![(String::from("UBS "), 1)],
+                lp_vol: vec![quote("UBS ", 1)],
                 volume: 1,
-                price: 1.5553,
-                side: String::from("Buy"),
+                price: Price::new(15553, 4),
+                side: BookSide::Buy,
             },
             FxAggBookEntry {
                 lp_vol: vec![
-                    (String::from("UBS "), 3),
-                    (String::from("CITI "), 1),
-                    (String::from("BARX "), 1),
-                    (String::from("BARX "), 5),
+                    quote("UBS ", 3),
+                    quote("CITI ", 1),
+                    quote("BARX ", 1),
+                    quote("BARX ", 5),
                 ],
                 volume: 10,
-                price: 1.5554,
-                side: String::from("Buy"),
+                price: Price::new(15554, 4),
+                side: BookSide::Buy,
             },
         ];
-        aggregator::remove_range_entries_from_top(&mut fx_buy_book, 1, "Buy");
+        aggregator::remove_range_entries_from_top(&mut fx_buy_book, 1, BookSide::Buy);
         assert_eq!(
             fx_buy_book,
             vec![
                 FxAggBookEntry {
-                    lp_vol: vec![(String::from("UBS "), 1)],
+                    lp_vol: vec![quote("UBS ", 1)],
                     volume: 1,
-                    price: 1.5553,
-                    side: String::from("Buy"),
+                    price: Price::new(15553, 4),
+                    side: BookSide::Buy,
                 },
                 FxAggBookEntry {
                     lp_vol: vec![
-                        (String::from("UBS "), 3),
-                        (String::from("CITI "), 1),
-                        (String::from("BARX "), 1),
-                        (String::from("BARX "), 5),
+                        quote("UBS ", 3),
+                        quote("CITI ", 1),
+                        quote("BARX ", 1),
+                        quote("BARX ", 5),
                     ],
                     volume: 10,
-                    price: 1.5554,
-                    side: String::from("Buy"),
+                    price: Price::new(15554, 4),
+                    side: BookSide::Buy,
                 },
             ]
         )
     }
 
-    #[test]
-    fn test_check_expired_quotes() {
-        let liquidity_provider = "JPMC ";
-        let volume = 1;
-        let mut fx_buy_book: Vec<FxAggBookEntry> = vec![
-            FxAggBookEntry {
-                lp_vol: vec![
-                    (String::from("MS "), 1),
-                    (String::from("UBS "), 5),
-                    (String::from("CITI "), 3),
-                    (String::from("BARX "), 3),
-                ],
-                volume: 12,
-                price: 1.5555,
-                side: String::from("Buy"),
-            },
-            FxAggBookEntry {
-                lp_vol: vec![
-                    (String::from("MS "), 3),
-                    (String::from("JPMC "), 1),
-                    (String::from("CITI "), 5),
-                ],
-                volume: 9,
-                price: 1.5556,
-                side: String::from("Buy"),
-            },
-            FxAggBookEntry {
-                lp_vol: vec![(String::from("UBS "), 1)],
-                volume: 1,
-                price: 1.5553,
-                side: String::from("Buy"),
-            },
-            FxAggBookEntry {
-                lp_vol: vec![
-                    (String::from("UBS "), 3),
-                    (String::from("CITI "), 1),
-                    (String::from("BARX "), 1),
-                    (String::from("BARX "), 5),
-                ],
-                volume: 10,
-                price: 1.5554,
-                side: String::from("Buy"),
-            },
-        ];
-
-        match aggregator::check_expired_quotes(&mut fx_buy_book, liquidity_provider, volume) {
-            Some(entry_to_remove) => {
-                println!("test check expired quotes, index to remove is {entry_to_remove}")
-            }
-            None => (),
-        };
-
-        assert_eq!(
-            fx_buy_book,
-            vec![
-                FxAggBookEntry {
-                    lp_vol: vec![
-                        (String::from("MS "), 1),
-                        (String::from("UBS "), 5),
-                        (String::from("CITI "), 3),
-                        (String::from("BARX "), 3),
-                    ],
-                    volume: 12,
-                    price: 1.5555,
-                    side: String::from("Buy"),
-                },
-                FxAggBookEntry {
-                    lp_vol: vec![(String::from("MS "), 3), (String::from("CITI "), 5),],
-                    volume: 9,
-                    price: 1.5556,
-                    side: String::from("Buy"),
-                },
-                FxAggBookEntry {
-                    lp_vol: vec![(String::from("UBS "), 1)],
-                    volume: 1,
-                    price: 1.5553,
-                    side: String::from("Buy"),
-                },
-                FxAggBookEntry {
-                    lp_vol: vec![
-                        (String::from("UBS "), 3),
-                        (String::from("CITI "), 1),
-                        (String::from("BARX "), 1),
-                        (String::from("BARX "), 5),
-                    ],
-                    volume: 10,
-                    price: 1.5554,
-                    side: String::from("Buy"),
-                },
-            ]
-        )
-    }
     #[test]
     fn test_find_buy_index_when_crossed() {
         let mut fx_book = FxBook {
@@ -475,73 +402,2178 @@ mod tests {
             buy_book: vec![
                 FxAggBookEntry {
                     lp_vol: vec![
-                        (String::from("MS "), 1),
-                        (String::from("UBS "), 5),
-                        (String::from("CITI "), 3),
-                        (String::from("BARX "), 3),
+                        quote("MS ", 1),
+                        quote("UBS ", 5),
+                        quote("CITI ", 3),
+                        quote("BARX ", 3),
                     ],
                     volume: 12,
-                    price: 1.5566,
-                    side: String::from("Buy"),
+                    price: Price::new(15566, 4),
+                    side: BookSide::Buy,
                 },
                 FxAggBookEntry {
-                    lp_vol: vec![
-                        (String::from("MS "), 3),
-                        (String::from("JPMC "), 1),
-                        (String::from("CITI "), 5),
-                    ],
+                    lp_vol: vec![quote("MS ", 3), quote("JPMC ", 1), quote("CITI ", 5)],
                     volume: 9,
-                    price: 1.5565,
-                    side: String::from("Buy"),
+                    price: Price::new(15565, 4),
+                    side: BookSide::Buy,
                 },
                 FxAggBookEntry {
-                    lp_vol: vec![(String::from("UBS "), 1)],
+                    lp_vol: vec![quote("UBS ", 1)],
                     volume: 1,
-                    price: 1.5553,
-                    side: String::from("Buy"),
+                    price: Price::new(15553, 4),
+                    side: BookSide::Buy,
                 },
                 FxAggBookEntry {
                     lp_vol: vec![
-                        (String::from("UBS "), 3),
-                        (String::from("CITI "), 1),
-                        (String::from("BARX "), 1),
-                        (String::from("BARX "), 5),
+                        quote("UBS ", 3),
+                        quote("CITI ", 1),
+                        quote("BARX ", 1),
+                        quote("BARX ", 5),
                     ],
                     volume: 10,
-                    price: 1.5554,
-                    side: String::from("Buy"),
+                    price: Price::new(15554, 4),
+                    side: BookSide::Buy,
                 },
             ],
             sell_book: vec![
                 FxAggBookEntry {
-                    lp_vol: vec![(String::from("MS "), 3), (String::from("JPMC "), 5)],
+                    lp_vol: vec![quote("MS ", 3), quote("JPMC ", 5)],
                     volume: 8,
-                    price: 1.5565,
-                    side: String::from("Sell"),
+                    price: Price::new(15565, 4),
+                    side: BookSide::Sell,
                 },
                 FxAggBookEntry {
-                    lp_vol: vec![
-                        (String::from("UBS "), 3),
-                        (String::from("CITI "), 3),
-                        (String::from("BARX "), 3),
-                    ],
+                    lp_vol: vec![quote("UBS ", 3), quote("CITI ", 3), quote("BARX ", 3)],
                     volume: 9,
-                    price: 1.5563,
-                    side: String::from("Sell"),
+                    price: Price::new(15563, 4),
+                    side: BookSide::Sell,
                 },
                 FxAggBookEntry {
-                    lp_vol: vec![(String::from("JPMC "), 1)],
+                    lp_vol: vec![quote("JPMC ", 1)],
                     volume: 1,
-                    price: 1.5567,
-                    side: String::from("Sell"),
+                    price: Price::new(15567, 4),
+                    side: BookSide::Sell,
                 },
             ],
             timestamp: 1753430617683973406,
+            ..Default::default()
         };
-        let fx_book_side = aggregator::get_book_side(&mut fx_book, "Buy");
+        let fx_book_side = aggregator::get_book_side(&mut fx_book, BookSide::Buy);
         assert_eq!(
-            aggregator::find_buy_index_when_crossed(fx_book_side, 1.5565),
+            aggregator::find_buy_index_when_crossed(fx_book_side, Price::new(15565, 4)),
             Some(1)
         );
     }
+
+    #[test]
+    fn test_price_parse_and_display() {
+        let price: Price = "1.5552".parse().unwrap();
+        assert_eq!(price, Price::new(15552, 4));
+        assert_eq!(price.to_string(), "1.5552");
+        assert_eq!(Price::new(15560, 4).to_string(), "1.5560");
+        assert_eq!(Price::new(-3, 4).to_string(), "-0.0003");
+        assert_eq!(Price::new(15012, 2).to_string(), "150.12");
+        assert!("".parse::<Price>().is_err());
+        assert!("1.55x2".parse::<Price>().is_err());
+        // only ASCII digits either side of the decimal point, with at most a leading minus sign
+        for value in [
+            "1.-5", "1.+5", "--1", "+1", "-", ".", "1..5", "1.5.5", "1 .5", "١.5",
+        ] {
+            assert!(
+                matches!(value.parse::<Price>(), Err(AppError::InvalidPrice(_))),
+                "{value} should not parse"
+            );
+        }
+        assert_eq!("-.5".parse::<Price>().unwrap(), Price::new(-5, 1));
+        assert_eq!("7.".parse::<Price>().unwrap(), Price::new(7, 0));
+        // too many digits for i64 ticks is an error rather than a panic
+        assert!(matches!(
+            "1.5552000000000000000".parse::<Price>(),
+            Err(AppError::InvalidPrice(_))
+        ));
+        assert!("99999999999999999999".parse::<Price>().is_err());
+        assert_eq!(
+            "0.000000000000000001".parse::<Price>().unwrap(),
+            Price::new(1, 18)
+        );
+        // more decimal places than a tick can represent is an error, so such a price is never displayed
+        assert!(matches!(
+            "0.000000000000000000001".parse::<Price>(),
+            Err(AppError::InvalidPrice(_))
+        ));
+        assert_eq!(Price::new(1, 21).to_string(), "0.000000000000000000001");
+    }
+
+    #[test]
+    fn test_price_exact_equality_and_ordering() {
+        // 1.5552 + 0.0001 + 0.0002 would not equal 1.5555 as an f64
+        let price = Price::new(15552, 4) + Price::from_pips(1.0, 4) + Price::from_pips(2.0, 4);
+        assert_eq!(price, Price::new(15555, 4));
+        assert_eq!(Price::new(15560, 4), "1.556".parse::<Price>().unwrap());
+        assert!(Price::new(15561, 4) > Price::new(1556, 3));
+        assert_eq!(
+            Price::new(15555, 4) - Price::new(15549, 4),
+            Price::from_pips(6.0, 4)
+        );
+    }
+
+    #[test]
+    fn test_price_rescale_and_pips() {
+        assert_eq!(
+            Price::new(155525, 5).rescale(4).unwrap(),
+            Price::new(15553, 4)
+        );
+        assert_eq!(
+            Price::new(-155525, 5).rescale(4).unwrap(),
+            Price::new(-15553, 4)
+        );
+        // rescaling that would overflow is an error rather than a panic
+        assert!(matches!(
+            Price::new(i64::MAX, 4).rescale(5),
+            Err(AppError::InvalidPrice(_))
+        ));
+        assert!(Price::new(15552, 4).rescale(30).is_err());
+        assert_eq!(Price::new(15552, 4).rescale(0).unwrap(), Price::new(2, 0));
+        // ordering and adding prices at very different scales saturates rather than overflowing
+        assert!(Price::new(i64::MAX, 0) > Price::new(1, 30));
+        assert_eq!(
+            Price::new(i64::MAX, 0) + Price::new(1, 0),
+            Price::new(i64::MAX, 0)
+        );
+        assert_eq!(Price::from_f64(1.55519999, 4), Price::new(15552, 4));
+        assert_eq!(crate::price::pip_decimals("USD/JPY"), 2);
+        assert_eq!(crate::price::pip_decimals("USD/EUR"), 4);
+        assert_eq!(Price::new(12, 4).to_pips(4), 12.0);
+    }
+
+    #[test]
+    fn test_add_agg_book_entry_merges_equal_prices() {
+        let mut fx_book = FxBook {
+            currency_pair: String::from("USD/EUR"),
+            buy_book: Vec::new(),
+            sell_book: Vec::new(),
+            timestamp: 1753440851702449924,
+            ..Default::default()
+        };
+
+        aggregator::add_agg_book_entry(
+            &mut fx_book,
+            "MS",
+            "B1",
+            1,
+            "1.5556".parse().unwrap(),
+            BookSide::Buy,
+        );
+        aggregator::add_agg_book_entry(
+            &mut fx_book,
+            "UBS",
+            "B3",
+            3,
+            "1.55560".parse().unwrap(),
+            BookSide::Buy,
+        );
+
+        assert_eq!(fx_book.buy_book.len(), 1);
+        assert_eq!(fx_book.buy_book[0].volume, 4);
+        assert_eq!(
+            fx_book.buy_book[0].lp_vol,
+            vec![
+                (String::from("MS"), 1, String::from("B1")),
+                (String::from("UBS"), 3, String::from("B3"))
+            ]
+        );
+    }
+
+    fn create_snapshot() -> MarketDataSnapshot {
+        MarketDataSnapshot {
+            liquidity_provider: String::from("MS"),
+            currency_pair: String::from("USD/EUR"),
+            msg_seq_num: 7,
+            entries: vec![
+                MdEntry {
+                    entry_id: String::from("B1"),
+                    side: BookSide::Buy,
+                    price: Price::new(15552, 4),
+                    volume: 1,
+                },
+                MdEntry {
+                    entry_id: String::from("S1"),
+                    side: BookSide::Sell,
+                    price: Price::new(15562, 4),
+                    volume: 1,
+                },
+            ],
+            timestamp: 1753440851702000000,
+        }
+    }
+
+    #[test]
+    fn test_encode_market_data_snapshot() {
+        let snapshot = create_snapshot();
+
+        let message = fix::encode_market_data_snapshot(&snapshot);
+        let body = "35=W\x0149=MS\x0156=FXAGG\x0134=7\x0152=20250725-10:54:11.702\x0155=USD/EUR\x01\
+                    268=2\x01269=0\x01270=1.5552\x01271=1000000\x01278=B1\x01\
+                    269=1\x01270=1.5562\x01271=1000000\x01278=S1\x01";
+        let header = format!("8=FIX.4.4\x019={}\x01", body.len());
+        let checksum = format!("{:03}", fix::checksum(&format!("{header}{body}")));
+
+        assert_eq!(message, format!("{header}{body}10={checksum}\x01"));
+        assert_eq!(fix::checksum("8=FIX.4.4\x019=5\x0135=0\x01"), 163);
+        assert_eq!(
+            snapshot.to_string(),
+            "MS | USD/EUR | 1.5552 | 1.5562 | 1753440851702000000"
+        );
+    }
+
+    #[test]
+    fn test_decode_market_data_round_trip() {
+        let snapshot = create_snapshot();
+        let message = fix::encode_market_data_snapshot(&snapshot);
+
+        assert_eq!(
+            fix::decode_market_data(&message).unwrap(),
+            MarketData::Snapshot(snapshot.clone())
+        );
+        // a trailing newline from the FIX log is ignored
+        assert_eq!(
+            fix::decode_market_data(&format!("{message}\n")).unwrap(),
+            MarketData::Snapshot(snapshot)
+        );
+    }
+
+    #[test]
+    fn test_decode_rejects_bad_checksum() {
+        let message = fix::encode_market_data_snapshot(&create_snapshot());
+        let (body, _checksum) = message.rsplit_once("10=").unwrap();
+        let tampered = format!("{body}10=000\x01");
+
+        assert!(matches!(
+            fix::decode_market_data(&tampered),
+            Err(AppError::BadChecksum(0, _))
+        ));
+
+        // a message cut off before the SOH ending CheckSum(10) is reported as such, not as a bad body length
+        let truncated = message.trim_end_matches('\x01');
+        assert!(matches!(
+            fix::decode_market_data(truncated),
+            Err(AppError::MissingTrailingSoh)
+        ));
+    }
+
+    #[test]
+    fn test_decode_rejects_bad_body_length() {
+        let message = fix::encode_market_data_snapshot(&create_snapshot());
+        let tampered = message.replace("270=1.5552", "270=1.55521");
+
+        assert!(matches!(
+            fix::decode_market_data(&tampered),
+            Err(AppError::BadBodyLength(_, _))
+        ));
+    }
+
+    #[test]
+    fn test_decode_rejects_missing_tag_and_unknown_msg_type() {
+        let heartbeat = "8=FIX.4.4\x019=5\x0135=0\x0110=163\x01";
+        assert!(matches!(
+            fix::decode_market_data(heartbeat),
+            Err(AppError::UnknownMsgType(msg_type)) if msg_type == "0"
+        ));
+
+        let no_symbol = "8=FIX.4.4\x019=5\x0135=W\x0110=202\x01";
+        assert!(matches!(
+            fix::decode_market_data(no_symbol),
+            Err(AppError::MissingTag(49))
+        ));
+
+        assert!(matches!(
+            fix::decode_market_data("MS | USD/EUR | 1.5552"),
+            Err(AppError::MalformedField(_))
+        ));
+    }
+
+    #[test]
+    fn test_decode_rejects_bad_group_count() {
+        let mut snapshot = create_snapshot();
+        snapshot.entries.pop();
+        let message = fix::encode_market_data_snapshot(&snapshot).replace("268=1", "268=2");
+
+        // re-frame the message so only the group count is wrong
+        assert!(matches!(
+            fix::decode_market_data(&reframe(&message)),
+            Err(AppError::BadGroupCount(2, 1))
+        ));
+    }
+
+    // a FIX message with its BodyLength(9) and CheckSum(10) recalculated after its body was changed
+    fn reframe(message: &str) -> String {
+        let body_start = message.find("35=").unwrap();
+        let body_end = message.rfind("10=").unwrap();
+        let body = &message[body_start..body_end];
+        let header = format!("8=FIX.4.4\x019={}\x01", body.len());
+        let checksum = fix::checksum(&format!("{header}{body}"));
+        format!("{header}{body}10={checksum:03}\x01")
+    }
+
+    #[test]
+    fn test_decode_rejects_bad_entry_values() {
+        let message = fix::encode_market_data_snapshot(&create_snapshot());
+        let decode = |from: &str, to: &str| {
+            assert!(message.contains(from));
+            fix::decode_market_data(&reframe(&message.replacen(from, to, 1)))
+        };
+        // sizes must be a positive whole number of millions within the range of the book
+        for size in [
+            "500000",
+            "0",
+            "-3000000",
+            "1500000",
+            "3000000000000000",
+            "1e6",
+        ] {
+            assert!(
+                matches!(
+                    decode("271=1000000", &format!("271={size}")),
+                    Err(AppError::InvalidTagValue(271))
+                ),
+                "271={size} should be rejected"
+            );
+        }
+        assert!(decode("271=1000000", "271=3000000").is_ok());
+        // prices must be plain decimals
+        for price in ["1.-5", "1.+5", "--1", "1.5552000000000000000"] {
+            assert!(
+                matches!(
+                    decode("270=1.5552", &format!("270={price}")),
+                    Err(AppError::InvalidTagValue(270))
+                ),
+                "270={price} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn test_update_fx_book_from_fix_message() {
+        let message = fix::encode_market_data_snapshot(&create_snapshot());
+        let market_data = fix::decode_market_data(&message).unwrap();
+        let mut fx_book = FxBook {
+            currency_pair: String::from("USD/EUR"),
+            buy_book: Vec::new(),
+            sell_book: Vec::new(),
+            timestamp: 0,
+            ..Default::default()
+        };
+
+        fx_book.update(&market_data).unwrap();
+
+        assert_eq!(fx_book.timestamp, 1753440851702000000);
+        assert_eq!(fx_book.buy_book[0].price, Price::new(15552, 4));
+        assert_eq!(fx_book.sell_book[0].price, Price::new(15562, 4));
+        assert_eq!(
+            fx_book.sell_book[0].lp_vol,
+            vec![(String::from("MS"), 1, String::from("S1"))]
+        );
+    }
+
+    fn create_incremental_refresh(
+        entries: Vec<(MdUpdateAction, &str, BookSide, i64)>,
+    ) -> MarketData {
+        MarketData::Incremental(MarketDataIncrementalRefresh {
+            liquidity_provider: String::from("MS"),
+            currency_pair: String::from("USD/EUR"),
+            msg_seq_num: 8,
+            entries: entries
+                .into_iter()
+                .map(|(action, entry_id, side, ticks)| MdIncrementalEntry {
+                    action,
+                    entry: MdEntry {
+                        entry_id: String::from(entry_id),
+                        side,
+                        price: Price::new(ticks, 4),
+                        volume: 1,
+                    },
+                })
+                .collect(),
+            timestamp: 1753440852702000000,
+        })
+    }
+
+    #[test]
+    fn test_incremental_refresh_round_trip() {
+        let refresh = create_incremental_refresh(vec![
+            (MdUpdateAction::Change, "B1", BookSide::Buy, 15553),
+            (MdUpdateAction::New, "S3", BookSide::Sell, 15570),
+        ]);
+        let MarketData::Incremental(incremental) = &refresh else {
+            panic!("expected an incremental refresh");
+        };
+        let message = fix::encode_market_data_incremental_refresh(incremental);
+
+        assert!(message.contains("\x0135=X\x01"));
+        assert!(
+            message.contains("268=2\x01279=1\x01269=0\x01278=B1\x0155=USD/EUR\x01270=1.5553\x01")
+        );
+        assert_eq!(fix::decode_market_data(&message).unwrap(), refresh);
+
+        // deletes carry no price or size
+        let delete =
+            create_incremental_refresh(vec![(MdUpdateAction::Delete, "S1", BookSide::Sell, 0)]);
+        let MarketData::Incremental(incremental) = &delete else {
+            panic!("expected an incremental refresh");
+        };
+        let message = fix::encode_market_data_incremental_refresh(incremental);
+        assert!(!message.contains("270="));
+        let MarketData::Incremental(decoded) = fix::decode_market_data(&message).unwrap() else {
+            panic!("expected an incremental refresh");
+        };
+        assert_eq!(decoded.entries[0].action, MdUpdateAction::Delete);
+        assert_eq!(decoded.entries[0].entry.entry_id, "S1");
+    }
+
+    #[test]
+    fn test_apply_incremental_refresh() {
+        let mut fx_book = FxBook {
+            currency_pair: String::from("USD/EUR"),
+            ..Default::default()
+        };
+        fx_book
+            .update(&MarketData::Snapshot(create_snapshot()))
+            .unwrap();
+
+        // move the 1M buy quote up a pip
+        fx_book
+            .update(&create_incremental_refresh(vec![(
+                MdUpdateAction::Change,
+                "B1",
+                BookSide::Buy,
+                15553,
+            )]))
+            .unwrap();
+        assert_eq!(fx_book.buy_book.len(), 1);
+        assert_eq!(fx_book.buy_book[0].price, Price::new(15553, 4));
+        assert_eq!(fx_book.timestamp, 1753440852702000000);
+
+        // add a new sell quote at a new level then delete the original one
+        fx_book
+            .update(&create_incremental_refresh(vec![
+                (MdUpdateAction::New, "S3", BookSide::Sell, 15570),
+                (MdUpdateAction::Delete, "S1", BookSide::Sell, 0),
+            ]))
+            .unwrap();
+        assert_eq!(fx_book.sell_book.len(), 1);
+        assert_eq!(fx_book.sell_book[0].price, Price::new(15570, 4));
+        assert_eq!(
+            fx_book.sell_book[0].lp_vol,
+            vec![(String::from("MS"), 1, String::from("S3"))]
+        );
+        assert_eq!(fx_book.lp_books["MS"].len(), 2);
+    }
+
+    #[test]
+    fn test_incremental_offers_into_empty_book() {
+        // offers arriving before any bid are added to the aggregated book as well as the liquidity provider's book
+        let mut fx_book = FxBook {
+            currency_pair: String::from("USD/EUR"),
+            ..Default::default()
+        };
+        fx_book
+            .update(&create_incremental_refresh(vec![
+                (MdUpdateAction::New, "S1", BookSide::Sell, 15562),
+                (MdUpdateAction::New, "S3", BookSide::Sell, 15565),
+            ]))
+            .unwrap();
+        assert!(fx_book.buy_book.is_empty());
+        let prices: Vec<i64> = fx_book
+            .sell_book
+            .iter()
+            .map(|level| level.price.ticks())
+            .collect();
+        assert_eq!(prices, vec![15562, 15565]);
+        assert_eq!(fx_book.lp_books["MS"].len(), 2);
+
+        // and can be deleted again
+        fx_book
+            .update(&create_incremental_refresh(vec![(
+                MdUpdateAction::Delete,
+                "S1",
+                BookSide::Sell,
+                0,
+            )]))
+            .unwrap();
+        assert_eq!(fx_book.sell_book.len(), 1);
+        assert_eq!(fx_book.sell_book[0].price, Price::new(15565, 4));
+    }
+
+    #[test]
+    fn test_get_incremental_entries() {
+        let previous_entries = vec![
+            MdEntry {
+                entry_id: String::from("B1"),
+                side: BookSide::Buy,
+                price: Price::new(15552, 4),
+                volume: 1,
+            },
+            MdEntry {
+                entry_id: String::from("S1"),
+                side: BookSide::Sell,
+                price: Price::new(15562, 4),
+                volume: 1,
+            },
+        ];
+        let mut entries = previous_entries.clone();
+        entries[1].price = Price::new(15563, 4);
+        let mut pulled = vec![false, false];
+
+        let mut rng = StdRng::seed_from_u64(1);
+
+        let incremental_entries =
+            simulator::get_incremental_entries(&previous_entries, &entries, &mut pulled, &mut rng);
+
+        assert_eq!(
+            incremental_entries,
+            vec![MdIncrementalEntry {
+                action: MdUpdateAction::Change,
+                entry: entries[1].clone(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_book_listener() {
+        // a listener that keeps a record of every book update it is given
+        #[derive(Default)]
+        struct RecordingListener {
+            records: Vec<String>,
+        }
+        impl BookListener for RecordingListener {
+            fn on_book_update(&mut self, fx_book: &FxBook) -> Result<(), AppError> {
+                self.records.push(listener::format_book_record(fx_book));
+                Ok(())
+            }
+        }
+
+        let mut fx_books = BookManager::default();
+        let mut book_listener = RecordingListener::default();
+        let fx_book = fx_books
+            .update(&MarketData::Snapshot(create_snapshot()))
+            .unwrap();
+        book_listener.on_book_update(fx_book).unwrap();
+
+        assert_eq!(
+            book_listener.records,
+            vec!["1753440851702000000|USD/EUR|1.5552:1|1.5562:1"]
+        );
+    }
+
+    #[test]
+    fn test_book_manager_routes_by_currency_pair() {
+        let mut fx_books = BookManager::default();
+        let usd_eur = create_snapshot();
+        let usd_jpy = MarketDataSnapshot {
+            currency_pair: String::from("USD/JPY"),
+            entries: vec![
+                MdEntry {
+                    entry_id: String::from("B1"),
+                    side: BookSide::Buy,
+                    price: Price::new(14725, 2),
+                    volume: 1,
+                },
+                MdEntry {
+                    entry_id: String::from("S1"),
+                    side: BookSide::Sell,
+                    price: Price::new(14735, 2),
+                    volume: 1,
+                },
+            ],
+            ..create_snapshot()
+        };
+
+        let fx_book = fx_books.update(&MarketData::Snapshot(usd_jpy)).unwrap();
+        assert_eq!(fx_book.currency_pair, "USD/JPY");
+        fx_books.update(&MarketData::Snapshot(usd_eur)).unwrap();
+
+        assert_eq!(fx_books.currency_pairs(), vec!["USD/EUR", "USD/JPY"]);
+        let usd_jpy_book = fx_books.get("USD/JPY").unwrap();
+        assert_eq!(usd_jpy_book.buy_book[0].price.to_string(), "147.25");
+        assert_eq!(usd_jpy_book.sell_book.len(), 1);
+        let usd_eur_book = fx_books.get("USD/EUR").unwrap();
+        assert_eq!(usd_eur_book.buy_book[0].price.to_string(), "1.5552");
+    }
+
+    fn lp_snapshot(liquidity_provider: &str, quotes: &[(&str, i64, i32)]) -> MarketData {
+        // a USD/EUR snapshot of (MDEntryID, price in ticks, volume) quotes, with buys starting with B
+        MarketData::Snapshot(MarketDataSnapshot {
+            liquidity_provider: String::from(liquidity_provider),
+            entries: quotes
+                .iter()
+                .map(|(entry_id, ticks, volume)| MdEntry {
+                    entry_id: String::from(*entry_id),
+                    side: if entry_id.starts_with('B') {
+                        BookSide::Buy
+                    } else {
+                        BookSide::Sell
+                    },
+                    price: Price::new(*ticks, 4),
+                    volume: *volume,
+                })
+                .collect(),
+            ..create_snapshot()
+        })
+    }
+
+    #[test]
+    fn test_execute_order() {
+        let mut fx_books = BookManager::default();
+        let ms_quotes = [
+            ("B1", 15552, 1),
+            ("S1", 15562, 1),
+            ("B10", 15549, 10),
+            ("S10", 15565, 10),
+        ];
+        fx_books.update(&lp_snapshot("MS", &ms_quotes)).unwrap();
+        let citi_quotes = [("B1", 15552, 1), ("S1", 15562, 1), ("S5", 15564, 5)];
+        fx_books.update(&lp_snapshot("CITI", &citi_quotes)).unwrap();
+
+        // a market order sweeps the levels from the top filling the liquidity providers in arrival order
+        let buy = Order {
+            currency_pair: String::from("USD/EUR"),
+            side: OrderSide::Buy,
+            volume: 4,
+            order_type: OrderType::Market,
+        };
+        let execution = fx_books.execute(&buy).unwrap();
+        assert_eq!(
+            execution
+                .fills
+                .iter()
+                .map(|fill| (
+                    fill.liquidity_provider.as_str(),
+                    fill.volume,
+                    fill.price.to_string()
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                ("MS", 1, String::from("1.5562")),
+                ("CITI", 1, String::from("1.5562")),
+                ("CITI", 2, String::from("1.5564")),
+            ]
+        );
+        assert!((execution.vwap().unwrap() - 1.5563).abs() < 1e-9);
+        let fx_book = fx_books.get("USD/EUR").unwrap();
+        assert_eq!(fx_book.sell_book[0].price, Price::new(15564, 4));
+        assert_eq!(
+            fx_book.sell_book[0].lp_vol,
+            vec![(String::from("CITI"), 3, String::from("S5"))]
+        );
+        assert_eq!(fx_book.lp_books["CITI"]["S5"].volume, 3);
+
+        // a limit order only fills at its limit or better and the rest is cancelled
+        let sell = Order {
+            side: OrderSide::Sell,
+            volume: 5,
+            order_type: OrderType::Limit(Price::new(15550, 4)),
+            ..buy.clone()
+        };
+        let execution = fx_books.execute(&sell).unwrap();
+        assert_eq!(execution.filled_volume(), 2);
+        assert_eq!(execution.unfilled_volume(), 3);
+        assert_eq!(fx_books.get("USD/EUR").unwrap().buy_book[0].volume, 10);
+
+        // the liquidity provider's next refresh replaces what was left of its quotes
+        fx_books.update(&lp_snapshot("CITI", &citi_quotes)).unwrap();
+        let fx_book = fx_books.get("USD/EUR").unwrap();
+        assert_eq!(
+            fx_book
+                .sell_book
+                .iter()
+                .map(|level| (level.price.ticks(), level.volume))
+                .collect::<Vec<_>>(),
+            vec![(15562, 1), (15564, 5), (15565, 10)]
+        );
+
+        assert!(matches!(
+            fx_books.execute(&Order {
+                volume: 0,
+                ..buy.clone()
+            }),
+            Err(AppError::InvalidOrder(_))
+        ));
+        assert!(matches!(
+            fx_books.execute(&Order {
+                currency_pair: String::from("USD/GBP"),
+                ..buy
+            }),
+            Err(AppError::InvalidOrder(reason)) if reason == "no book for USD/GBP"
+        ));
+    }
+
+    #[test]
+    fn test_lp_books_stay_in_sync() {
+        // every quote with volume in a liquidity provider's book is in the aggregated book under its MDEntryID
+        // and no price level is left without quotes
+        let in_sync = |fx_book: &FxBook| {
+            let levels = fx_book.buy_book.iter().chain(&fx_book.sell_book);
+            let mut aggregated: Vec<(String, String, i32)> = levels
+                .clone()
+                .flat_map(|level| &level.lp_vol)
+                .map(|lp_vol| (lp_vol.0.clone(), lp_vol.2.clone(), lp_vol.1))
+                .collect();
+            let mut lp_quotes: Vec<(String, String, i32)> = fx_book
+                .lp_books
+                .iter()
+                .flat_map(|(liquidity_provider, lp_book)| {
+                    lp_book
+                        .values()
+                        .filter(|entry| entry.volume > 0)
+                        .map(|entry| {
+                            (
+                                liquidity_provider.clone(),
+                                entry.entry_id.clone(),
+                                entry.volume,
+                            )
+                        })
+                })
+                .collect();
+            aggregated.sort();
+            lp_quotes.sort();
+            aggregated == lp_quotes && levels.clone().all(|level| !level.lp_vol.is_empty())
+        };
+
+        // a partly filled 3M quote is left at the same price and size as the 1M quote behind it
+        let mut fx_books = BookManager::default();
+        let ms_quotes = [("B3", 15552, 3), ("B1", 15552, 1), ("S1", 15562, 1)];
+        fx_books.update(&lp_snapshot("MS", &ms_quotes)).unwrap();
+        let sell = Order {
+            currency_pair: String::from("USD/EUR"),
+            side: OrderSide::Sell,
+            volume: 2,
+            order_type: OrderType::Market,
+        };
+        fx_books.execute(&sell).unwrap();
+        let fx_book = fx_books.get("USD/EUR").unwrap();
+        assert_eq!(fx_book.lp_books["MS"]["B3"].volume, 1);
+        assert!(in_sync(fx_book));
+
+        // so deleting the 1M quote removes that quote rather than what is left of the 3M one
+        fx_books
+            .update(&create_incremental_refresh(vec![(
+                MdUpdateAction::Delete,
+                "B1",
+                BookSide::Buy,
+                0,
+            )]))
+            .unwrap();
+        let fx_book = fx_books.get("USD/EUR").unwrap();
+        assert_eq!(
+            fx_book.buy_book[0].lp_vol,
+            vec![(String::from("MS"), 1, String::from("B3"))]
+        );
+        assert!(in_sync(fx_book));
+
+        // an offer removed to uncross the books is taken off its liquidity provider's book too
+        let citi_quotes = [("B1", 15563, 1), ("S1", 15573, 1)];
+        fx_books.update(&lp_snapshot("CITI", &citi_quotes)).unwrap();
+        let fx_book = fx_books.get("USD/EUR").unwrap();
+        assert_eq!(fx_book.sell_book[0].price, Price::new(15573, 4));
+        assert_eq!(fx_book.lp_books["MS"]["S1"].volume, 0);
+        assert!(in_sync(fx_book));
+
+        // and a quote removed to keep the spread
+        let mut fx_books = BookManager::new(
+            &[],
+            &Settings {
+                min_spread_pips: 6.0,
+                ..Default::default()
+            },
+        );
+        let ms_quotes = [("B1", 15552, 1), ("S1", 15562, 1)];
+        fx_books.update(&lp_snapshot("MS", &ms_quotes)).unwrap();
+        let citi_quotes = [("B1", 15557, 1), ("S1", 15567, 1)];
+        fx_books.update(&lp_snapshot("CITI", &citi_quotes)).unwrap();
+        let fx_book = fx_books.get("USD/EUR").unwrap();
+        assert_eq!(fx_book.buy_book[0].price, Price::new(15552, 4));
+        assert_eq!(fx_book.lp_books["CITI"]["B1"].volume, 0);
+        assert!(in_sync(fx_book));
+
+        // a snapshot replaces every quote of its liquidity provider, including lifted ones
+        let citi_quotes = [("B1", 15550, 1), ("S1", 15567, 1)];
+        fx_books.update(&lp_snapshot("CITI", &citi_quotes)).unwrap();
+        let fx_book = fx_books.get("USD/EUR").unwrap();
+        assert_eq!(fx_book.buy_book.len(), 2);
+        assert_eq!(fx_book.lp_books["CITI"]["B1"].volume, 1);
+        assert!(in_sync(fx_book));
+    }
+
+    #[test]
+    fn test_position_keeper() {
+        // buys average their cost and a larger sell closes the position, realizing P&L, then opens a short
+        let mut position_keeper = PositionKeeper::default();
+        position_keeper.add_fill("USD/EUR", "MS", OrderSide::Buy, 2, 1.5);
+        position_keeper.add_fill("USD/EUR", "CITI", OrderSide::Buy, 2, 1.6);
+        let position = &position_keeper.get("USD/EUR").unwrap().position;
+        assert_eq!(position.net_volume, 4);
+        assert!((position.average_price - 1.55).abs() < 1e-9);
+        position_keeper.add_fill("USD/EUR", "UBS", OrderSide::Sell, 6, 1.7);
+        let pair_position = position_keeper.get("USD/EUR").unwrap();
+        assert_eq!(pair_position.position.net_volume, -2);
+        assert!((pair_position.position.average_price - 1.7).abs() < 1e-9);
+        assert!((position_keeper.realized_pnl("USD/EUR") - 600_000.0).abs() < 1e-3);
+        assert!((pair_position.position.unrealized_pnl(1.65) - 100_000.0).abs() < 1e-3);
+        assert_eq!(pair_position.lp_positions["UBS"].net_volume, -6);
+        assert_eq!(pair_position.lp_positions["MS"].realized_pnl, 0.0);
+
+        // orders executed against the books are added to the positions and marked to the book mid
+        let mut fx_books = BookManager::default();
+        fx_books
+            .update(&lp_snapshot("MS", &[("B1", 15552, 1), ("S1", 15562, 1)]))
+            .unwrap();
+        let order = Order {
+            currency_pair: String::from("USD/EUR"),
+            side: OrderSide::Buy,
+            volume: 1,
+            order_type: OrderType::Market,
+        };
+        fx_books.execute(&order).unwrap();
+        let position = &fx_books.positions.get("USD/EUR").unwrap().lp_positions["MS"];
+        assert_eq!(position.net_volume, 1);
+        assert!((position.average_price - 1.5562).abs() < 1e-9);
+        let fx_book = fx_books.get("USD/EUR").unwrap();
+        assert_eq!(fx_books.positions.unrealized_pnl(fx_book), None);
+        fx_books
+            .update(&lp_snapshot("MS", &[("B1", 15560, 1), ("S1", 15570, 1)]))
+            .unwrap();
+        let fx_book = fx_books.get("USD/EUR").unwrap();
+        let unrealized_pnl = fx_books.positions.unrealized_pnl(fx_book).unwrap();
+        assert!((unrealized_pnl - 300.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_risk_checks() {
+        let risk_config = TEST_CONFIG.replace(
+            "  min_spread_pips: 4\n",
+            "  min_spread_pips: 4\n  risk: { max_order_volume: 5, max_net_position: 4, max_lp_notional: 3500000, price_collar_pips: 10 }\n",
+        );
+        assert!(matches!(
+            config::parse_configs(&risk_config.replace("max_order_volume: 5", "max_order_volume: 0"), &mut Vec::new()),
+            Err(AppError::ConfigFile(e)) if e.to_string().contains("max_order_volume 0 must be greater than 0")
+        ));
+        let mut configs: Vec<Config> = Vec::new();
+        let settings = config::parse_configs(&risk_config, &mut configs).unwrap();
+        let mut fx_books = BookManager::new(&configs, &settings);
+        fx_books
+            .update(&lp_snapshot(
+                "MS",
+                &[("B1", 15552, 1), ("S1", 15562, 1), ("S10", 15575, 10)],
+            ))
+            .unwrap();
+        let buy = |volume: i32| Order {
+            currency_pair: String::from("USD/EUR"),
+            side: OrderSide::Buy,
+            volume,
+            order_type: OrderType::Market,
+        };
+        let risk_error = |fx_books: &mut BookManager, order: &Order| match fx_books.execute(order) {
+            Err(AppError::Risk(e)) => e,
+            result => panic!("expected a risk error, got {result:?}"),
+        };
+
+        assert_eq!(
+            risk_error(&mut fx_books, &buy(6)),
+            RiskError::MaxOrderVolume {
+                volume: 6,
+                limit: 5
+            }
+        );
+        // 2M would fill 13 pips through the top of book
+        assert!(matches!(
+            risk_error(&mut fx_books, &buy(2)),
+            RiskError::PriceCollar { price, .. } if price == Price::new(15575, 4)
+        ));
+        fx_books.execute(&buy(1)).unwrap();
+        assert!(matches!(
+            risk_error(&mut fx_books, &buy(5)),
+            RiskError::MaxNetPosition {
+                position: 6,
+                limit: 4,
+                ..
+            }
+        ));
+        assert!(matches!(
+            risk_error(&mut fx_books, &buy(3)),
+            RiskError::MaxLpNotional { liquidity_provider, .. } if liquidity_provider == "MS"
+        ));
+        fx_books.execute(&buy(1)).unwrap();
+
+        // nothing is executed while the kill switch is engaged, not even an order reducing the position
+        fx_books.risk.set_kill_switch(true);
+        let sell = Order {
+            side: OrderSide::Sell,
+            ..buy(1)
+        };
+        assert_eq!(risk_error(&mut fx_books, &sell), RiskError::KillSwitch);
+        assert_eq!(
+            fx_books.execute(&sell).unwrap_err().to_string(),
+            "order rejected by risk check - kill switch is engaged"
+        );
+        fx_books.risk.set_kill_switch(false);
+        fx_books.execute(&sell).unwrap();
+        assert_eq!(
+            fx_books
+                .positions
+                .get("USD/EUR")
+                .unwrap()
+                .position
+                .net_volume,
+            1
+        );
+
+        // routed orders are checked against what each liquidity provider is sent, so routing 3M of a 4M order to CITI
+        // breaches its notional limit although the 2M of each liquidity provider the book would fill does not
+        let mut fx_books = BookManager::new(&configs, &settings);
+        fx_books
+            .update(&lp_snapshot("MS", &[("S2", 15562, 2)]))
+            .unwrap();
+        fx_books
+            .update(&lp_snapshot("CITI", &[("S3", 15562, 3)]))
+            .unwrap();
+        fx_books.check_order(&buy(4)).unwrap();
+        let route_plan = router::route_order(
+            fx_books.get("USD/EUR").unwrap(),
+            &buy(4),
+            &RouterSettings::default(),
+        )
+        .unwrap();
+        assert_eq!(route_plan.child_orders[0].liquidity_provider, "CITI");
+        assert_eq!(route_plan.child_orders[0].volume, 3);
+        let fx_books_mutex = Arc::new(Mutex::new(fx_books));
+        let result = crate::run(venue::send_child_orders(
+            &mut venue::create_venues(&configs, Some(7)),
+            &route_plan,
+            "1",
+            &fx_books_mutex,
+            &mut SimClock::new(ClockMode::AsFastAsPossible, clock::VIRTUAL_START_TIMESTAMP),
+        ));
+        assert!(matches!(
+            result,
+            Err(AppError::Risk(RiskError::MaxLpNotional { liquidity_provider, .. })) if liquidity_provider == "CITI"
+        ));
+        assert!(
+            fx_books_mutex
+                .lock()
+                .unwrap()
+                .positions
+                .get("USD/EUR")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_price_history() {
+        let mut fx_books = BookManager::default();
+        fx_books
+            .update(&lp_snapshot("MS", &[("B1", 15552, 1), ("S1", 15562, 1)]))
+            .unwrap();
+        let citi_snapshot = lp_snapshot(
+            "CITI",
+            &[
+                ("B1", 15554, 1),
+                ("B5", 15553, 5),
+                ("S1", 15566, 1),
+                ("S5", 15564, 5),
+            ],
+        );
+        fx_books.update(&citi_snapshot).unwrap();
+        let history = fx_books.history("USD/EUR").unwrap();
+        assert_eq!(history.points().len(), 2);
+        let point = history.points().back().unwrap();
+        assert_eq!(point.bid, Some(Price::new(15554, 4)));
+        assert_eq!(point.ask, Some(Price::new(15562, 4)));
+        assert!((point.mid().unwrap() - 1.5558).abs() < 1e-9);
+        // each liquidity provider's 1M tier quotes, even when a larger tier is priced better
+        assert_eq!(
+            point.lp_quotes["CITI"],
+            (Some(Price::new(15554, 4)), Some(Price::new(15566, 4)))
+        );
+        // and a gap once the 1M tier has been lifted rather than the next tier's quote
+        let sell = Order {
+            currency_pair: String::from("USD/EUR"),
+            side: OrderSide::Sell,
+            volume: 1,
+            order_type: OrderType::Market,
+        };
+        fx_books.execute(&sell).unwrap();
+        let history = fx_books.history("USD/EUR").unwrap();
+        let point = history.points().back().unwrap();
+        assert_eq!(point.bid, Some(Price::new(15553, 4)));
+        assert_eq!(point.lp_quotes["CITI"], (None, Some(Price::new(15566, 4))));
+        assert_eq!(history.points().len(), 3);
+        assert_eq!(
+            history
+                .liquidity_providers()
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec![String::from("CITI"), String::from("MS")]
+        );
+
+        // the oldest updates are dropped once the history is full and a time window keeps only the latest updates
+        let mut price_history = PriceHistory::new(3);
+        let mut fx_book = FxBook::new("USD/EUR", 0.0);
+        for secs in [0, 100, 200, 290] {
+            fx_book.timestamp = secs * 1_000_000_000;
+            price_history.record(&fx_book);
+        }
+        assert_eq!(price_history.points().len(), 3);
+        let latest = price_history.latest_timestamp().unwrap();
+        assert_eq!(latest, 290_000_000_000);
+        assert_eq!(
+            price_history
+                .since(TimeWindow::OneMinute.start(latest))
+                .count(),
+            1
+        );
+        assert_eq!(
+            price_history
+                .since(TimeWindow::FiveMinutes.start(latest))
+                .count(),
+            3
+        );
+        assert_eq!(TimeWindow::All.start(latest), 0);
+    }
+
+    #[test]
+    fn test_market_depth() {
+        let mut fx_books = BookManager::default();
+        fx_books
+            .update(&lp_snapshot(
+                "MS",
+                &[("B1", 15552, 1), ("S1", 15562, 1), ("S10", 15565, 10)],
+            ))
+            .unwrap();
+        fx_books
+            .update(&lp_snapshot(
+                "CITI",
+                &[("B3", 15552, 3), ("B5", 15550, 5), ("S3", 15562, 3)],
+            ))
+            .unwrap();
+        let fx_book = fx_books.get("USD/EUR").unwrap();
+        let depth = |depth: Vec<aggregator::DepthLevel>| {
+            depth
+                .into_iter()
+                .map(|level| {
+                    (
+                        level.price.ticks(),
+                        level.cumulative_volume,
+                        level.lp_vol.len(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            depth(aggregator::market_depth(&fx_book.buy_book)),
+            vec![(15552, 4, 2), (15550, 9, 1)]
+        );
+        assert_eq!(
+            depth(aggregator::market_depth(&fx_book.sell_book)),
+            vec![(15562, 4, 2), (15565, 14, 1)]
+        );
+        assert!(aggregator::market_depth(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_book_changes() {
+        let mut fx_books = BookManager::default();
+        fx_books
+            .update(&lp_snapshot(
+                "MS",
+                &[("B1", 15552, 1), ("B5", 15550, 5), ("S1", 15562, 1)],
+            ))
+            .unwrap();
+        let previous = fx_books.get("USD/EUR").unwrap().buy_book.clone();
+        let mut book_highlighter = BookHighlighter::default();
+        book_highlighter.update(fx_books.get("USD/EUR").unwrap(), 0.0);
+        // the first state of a book is not flashed
+        assert!(!book_highlighter.is_flashing());
+
+        fx_books
+            .update(&lp_snapshot(
+                "MS",
+                &[("B2", 15554, 2), ("B3", 15552, 3), ("S1", 15562, 1)],
+            ))
+            .unwrap();
+        let fx_book = fx_books.get("USD/EUR").unwrap();
+        let changes = aggregator::level_changes(&previous, &fx_book.buy_book)
+            .into_iter()
+            .map(|(price, change)| (price.ticks(), change))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            changes,
+            vec![
+                (15554, LevelChange::Inserted),
+                (15552, LevelChange::Updated),
+                (15550, LevelChange::Removed)
+            ]
+        );
+        assert!(aggregator::level_changes(&fx_book.sell_book, &fx_book.sell_book).is_empty());
+
+        book_highlighter.update(fx_book, 1.0);
+        assert_eq!(
+            book_highlighter.tick(BookSide::Buy),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(book_highlighter.tick(BookSide::Sell), None);
+        assert!(
+            book_highlighter
+                .flash(BookSide::Buy, fx_book.buy_book[0].price, 1.0)
+                .is_some()
+        );
+        assert!(
+            book_highlighter
+                .flash(BookSide::Sell, fx_book.sell_book[0].price, 1.0)
+                .is_none()
+        );
+        assert_eq!(book_highlighter.removed_levels(BookSide::Buy).len(), 1);
+        // flashes fade, and removed levels go with them
+        book_highlighter.update(fx_book, 2.0);
+        assert!(!book_highlighter.is_flashing());
+        assert!(book_highlighter.removed_levels(BookSide::Buy).is_empty());
+    }
+
+    #[test]
+    fn test_feed_statistics() {
+        let secs = |secs: u64| secs * 1_000_000_000;
+        let mut feeds = FeedMonitor::default();
+        feeds.add_feed("USD/EUR", "MS");
+        feeds.add_feed("USD/EUR", "CITI");
+        let pair_feeds = |feeds: &FeedMonitor| feeds.pair_feeds("USD/EUR").unwrap().clone();
+        assert_eq!(
+            pair_feeds(&feeds)["MS"].status(secs(1)),
+            FeedStatus::Waiting
+        );
+
+        // MS quotes every second, CITI once and then falls silent
+        for n in 0..10 {
+            feeds.record(&lp_snapshot("MS", &[("B1", 15552, 1)]), secs(100 + n));
+        }
+        feeds.record(&lp_snapshot("CITI", &[("B1", 15552, 1)]), secs(100));
+        let now = secs(109);
+        let ms = &pair_feeds(&feeds)["MS"];
+        assert_eq!(ms.messages, 10);
+        assert_eq!(ms.average_interval_secs(), Some(1.0));
+        assert_eq!(ms.message_rate(now), 1.0);
+        assert_eq!(ms.status(now), FeedStatus::Live);
+        assert_eq!(pair_feeds(&feeds)["CITI"].status(now), FeedStatus::Stalled);
+        assert_eq!(feeds.message_rate("USD/EUR", now), 1.1);
+        // a feed is stalled after ten of its average intervals without a message
+        assert_eq!(ms.status(secs(119)), FeedStatus::Live);
+        assert_eq!(ms.status(secs(120)), FeedStatus::Stalled);
+        assert_eq!(ms.message_rate(secs(120)), 0.0);
+
+        feeds.complete("USD/EUR", "CITI");
+        assert_eq!(
+            pair_feeds(&feeds)["CITI"].status(now),
+            FeedStatus::Completed
+        );
+        assert_eq!(feeds.message_rate("GBP/USD", now), 0.0);
+    }
+
+    #[test]
+    fn test_trade_ticket() {
+        let mut fx_books = BookManager::default();
+        fx_books
+            .update(&lp_snapshot(
+                "MS",
+                &[("B1", 15552, 1), ("S1", 15562, 1), ("S10", 15565, 10)],
+            ))
+            .unwrap();
+        // a ticket opened from the second offer buys everything up to and including it
+        let mut trade_ticket =
+            TradeTicket::new("USD/EUR", OrderSide::Buy, Price::new(15565, 4), 11);
+        assert_eq!(trade_ticket.limit_price, "1.5565");
+        let order = trade_ticket.order(OrderSide::Buy).unwrap();
+        assert_eq!(order.order_type, OrderType::Limit(Price::new(15565, 4)));
+        trade_ticket.volume = 12;
+
+        // a confirmed order is routed to the liquidity providers' venues, which fill it from their quotes in the book
+        let mut configs: Vec<Config> = Vec::new();
+        let settings = config::parse_configs(TEST_CONFIG, &mut configs).unwrap();
+        let fx_books_mutex = Arc::new(Mutex::new(fx_books));
+        let execution = crate::run(venue::route_and_send(
+            &mut venue::create_venues(&configs, Some(7)),
+            &trade_ticket.order(OrderSide::Buy).unwrap(),
+            &settings.router,
+            "1",
+            &fx_books_mutex,
+            &mut SimClock::new(ClockMode::AsFastAsPossible, clock::VIRTUAL_START_TIMESTAMP),
+        ))
+        .unwrap();
+        assert_eq!(execution.filled_volume(), 11);
+        assert_eq!(execution.unfilled_volume(), 1);
+        assert!((execution.vwap().unwrap() - 1.556473).abs() < 1e-6);
+        let fx_books = fx_books_mutex.lock().unwrap();
+        assert!(fx_books.get("USD/EUR").unwrap().sell_book.is_empty());
+        assert_eq!(
+            fx_books
+                .positions
+                .get("USD/EUR")
+                .unwrap()
+                .position
+                .net_volume,
+            11
+        );
+
+        trade_ticket.limit_price = String::from("1.55x");
+        assert!(
+            trade_ticket
+                .order(OrderSide::Sell)
+                .unwrap_err()
+                .starts_with("limit price \"1.55x\"")
+        );
+        trade_ticket.market = true;
+        let order = trade_ticket.order(OrderSide::Sell).unwrap();
+        assert_eq!(
+            (order.side, order.order_type),
+            (OrderSide::Sell, OrderType::Market)
+        );
+    }
+
+    #[test]
+    fn test_route_order() {
+        let mut fx_books = BookManager::default();
+        let quotes = [
+            (
+                "MS",
+                vec![("B1", 15552, 1), ("S1", 15562, 1), ("S10", 15565, 10)],
+            ),
+            ("CITI", vec![("S5", 15562, 5), ("S10", 15564, 10)]),
+            ("UBS", vec![("S3", 15562, 3)]),
+        ];
+        for (liquidity_provider, quotes) in &quotes {
+            fx_books
+                .update(&lp_snapshot(liquidity_provider, quotes))
+                .unwrap();
+        }
+        let fx_book = fx_books.get("USD/EUR").unwrap();
+        let order = Order {
+            currency_pair: String::from("USD/EUR"),
+            side: OrderSide::Buy,
+            volume: 6,
+            order_type: OrderType::Market,
+        };
+        let route = |order: &Order, policy: RoutingPolicy, min_fill_volume: Option<i32>| {
+            let router_settings = RouterSettings {
+                policy,
+                min_fill_volume,
+            };
+            router::route_order(fx_book, order, &router_settings).unwrap()
+        };
+        let child_volumes = |route_plan: &RoutePlan| {
+            route_plan
+                .child_orders
+                .iter()
+                .map(|child| (child.liquidity_provider.clone(), child.volume))
+                .collect::<Vec<_>>()
+        };
+        let lp_volumes = |lp_volumes: &[(&str, i32)]| {
+            lp_volumes
+                .iter()
+                .map(|(liquidity_provider, volume)| (String::from(*liquidity_provider), *volume))
+                .collect::<Vec<_>>()
+        };
+
+        // 6M of the 9M at the top of book, split by each policy
+        let best_price = route(&order, RoutingPolicy::BestPrice, None);
+        assert_eq!(
+            child_volumes(&best_price),
+            lp_volumes(&[("CITI", 5), ("UBS", 1)])
+        );
+        assert!(best_price.slippage_pips().unwrap().abs() < 1e-6);
+        let pro_rata = route(&order, RoutingPolicy::ProRata, None);
+        assert_eq!(
+            child_volumes(&pro_rata),
+            lp_volumes(&[("MS", 1), ("CITI", 3), ("UBS", 2)])
+        );
+        let ranking = vec![String::from("UBS"), String::from("MS")];
+        let preference = route(&order, RoutingPolicy::Preference { ranking }, None);
+        assert_eq!(
+            child_volumes(&preference),
+            lp_volumes(&[("UBS", 3), ("MS", 1), ("CITI", 2)])
+        );
+
+        // liquidity providers below the minimum fill are left out so the order goes deeper into the book
+        let min_fill = route(&order, RoutingPolicy::BestPrice, Some(2));
+        assert_eq!(child_volumes(&min_fill), lp_volumes(&[("CITI", 6)]));
+        assert_eq!(min_fill.child_orders[0].limit_price, Price::new(15564, 4));
+        assert!((min_fill.slippage_pips().unwrap() - 1.0 / 3.0).abs() < 1e-6);
+
+        // a limit order is only routed to the levels at its limit price or better
+        let limit_order = Order {
+            volume: 20,
+            order_type: OrderType::Limit(Price::new(15562, 4)),
+            ..order.clone()
+        };
+        let limit = route(&limit_order, RoutingPolicy::ProRata, None);
+        assert_eq!(limit.routed_volume(), 9);
+        assert_eq!(limit.unrouted_volume(), 11);
+
+        let router_config = TEST_CONFIG.replace(
+            "  min_spread_pips: 4\n",
+            "  min_spread_pips: 4\n  router: { policy: { kind: pro_rata }, min_fill_volume: 0 }\n",
+        );
+        assert!(matches!(
+            config::parse_configs(&router_config, &mut Vec::new()),
+            Err(AppError::ConfigFile(e)) if e.to_string().contains("min_fill_volume 0 must be greater than 0")
+        ));
+        let router_config = router_config.replace("min_fill_volume: 0", "min_fill_volume: 2");
+        let settings = config::parse_configs(&router_config, &mut Vec::new()).unwrap();
+        assert_eq!(settings.router.policy, RoutingPolicy::ProRata);
+    }
+
+    #[test]
+    fn test_lp_venue() {
+        let venue_config = TEST_CONFIG.replace(
+            "    run_iterations: 10\n",
+            "    run_iterations: 10\n    venue: { latency_ms: 5, last_look_ms: 10, max_price_move_pips: 1 }\n",
+        );
+        let mut configs: Vec<Config> = Vec::new();
+        config::parse_configs(&venue_config, &mut configs).unwrap();
+        assert_eq!(configs[0].venue.response_time(), Duration::from_millis(15));
+        assert_eq!(configs[1].venue.response_time(), Duration::ZERO);
+        assert!(matches!(
+            config::parse_configs(
+                &venue_config.replace("max_price_move_pips: 1", "reject_probability: 1.5"),
+                &mut Vec::new()
+            ),
+            Err(AppError::ConfigFile(e)) if e.to_string().contains("reject_probability 1.5 is not a probability")
+        ));
+        assert!(matches!(
+            config::parse_configs(
+                &venue_config.replace("max_price_move_pips: 1", "max_price_move_pips: 0.5"),
+                &mut Vec::new()
+            ),
+            Err(AppError::ConfigFile(e)) if e.to_string().contains("max_price_move_pips 0.5 must be a whole number of pips")
+        ));
+
+        let mut fx_books = BookManager::default();
+        fx_books
+            .update(&lp_snapshot(
+                "MS",
+                &[("B1", 15552, 1), ("S1", 15562, 1), ("S10", 15565, 10)],
+            ))
+            .unwrap();
+        let order = Order {
+            currency_pair: String::from("USD/EUR"),
+            side: OrderSide::Buy,
+            volume: 5,
+            order_type: OrderType::Market,
+        };
+        let fx_book = fx_books.get("USD/EUR").unwrap();
+        assert_eq!(
+            venue::lp_quote(fx_book, "MS", OrderSide::Buy),
+            Some(Price::new(15562, 4))
+        );
+        let route_plan = router::route_order(fx_book, &order, &RouterSettings::default()).unwrap();
+        let child_order = &route_plan.child_orders[0];
+
+        // filled level by level when the quote has moved no more than the tolerance against the best routed price
+        let mut lp_venue = LpVenue::new(&configs[0], StdRng::seed_from_u64(1));
+        let reports = lp_venue.handle_child_order(
+            "1-1",
+            OrderSide::Buy,
+            child_order,
+            Some(Price::new(15563, 4)),
+            0,
+        );
+        let fills: Vec<_> = reports
+            .iter()
+            .map(|report| {
+                (
+                    report.ord_status,
+                    report.last_qty,
+                    report.last_px,
+                    report.leaves_qty,
+                )
+            })
+            .collect();
+        assert_eq!(
+            fills,
+            vec![
+                (OrdStatus::PartiallyFilled, 1, Price::new(15562, 4), 4),
+                (OrdStatus::Filled, 4, Price::new(15565, 4), 0)
+            ]
+        );
+        assert!((reports[1].avg_px - 1.55644).abs() < 1e-9);
+        let fix_message = fix::decode(&fix::encode_execution_report(&reports[1])).unwrap();
+        assert_eq!(fix_message.msg_type().unwrap(), "8");
+        assert_eq!(fix_message.get(150), Some("F"));
+        assert_eq!(fix_message.get(39), Some("2"));
+        assert_eq!(fix_message.get(32), Some("4000000"));
+        assert_eq!(fix_message.get(31), Some("1.5565"));
+        assert_eq!(fix_message.get(14), Some("5000000"));
+
+        // rejected when the quote has moved further, is pulled or at random
+        let reject_reason = |lp_venue: &mut LpVenue, quote: Option<Price>| {
+            let reports = lp_venue.handle_child_order("1-1", OrderSide::Buy, child_order, quote, 0);
+            assert_eq!(reports.len(), 1);
+            assert_eq!(reports[0].ord_status, OrdStatus::Rejected);
+            reports[0].text.clone().unwrap()
+        };
+        assert_eq!(
+            reject_reason(&mut lp_venue, Some(Price::new(15564, 4))),
+            "price moved 2.0 pips"
+        );
+        // even when the quote is still within the tolerance of the worst routed price
+        assert_eq!(
+            reject_reason(&mut lp_venue, Some(Price::new(15566, 4))),
+            "price moved 4.0 pips"
+        );
+        assert_eq!(reject_reason(&mut lp_venue, None), "no quote");
+        let mut always_rejects = LpVenue::new(&configs[0], StdRng::seed_from_u64(1));
+        always_rejects.venue.reject_probability = 1.0;
+        assert_eq!(
+            reject_reason(&mut always_rejects, Some(Price::new(15562, 4))),
+            "last look reject"
+        );
+
+        // the child orders of a route are decided against the liquidity providers' quotes in the aggregated book
+        let mut venues = venue::create_venues(&configs, Some(7));
+        let fx_books_mutex = Arc::new(Mutex::new(fx_books));
+        // venue timing follows the simulation clock, so as fast as possible runs do not wait in real time
+        let start = clock::VIRTUAL_START_TIMESTAMP;
+        let mut sim_clock = SimClock::new(ClockMode::AsFastAsPossible, start);
+        let reports = crate::run(venue::send_child_orders(
+            &mut venues,
+            &route_plan,
+            "1",
+            &fx_books_mutex,
+            &mut sim_clock,
+        ))
+        .unwrap();
+        assert_eq!(reports.last().unwrap().ord_status, OrdStatus::Filled);
+        assert_eq!(reports.last().unwrap().cl_ord_id, "1-1");
+        assert_eq!(
+            reports.last().unwrap().timestamp,
+            start + 15 * clock::NANOS_PER_MILLI
+        );
+        assert_eq!(sim_clock.now(), start + 15 * clock::NANOS_PER_MILLI);
+        let position = fx_books_mutex
+            .lock()
+            .unwrap()
+            .positions
+            .get("USD/EUR")
+            .cloned();
+        assert_eq!(position.unwrap().position.net_volume, 5);
+        // and the filled volume is taken from the liquidity provider's quotes in the book
+        {
+            let fx_books = fx_books_mutex.lock().unwrap();
+            let fx_book = fx_books.get("USD/EUR").unwrap();
+            assert_eq!(fx_book.sell_book.len(), 1);
+            assert_eq!(fx_book.sell_book[0].price, Price::new(15565, 4));
+            assert_eq!(fx_book.sell_book[0].volume, 6);
+            assert_eq!(fx_book.lp_books["MS"]["S1"].volume, 0);
+            assert_eq!(fx_book.lp_books["MS"]["S10"].volume, 6);
+            assert_eq!(
+                fx_books
+                    .history("USD/EUR")
+                    .unwrap()
+                    .points()
+                    .back()
+                    .unwrap()
+                    .ask,
+                Some(Price::new(15565, 4))
+            );
+        }
+
+        // routed orders go through the risk checks, so none are sent while the kill switch is engaged
+        fx_books_mutex.lock().unwrap().risk.set_kill_switch(true);
+        let result = crate::run(venue::send_child_orders(
+            &mut venues,
+            &route_plan,
+            "2",
+            &fx_books_mutex,
+            &mut sim_clock,
+        ));
+        assert!(matches!(result, Err(AppError::Risk(RiskError::KillSwitch))));
+        let fx_books = fx_books_mutex.lock().unwrap();
+        assert_eq!(
+            fx_books
+                .positions
+                .get("USD/EUR")
+                .unwrap()
+                .position
+                .net_volume,
+            5
+        );
+        assert_eq!(sim_clock.now(), start + 15 * clock::NANOS_PER_MILLI);
+    }
+
+    const TEST_CONFIG: &str = "
+settings:
+  min_spread_pips: 4
+  arrival: { kind: uniform, min_ms: 10, max_ms: 20 }
+liquidity_providers:
+  - name: MS
+    currency_pair: USD/EUR
+    buy_price: 1.5554
+    spread_pips: 11
+    tiers:
+      - { volume: 1, markup_pips: 0 }
+      - { volume: 10, markup_pips: 3 }
+    run_iterations: 10
+  - name: CITI
+    currency_pair: USD/JPY
+    buy_price: 147.25
+    spread_pips: 10
+    tiers:
+      - { volume: 1, markup_pips: 0 }
+    run_iterations: 5
+    update_type: incremental
+";
+
+    #[test]
+    fn test_parse_configs() {
+        let mut configs: Vec<Config> = Vec::new();
+        let settings = config::parse_configs(TEST_CONFIG, &mut configs).unwrap();
+
+        assert_eq!(settings.min_spread_pips, 4.0);
+        assert_eq!(
+            configs[1].arrival,
+            ArrivalModel::Uniform {
+                min_ms: 10.0,
+                max_ms: 20.0
+            }
+        );
+        // settings left out of the file take their defaults
+        assert_eq!(settings.price_model, PriceModel::default());
+        assert_eq!(settings.logs.fix_log, "logs/fix.log");
+
+        assert_eq!(configs.len(), 2);
+        assert_eq!(configs[0].liquidity_provider, "MS");
+        assert_eq!(configs[0].buy_price, Price::new(15554, 4));
+        assert_eq!(configs[0].spread, Price::new(11, 4));
+        assert_eq!(configs[0].tiers[1].markup, Price::new(3, 4));
+        assert_eq!(configs[0].update_type, UpdateType::Snapshot);
+        assert_eq!(configs[1].buy_price.to_string(), "147.25");
+        assert_eq!(configs[1].spread, Price::new(10, 2));
+        assert_eq!(configs[1].update_type, UpdateType::Incremental);
+    }
+
+    #[test]
+    fn test_parse_configs_errors() {
+        let error_message = |contents: &str| {
+            let result = config::parse_configs(contents, &mut Vec::new());
+            match result {
+                Err(AppError::ConfigFile(e)) => e.to_string(),
+                _ => panic!("expected a config file error"),
+            }
+        };
+
+        // invalid values are reported with the line and column of the field
+        let message = error_message(&TEST_CONFIG.replace("volume: 10,", "volume: -10,"));
+        assert!(message.contains("-10 must be greater than 0"), "{message}");
+        assert!(message.contains("line 12 column"), "{message}");
+
+        // a sub-pip markup would be rounded away so is rejected
+        let message = error_message(&TEST_CONFIG.replace("markup_pips: 3 ", "markup_pips: 3.4 "));
+        assert!(
+            message.contains("3.4 must be a whole number of pips"),
+            "{message}"
+        );
+        assert!(message.contains("line 12 column"), "{message}");
+        let message =
+            error_message(&TEST_CONFIG.replace("min_spread_pips: 4", "min_spread_pips: 0.5"));
+        assert!(
+            message.contains("0.5 must be a whole number of pips"),
+            "{message}"
+        );
+
+        let message = error_message(&TEST_CONFIG.replace("volume: 10,", "volume: 1,"));
+        assert!(message.contains("listed more than once"), "{message}");
+
+        let message = error_message(&TEST_CONFIG.replace("USD/JPY", "USDJPY"));
+        assert!(message.contains("USDJPY is not of the form"), "{message}");
+        assert!(message.contains("liquidity_providers[1]"), "{message}");
+
+        let message = error_message(&TEST_CONFIG.replace("update_type", "update"));
+        assert!(message.contains("unknown field `update`"), "{message}");
+
+        let message = error_message(&TEST_CONFIG.replace("incremental", "full"));
+        assert!(message.contains("unknown variant `full`"), "{message}");
+
+        let message = error_message(&TEST_CONFIG.replace("    run_iterations: 5\n", ""));
+        assert!(
+            message.contains("missing field `run_iterations`"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn test_virtual_time_feed_completion() {
+        let mut configs: Vec<Config> = Vec::new();
+        let mut settings = config::parse_configs(TEST_CONFIG, &mut configs).unwrap();
+        let start = clock::VIRTUAL_START_TIMESTAMP;
+
+        // CITI has fewer iterations so its stream completes while MS is still quoting
+        let mut scheduler = VirtualTimeScheduler::new(&configs, &settings, 42, start);
+        let mut completed: Vec<((String, String), usize)> = Vec::new();
+        let mut messages = 0;
+        while scheduler.next().is_some() {
+            messages += 1;
+            completed.extend(
+                scheduler
+                    .take_completed()
+                    .into_iter()
+                    .map(|feed| (feed, messages)),
+            );
+        }
+        let citi = (String::from("USD/JPY"), String::from("CITI"));
+        let ms = (String::from("USD/EUR"), String::from("MS"));
+        assert_eq!(completed.len(), 2);
+        assert_eq!(completed[0].0, citi);
+        assert!(completed[0].1 < messages);
+        assert_eq!(completed[1], (ms.clone(), messages));
+
+        // a virtual time run merges every liquidity provider into one stream but still reports each one completing
+        settings.clock = ClockMode::AsFastAsPossible;
+        settings.seed = Some(42);
+        let feeds = crate::run(async {
+            let (feed_completions, mut completed_feeds) = tokio::sync::mpsc::unbounded_channel();
+            let mut streams = simulator::start_streams(&configs, &settings, &feed_completions);
+            drop(feed_completions);
+            assert_eq!(streams.len(), 1);
+            while streams.next().await.is_some() {}
+            let mut feeds = Vec::new();
+            while let Some(feed) = completed_feeds.recv().await {
+                feeds.push(feed);
+            }
+            feeds
+        });
+        assert_eq!(feeds, vec![citi.clone(), ms]);
+
+        let mut fx_books = BookManager::new(&configs, &settings);
+        fx_books.feeds.complete(&citi.0, &citi.1);
+        let status = |fx_books: &BookManager, currency_pair: &str, liquidity_provider: &str| {
+            fx_books.feeds.pair_feeds(currency_pair).unwrap()[liquidity_provider].status(0)
+        };
+        assert_eq!(status(&fx_books, "USD/JPY", "CITI"), FeedStatus::Completed);
+        assert_eq!(status(&fx_books, "USD/EUR", "MS"), FeedStatus::Waiting);
+        fx_books.feeds.complete_all();
+        assert_eq!(status(&fx_books, "USD/EUR", "MS"), FeedStatus::Completed);
+    }
+
+    #[test]
+    fn test_seeded_scheduler_is_reproducible() {
+        let mut configs: Vec<Config> = Vec::new();
+        let settings = config::parse_configs(TEST_CONFIG, &mut configs).unwrap();
+        let start = clock::VIRTUAL_START_TIMESTAMP;
+        let run = |seed: u64| {
+            VirtualTimeScheduler::new(&configs, &settings, seed, start).collect::<Vec<_>>()
+        };
+
+        let first_run = run(42);
+        // every snapshot iteration is sent but unchanged incremental updates are not
+        assert!(first_run.len() > 10 && first_run.len() <= 15);
+        assert_eq!(first_run, run(42));
+        assert_ne!(first_run, run(43));
+
+        // quotes are merged in virtual time order starting from the virtual clock's start
+        assert!(first_run.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+        assert!(first_run[0].0 >= clock::VIRTUAL_START_TIMESTAMP);
+        let market_data = fix::decode_market_data(&first_run[0].1).unwrap();
+        // FIX sending times are to the millisecond
+        assert_eq!(
+            market_data.timestamp(),
+            first_run[0].0 / clock::NANOS_PER_MILLI * clock::NANOS_PER_MILLI
+        );
+
+        // each stream has its own generator so removing a liquidity provider leaves the other unchanged
+        let ms_only: Vec<(u64, String)> =
+            VirtualTimeScheduler::new(&configs[..1], &settings, 42, start).collect();
+        assert_eq!(
+            ms_only,
+            first_run
+                .into_iter()
+                .filter(|(_, marketdata)| fix::decode_market_data(marketdata)
+                    .unwrap()
+                    .liquidity_provider()
+                    == "MS")
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_virtual_time_run_duration_and_clock() {
+        let mut configs: Vec<Config> = Vec::new();
+        let mut settings = config::parse_configs(TEST_CONFIG, &mut configs).unwrap();
+        // a quote every simulated second from each liquidity provider for three seconds
+        configs[0].arrival = ArrivalModel::Fixed { rate_per_sec: 1.0 };
+        settings.run_duration_secs = Some(3);
+        let start = clock::VIRTUAL_START_TIMESTAMP;
+
+        let quotes: Vec<(u64, String)> =
+            VirtualTimeScheduler::new(&configs[..1], &settings, 1, start).collect();
+        assert_eq!(
+            quotes
+                .iter()
+                .map(|(timestamp, _)| (timestamp - start) / 1_000_000_000)
+                .collect::<Vec<u64>>(),
+            vec![1, 2, 3]
+        );
+
+        // real time to wait for a simulated second in each clock mode
+        let second = 1_000_000_000;
+        assert_eq!(
+            ClockMode::RealTime.real_duration(second),
+            Some(Duration::from_secs(1))
+        );
+        assert_eq!(
+            ClockMode::Accelerated { speed: 10.0 }.real_duration(second),
+            Some(Duration::from_millis(100))
+        );
+        assert_eq!(ClockMode::AsFastAsPossible.real_duration(second), None);
+
+        // as fast as possible simulations still move simulated time on
+        let mut sim_clock = SimClock::new(ClockMode::AsFastAsPossible, start);
+        crate::run(sim_clock.sleep_until(start + second));
+        assert_eq!(sim_clock.now(), start + second);
+
+        let clock_config = TEST_CONFIG.replace(
+            "  min_spread_pips: 4\n",
+            "  min_spread_pips: 4\n  clock: { mode: accelerated, speed: 0 }\n",
+        );
+        assert!(matches!(
+            config::parse_configs(&clock_config, &mut Vec::new()),
+            Err(AppError::ConfigFile(e)) if e.to_string().contains("clock speed 0 must be greater than 0")
+        ));
+    }
+
+    #[test]
+    fn test_price_models() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let pip_size = 0.0001;
+
+        // trend moves are whole pips within the range
+        let trend = PriceModel::Trend {
+            min_pips: 1.0,
+            max_pips: 3.0,
+        };
+        let change = trend.next_price(1.5, 1.5, 1.0, pip_size, &mut rng) - 1.5;
+        assert!((1..=3).any(|pips| (change - pips as f64 * pip_size).abs() < 1e-9));
+
+        // without volatility a geometric Brownian motion just grows at the drift rate
+        let gbm = PriceModel::Gbm {
+            drift: 0.1,
+            volatility: 0.0,
+        };
+        let year = 365.25 * 24.0 * 3600.0;
+        let price = gbm.next_price(1.5, 1.5, year, pip_size, &mut rng);
+        assert!((price - 1.5 * 0.1_f64.exp()).abs() < 1e-9);
+
+        // a mean reverting price halves its distance from the mean every half life
+        let mean_reverting = PriceModel::OrnsteinUhlenbeck {
+            mean: Some(1.5),
+            half_life_secs: 60.0,
+            volatility: 0.0,
+        };
+        let price = mean_reverting.next_price(1.6, 1.2, 60.0, pip_size, &mut rng);
+        assert!((price.ln() - 1.5_f64.ln() - (1.6_f64.ln() - 1.5_f64.ln()) / 2.0).abs() < 1e-9);
+        // with no mean given the price reverts to the initial price
+        let mean_reverting = PriceModel::OrnsteinUhlenbeck {
+            mean: None,
+            half_life_secs: 1.0,
+            volatility: 0.0,
+        };
+        let price = mean_reverting.next_price(1.6, 1.2, 3600.0, pip_size, &mut rng);
+        assert!((price - 1.2).abs() < 1e-9);
+
+        // jumps of exactly 10 pips arriving about once a second
+        let jump_diffusion = PriceModel::JumpDiffusion {
+            drift: 0.0,
+            volatility: 0.0,
+            jumps_per_hour: 3600.0,
+            jump_mean_pips: 10.0,
+            jump_std_pips: 0.0,
+        };
+        let price = jump_diffusion.next_price(1.5, 1.5, 60.0, pip_size, &mut rng);
+        let jumps = (price - 1.5) / (10.0 * pip_size);
+        assert!(
+            jumps > 30.0 && (jumps - jumps.round()).abs() < 1e-6,
+            "{jumps}"
+        );
+
+        // prices never fall below a pip
+        let crash = PriceModel::Trend {
+            min_pips: -100.0,
+            max_pips: -100.0,
+        };
+        assert_eq!(
+            crash.next_price(0.0005, 1.5, 1.0, pip_size, &mut rng),
+            pip_size
+        );
+    }
+
+    #[test]
+    fn test_price_model_config() {
+        // the global price model applies to every liquidity provider without its own price model
+        let price_model_config = TEST_CONFIG.replace(
+            "    run_iterations: 5\n",
+            "    run_iterations: 5\n    price_model: { kind: gbm, drift: -0.05, volatility: 0.1 }\n",
+        );
+        let mut configs: Vec<Config> = Vec::new();
+        config::parse_configs(&price_model_config, &mut configs).unwrap();
+        assert_eq!(configs[0].price_model, PriceModel::default());
+        assert_eq!(
+            configs[1].price_model,
+            PriceModel::Gbm {
+                drift: -0.05,
+                volatility: 0.1
+            }
+        );
+
+        let bad_config = price_model_config.replace(
+            "{ kind: gbm, drift: -0.05, volatility: 0.1 }",
+            "{ kind: ornstein_uhlenbeck, half_life_secs: 0, volatility: 0.1 }",
+        );
+        assert!(matches!(
+            config::parse_configs(&bad_config, &mut Vec::new()),
+            Err(AppError::ConfigFile(e)) if e.to_string().contains("half_life_secs 0 must be greater than 0")
+        ));
+    }
+
+    #[test]
+    fn test_arrival_models() {
+        // liquidity providers can override the global arrival model and quiet periods
+        let arrival_config = TEST_CONFIG.replace(
+            "    run_iterations: 5\n",
+            "    run_iterations: 5\n    arrival: { kind: poisson, rate_per_sec: 2000 }\n    quiet_periods: { mean_gap_secs: 60, mean_duration_secs: 5 }\n",
+        );
+        let mut configs: Vec<Config> = Vec::new();
+        config::parse_configs(&arrival_config, &mut configs).unwrap();
+        assert_eq!(configs[0].quiet_periods, None);
+        assert_eq!(
+            configs[1].arrival,
+            ArrivalModel::Poisson {
+                rate_per_sec: 2000.0
+            }
+        );
+
+        let bad_config = arrival_config.replace(
+            "{ kind: poisson, rate_per_sec: 2000 }",
+            "{ kind: hawkes, base_rate_per_sec: 10, excitation_per_sec: 5, decay_per_sec: 5 }",
+        );
+        assert!(matches!(
+            config::parse_configs(&bad_config, &mut Vec::new()),
+            Err(AppError::ConfigFile(e)) if e.to_string().contains("must be at least 0 and less than decay_per_sec 5")
+        ));
+
+        // simulated seconds for 10000 quotes
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut run_secs = |arrival, quiet_periods| {
+            let mut arrival_process = ArrivalProcess::new(arrival, quiet_periods);
+            (0..10_000)
+                .map(|_| arrival_process.next_interval(&mut rng))
+                .sum::<u64>() as f64
+                / 1_000_000_000.0
+        };
+
+        let fixed = ArrivalModel::Fixed {
+            rate_per_sec: 5000.0,
+        };
+        assert_eq!(run_secs(fixed, None), 2.0);
+        let poisson = ArrivalModel::Poisson {
+            rate_per_sec: 5000.0,
+        };
+        assert!((run_secs(poisson, None) - 2.0).abs() < 0.1);
+        // bursts double the base rate when each quote triggers half a quote on average
+        let hawkes = ArrivalModel::Hawkes {
+            base_rate_per_sec: 2500.0,
+            excitation_per_sec: 500.0,
+            decay_per_sec: 1000.0,
+        };
+        assert!((run_secs(hawkes, None) - 2.0).abs() < 0.2);
+        // a quiet second after on average every tenth of a second of quoting
+        let quiet_periods = QuietPeriods {
+            mean_gap_secs: 0.1,
+            mean_duration_secs: 1.0,
+        };
+        assert!(run_secs(fixed, Some(quiet_periods)) > 10.0);
+    }
+
+    const SHARED_MID_CONFIG: &str = "
+currency_pairs:
+  - currency_pair: USD/EUR
+    mid_price: 1.5557
+    price_model: { kind: gbm, volatility: 0.5 }
+liquidity_providers:
+  - name: CITI
+    currency_pair: USD/EUR
+    spread_pips: 10
+    tiers: [{ volume: 1, markup_pips: 0 }]
+    run_iterations: 5
+  - name: BARX
+    currency_pair: USD/EUR
+    spread_pips: 10
+    tiers: [{ volume: 1, markup_pips: 0 }]
+    run_iterations: 5
+";
+
+    #[test]
+    fn test_shared_mid_price() {
+        let mut configs: Vec<Config> = Vec::new();
+        let settings = config::parse_configs(SHARED_MID_CONFIG, &mut configs).unwrap();
+        let start = clock::VIRTUAL_START_TIMESTAMP;
+        let market = configs[0].market.clone().unwrap();
+        // the liquidity providers start half a spread below the mid price
+        assert_eq!(configs[1].buy_price, Price::new(15552, 4));
+
+        let new_mid_path = || {
+            MidPricePath::new(
+                start,
+                market.step_ms * 1_000_000,
+                market.mid_price,
+                market.price_model,
+                0.0001,
+                30_000_000_000,
+                StdRng::seed_from_u64(simulator::derive_seed(42, "mid", "USD/EUR")),
+            )
+        };
+        // the path is the same whichever order its mid prices are asked for
+        let mut first_path = new_mid_path();
+        let mut second_path = new_mid_path();
+        let later = first_path.mid_at(start + 30_000_000_000);
+        let earlier = first_path.mid_at(start + 5_000_000_000);
+        assert_eq!(second_path.mid_at(start + 5_000_000_000), earlier);
+        assert_eq!(second_path.mid_at(start + 30_000_000_000), later);
+        assert_ne!(earlier, later);
+        // only the steps back as far as the latency it holds are kept, with the oldest mid price for earlier times
+        let hour_later = first_path.mid_at(start + 3_600_000_000_000);
+        assert_eq!(second_path.mid_at(start + 3_600_000_000_000), hour_later);
+        assert_eq!(first_path.retained_steps(), 31);
+        let oldest = first_path.mid_at(start + 3_570_000_000_000);
+        assert_eq!(first_path.mid_at(start), oldest);
+        assert_eq!(first_path.retained_steps(), 31);
+
+        // without noise, skew or latency every liquidity provider quotes half a spread either side of the shared mid price
+        let quotes: Vec<(u64, String)> =
+            VirtualTimeScheduler::new(&configs, &settings, 42, start).collect();
+        assert_eq!(quotes.len(), 10);
+        let mut mid_path = new_mid_path();
+        for (timestamp, marketdata) in &quotes {
+            let MarketData::Snapshot(snapshot) = fix::decode_market_data(marketdata).unwrap()
+            else {
+                panic!("expected a snapshot");
+            };
+            let mid = mid_path.mid_at(*timestamp);
+            assert_eq!(snapshot.entries[0].price, Price::from_f64(mid - 0.0005, 4));
+            assert_eq!(
+                snapshot.entries[1].price,
+                Price::from_f64(mid - 0.0005, 4) + Price::new(10, 4)
+            );
+        }
+
+        // every quote crosses the book when the crossing probability is 1
+        let crossing_config = SHARED_MID_CONFIG.replace(
+            "    price_model: { kind: gbm, volatility: 0.5 }\n",
+            "    price_model: { kind: gbm, volatility: 0.5 }\n    crossing_probability: 1\n    crossing_pips: 2\n",
+        );
+        let mut configs: Vec<Config> = Vec::new();
+        let settings = config::parse_configs(&crossing_config, &mut configs).unwrap();
+        let mut mid_path = new_mid_path();
+        for (timestamp, marketdata) in VirtualTimeScheduler::new(&configs, &settings, 42, start) {
+            let MarketData::Snapshot(snapshot) = fix::decode_market_data(&marketdata).unwrap()
+            else {
+                panic!("expected a snapshot");
+            };
+            let buy_price = snapshot.entries[0].price.to_f64();
+            let mid = mid_path.mid_at(timestamp);
+            let mispricing = (buy_price - (mid - 0.0005)).abs();
+            assert!((mispricing - 0.0012).abs() < 0.00006, "{mispricing}");
+        }
+
+        // a liquidity provider needs a buy price unless its currency pair has a mid price
+        let no_mid_config = SHARED_MID_CONFIG
+            .replace("USD/EUR", "USD/GBP")
+            .replacen("USD/GBP", "USD/EUR", 1);
+        let error_message = |contents: &str| match config::parse_configs(contents, &mut Vec::new())
+        {
+            Err(AppError::ConfigFile(e)) => e.to_string(),
+            _ => panic!("expected a config file error"),
+        };
+        // errors between sections are reported at the liquidity provider's section
+        let message = error_message(&no_mid_config);
+        assert!(
+            message.contains("CITI has no buy_price and USD/GBP has no mid_price"),
+            "{message}"
+        );
+        assert!(message.contains("liquidity_providers[0]:"), "{message}");
+        assert!(message.contains("at line 7 column"), "{message}");
+        let message = error_message(&SHARED_MID_CONFIG.replace(
+            "  - name: BARX\n",
+            "  - name: BARX\n    price_model: { kind: gbm, volatility: 0.5 }\n",
+        ));
+        assert!(
+            message.contains("BARX has a price_model but quotes around the USD/EUR mid price"),
+            "{message}"
+        );
+        assert!(message.contains("liquidity_providers[1]:"), "{message}");
+        assert!(message.contains("at line 12 column"), "{message}");
+        // a mid price finer than a pip would be rounded away so is rejected
+        let message = error_message(&SHARED_MID_CONFIG.replace("1.5557", "1.55575"));
+        assert!(
+            message.contains("mid_price 1.55575 has more decimal places than a USD/EUR pip"),
+            "{message}"
+        );
+        assert!(message.contains("currency_pairs[0]:"), "{message}");
+        assert!(message.contains("at line 3 column"), "{message}");
+    }
+
+    #[test]
+    fn test_parse_replay_log() {
+        let mut configs: Vec<Config> = Vec::new();
+        let settings = config::parse_configs(TEST_CONFIG, &mut configs).unwrap();
+        let start = clock::VIRTUAL_START_TIMESTAMP;
+        let recorded: Vec<(u64, String)> =
+            VirtualTimeScheduler::new(&configs, &settings, 42, start).collect();
+
+        // a recorded FIX log is replayed message for message with its sending times
+        let fix_log: String = recorded
+            .iter()
+            .map(|(_, message)| format!("{message}\n"))
+            .collect();
+        let replayed = replay::parse_replay_log(&fix_log, &configs);
+        assert_eq!(replayed.len(), recorded.len());
+        for ((timestamp, message), (recorded_timestamp, recorded_message)) in
+            replayed.iter().zip(&recorded)
+        {
+            assert_eq!(message, recorded_message);
+            assert_eq!(
+                *timestamp,
+                Some(recorded_timestamp / clock::NANOS_PER_MILLI * clock::NANOS_PER_MILLI)
+            );
+        }
+
+        // pipe delimited lines take the volumes of the configured tiers, or 1M, 3M and 5M of the original format,
+        // and lines which can't be read are skipped
+        let pipe_log = "MS | USD/EUR | 1.5554 | 1.5565 | 1.5557 | 1.5562 | 100
+not | a | quote
+BARX | USD/EUR | 1.556 | 1.5571 | 1.5561 | 1.557 | 1.5562 | 1.5569 | 200
+";
+        let replayed = replay::parse_replay_log(pipe_log, &configs);
+        assert_eq!(replayed.len(), 2);
+        assert_eq!(replayed[1].0, Some(200));
+        let MarketData::Snapshot(ms) = fix::decode_market_data(&replayed[0].1).unwrap() else {
+            panic!("expected a snapshot");
+        };
+        assert_eq!(ms.entries[3].volume, 10);
+        assert_eq!(ms.entries[3].price, Price::new(15562, 4));
+        let MarketData::Snapshot(barx) = fix::decode_market_data(&replayed[1].1).unwrap() else {
+            panic!("expected a snapshot");
+        };
+        assert_eq!(
+            barx.entries
+                .iter()
+                .map(|entry| (entry.entry_id.as_str(), entry.price.to_string()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("B1", String::from("1.5560")),
+                ("S1", String::from("1.5571")),
+                ("B3", String::from("1.5561")),
+                ("S3", String::from("1.5570")),
+                ("B5", String::from("1.5562")),
+                ("S5", String::from("1.5569")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cli_args() {
+        use clap::Parser;
+
+        // no subcommand runs the GUI with the default config
+        let Command::Gui(run_args) = Cli::parse_from(["fx_sim_agg_gui"]).command() else {
+            panic!("expected the gui subcommand");
+        };
+        assert_eq!(run_args.config_args.config, config::DEFAULT_CONFIG_PATH);
+
+        let cli = Cli::parse_from([
+            "fx_sim_agg_gui",
+            "headless",
+            "--config",
+            "test.yaml",
+            "--fix-log",
+            "out/fix.log",
+            "--duration",
+            "30",
+        ]);
+        let Command::Headless(run_args) = cli.command() else {
+            panic!("expected the headless subcommand");
+        };
+        assert_eq!(run_args.config_args.config, "test.yaml");
+
+        // flags override the config file settings and anything not given is left alone
+        let mut settings = Settings::default();
+        run_args.apply(&mut settings);
+        assert_eq!(settings.logs.fix_log, "out/fix.log");
+        assert_eq!(settings.logs.log_config, "logging_config.yaml");
+        assert_eq!(settings.run_duration_secs, Some(30));
+
+        assert!(Cli::try_parse_from(["fx_sim_agg_gui", "headless", "--duration", "x"]).is_err());
+        assert!(Cli::try_parse_from(["fx_sim_agg_gui", "headless", "--speed", "0"]).is_err());
+        assert!(
+            Cli::try_parse_from(["fx_sim_agg_gui", "headless", "--speed", "10", "--afap"]).is_err()
+        );
+
+        // replays keep their original timing unless sped up, whatever the config file's clock
+        let Command::Replay(replay_args) =
+            Cli::parse_from(["fx_sim_agg_gui", "replay", "logs/fix.log", "--step"]).command()
+        else {
+            panic!("expected the replay subcommand");
+        };
+        settings.clock = ClockMode::AsFastAsPossible;
+        replay_args.apply(&mut settings);
+        assert_eq!(settings.clock, ClockMode::RealTime);
+        assert_eq!(settings.run_duration_secs, None);
+        assert!(settings.replay.is_some_and(|replay| replay.step));
+        assert!(
+            Cli::try_parse_from(["fx_sim_agg_gui", "replay", "fix.log", "--afap", "--step"])
+                .is_err()
+        );
+        assert!(matches!(
+            Cli::parse_from(["fx_sim_agg_gui", "validate-config", "-c", "test.yaml"]).command(),
+            Command::ValidateConfig(config_args) if config_args.config == "test.yaml"
+        ));
+    }
+
+    #[test]
+    fn test_get_tier_entries() {
+        let tiers = vec![
+            Tier {
+                volume: 1,
+                markup: Price::new(0, 4),
+            },
+            Tier {
+                volume: 10,
+                markup: Price::new(2, 4),
+            },
+        ];
+        let entries =
+            simulator::get_tier_entries(&tiers, Price::new(15552, 4), Price::new(15562, 4));
+
+        assert_eq!(
+            entries
+                .iter()
+                .map(|entry| (
+                    entry.entry_id.as_str(),
+                    entry.side,
+                    entry.price.to_string(),
+                    entry.volume
+                ))
+                .collect::<Vec<(&str, BookSide, String, i32)>>(),
+            vec![
+                ("B1", BookSide::Buy, String::from("1.5552"), 1),
+                ("S1", BookSide::Sell, String::from("1.5562"), 1),
+                ("B10", BookSide::Buy, String::from("1.5554"), 10),
+                ("S10", BookSide::Sell, String::from("1.5560"), 10),
+            ]
+        );
+    }
 }
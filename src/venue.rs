@@ -0,0 +1,363 @@
+//! # FX Simulator and Aggregator - fx_sim_agg_gui
+//!
+//! `venue.rs` simulates each liquidity provider as an execution venue for the child orders of a `RoutePlan`. A child order
+//! reaches the liquidity provider after its latency and is held for its last look, then rejected if the liquidity provider's
+//! quote has moved against the best price it was routed at by more than its tolerance or at random, and otherwise filled
+//! level by level, each fill or reject returned as an `ExecutionReport`.
+use crate::AppError;
+use crate::aggregator::{BookManager, FxBook};
+use crate::clock::{NANOS_PER_MILLI, SimClock};
+use crate::config::Config;
+use crate::execution::{Execution, Fill, Order, OrderSide};
+use crate::fix::{ExecutionReport, OrdStatus};
+use crate::price::{Price, pip_decimals};
+use crate::router::{self, ChildOrder, RoutePlan, RouterSettings};
+use crate::simulator::derive_seed;
+use log::info;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Deserialize;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct VenueConfig {
+    // time (milliseconds) for a child order to reach the liquidity provider
+    pub latency_ms: u64,
+    // time (milliseconds) the liquidity provider holds a child order before deciding to fill or reject it
+    pub last_look_ms: u64,
+    // chance of the liquidity provider rejecting a child order whatever its price
+    pub reject_probability: f64,
+    // reject a child order when the liquidity provider's quote has moved more than this many pips against it
+    pub max_price_move_pips: Option<f64>,
+}
+
+impl VenueConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&self.reject_probability) {
+            return Err(format!(
+                "reject_probability {} is not a probability between 0 and 1",
+                self.reject_probability
+            ));
+        }
+        // quotes move in whole pips so, like spreads and markups, the tolerance is a whole number of pips
+        if let Some(max_price_move_pips) = self.max_price_move_pips
+            && !(max_price_move_pips >= 0.0 && max_price_move_pips.fract() == 0.0)
+        {
+            return Err(format!(
+                "max_price_move_pips {max_price_move_pips} must be a whole number of pips"
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn response_time(&self) -> Duration {
+        // how long after a child order is sent its fills or reject are decided
+        Duration::from_nanos((self.latency_ms + self.last_look_ms) * NANOS_PER_MILLI)
+    }
+}
+
+// the fill, or reject, an execution report is sent for
+struct ReportFill {
+    ord_status: OrdStatus,
+    last_qty: i32,
+    last_px: Price,
+    cum_qty: i32,
+    leaves_qty: i32,
+    avg_px: f64,
+    text: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct LpVenue {
+    pub liquidity_provider: String,
+    pub currency_pair: String,
+    pub venue: VenueConfig,
+    rng: StdRng,
+    msg_seq_num: u32,
+    exec_count: u64,
+}
+
+impl LpVenue {
+    pub fn new(config: &Config, rng: StdRng) -> Self {
+        LpVenue {
+            liquidity_provider: config.liquidity_provider.clone(),
+            currency_pair: config.currency_pair.clone(),
+            venue: config.venue,
+            rng,
+            msg_seq_num: 0,
+            exec_count: 0,
+        }
+    }
+
+    pub fn handle_child_order(
+        &mut self,
+        cl_ord_id: &str,
+        side: OrderSide,
+        child_order: &ChildOrder,
+        quote: Option<Price>,
+        timestamp: u64,
+    ) -> Vec<ExecutionReport> {
+        // decide at the end of the last look, against the liquidity provider's quote at that time on the
+        // side the child order takes, whether to reject the child order or fill each of its levels
+        if let Some(reason) = self.last_look(side, child_order, quote) {
+            let reject = ReportFill {
+                ord_status: OrdStatus::Rejected,
+                last_qty: 0,
+                last_px: Price::default(),
+                cum_qty: 0,
+                leaves_qty: 0,
+                avg_px: 0.0,
+                text: Some(reason),
+            };
+            let report = self.execution_report(cl_ord_id, side, child_order, reject, timestamp);
+            info!("{report}");
+            return vec![report];
+        }
+
+        let mut reports: Vec<ExecutionReport> = Vec::new();
+        let mut cum_qty = 0;
+        let mut notional = 0.0;
+        for (price, volume) in &child_order.levels {
+            cum_qty += volume;
+            notional += price.to_f64() * f64::from(*volume);
+            let leaves_qty = child_order.volume - cum_qty;
+            let ord_status = if leaves_qty == 0 {
+                OrdStatus::Filled
+            } else {
+                OrdStatus::PartiallyFilled
+            };
+            let fill = ReportFill {
+                ord_status,
+                last_qty: *volume,
+                last_px: *price,
+                cum_qty,
+                leaves_qty,
+                avg_px: notional / f64::from(cum_qty),
+                text: None,
+            };
+            let report = self.execution_report(cl_ord_id, side, child_order, fill, timestamp);
+            info!("{report}");
+            reports.push(report);
+        }
+        reports
+    }
+
+    fn last_look(
+        &mut self,
+        side: OrderSide,
+        child_order: &ChildOrder,
+        quote: Option<Price>,
+    ) -> Option<String> {
+        // the reason for rejecting the child order, or None to fill it
+        let Some(quote) = quote else {
+            return Some(String::from("no quote"));
+        };
+        if let Some(max_price_move_pips) = self.venue.max_price_move_pips {
+            // how far the quote has moved against the best price the child order was routed at
+            let routed_price = child_order
+                .levels
+                .first()
+                .map_or(child_order.limit_price, |level| level.0);
+            let price_move = match side {
+                OrderSide::Buy => quote - routed_price,
+                OrderSide::Sell => routed_price - quote,
+            };
+            let price_move_pips = price_move.to_pips(pip_decimals(&self.currency_pair));
+            if price_move_pips > max_price_move_pips {
+                return Some(format!("price moved {price_move_pips:.1} pips"));
+            }
+        }
+        if self.rng.random_bool(self.venue.reject_probability) {
+            return Some(String::from("last look reject"));
+        }
+        None
+    }
+
+    fn execution_report(
+        &mut self,
+        cl_ord_id: &str,
+        side: OrderSide,
+        child_order: &ChildOrder,
+        fill: ReportFill,
+        timestamp: u64,
+    ) -> ExecutionReport {
+        self.msg_seq_num += 1;
+        self.exec_count += 1;
+        ExecutionReport {
+            liquidity_provider: self.liquidity_provider.clone(),
+            currency_pair: self.currency_pair.clone(),
+            msg_seq_num: self.msg_seq_num,
+            cl_ord_id: String::from(cl_ord_id),
+            exec_id: format!("{}-{}", self.liquidity_provider, self.exec_count),
+            side,
+            ord_status: fill.ord_status,
+            order_qty: child_order.volume,
+            last_qty: fill.last_qty,
+            last_px: fill.last_px,
+            cum_qty: fill.cum_qty,
+            leaves_qty: fill.leaves_qty,
+            avg_px: fill.avg_px,
+            text: fill.text,
+            timestamp,
+        }
+    }
+}
+
+pub fn create_venues(configs: &[Config], seed: Option<u64>) -> Vec<LpVenue> {
+    // a venue for every liquidity provider and currency pair, with its own random number generator for rejects
+    configs
+        .iter()
+        .map(|config| {
+            let rng = match seed {
+                Some(seed) => StdRng::seed_from_u64(derive_seed(
+                    seed,
+                    &format!("venue {}", config.liquidity_provider),
+                    &config.currency_pair,
+                )),
+                None => StdRng::from_os_rng(),
+            };
+            LpVenue::new(config, rng)
+        })
+        .collect()
+}
+
+pub fn lp_quote(fx_book: &FxBook, liquidity_provider: &str, side: OrderSide) -> Option<Price> {
+    // the liquidity provider's best quote with volume left on the side of its book an order takes
+    let book_side = side.book_side();
+    let prices = fx_book
+        .lp_books
+        .get(liquidity_provider)?
+        .values()
+        .filter(|entry| entry.side == book_side && entry.volume > 0)
+        .map(|entry| entry.price);
+    match side {
+        OrderSide::Buy => prices.min(),
+        OrderSide::Sell => prices.max(),
+    }
+}
+
+pub async fn send_child_orders(
+    venues: &mut [LpVenue],
+    route_plan: &RoutePlan,
+    order_id: &str,
+    fx_books_mutex: &Arc<Mutex<BookManager>>,
+    sim_clock: &mut SimClock,
+) -> Result<Vec<ExecutionReport>, AppError> {
+    // nothing is sent unless the child orders pass the same risk checks and kill switch as orders executed against the book
+    fx_books_mutex
+        .lock()
+        .unwrap()
+        .check_route_plan(route_plan)?; // panic if can't get lock
+
+    // the child orders are sent together at the simulation clock's current time so each is decided after its
+    // own venue's response time in simulated time, against the liquidity provider's quote in the aggregated
+    // book at that moment, and the fills are taken from the book and added to the positions of the book manager. Child orders to a
+    // liquidity provider without a venue are rejected straight away
+    let sent = sim_clock.now();
+    let order = &route_plan.order;
+    let mut pending: Vec<(u64, String, &ChildOrder, Option<usize>)> = Vec::new();
+    for (number, child_order) in (1..).zip(&route_plan.child_orders) {
+        let venue_index = venues.iter().position(|venue| {
+            venue.liquidity_provider == child_order.liquidity_provider
+                && venue.currency_pair == order.currency_pair
+        });
+        let response_time = venue_index
+            .map(|index| venues[index].venue.response_time())
+            .unwrap_or_default();
+        pending.push((
+            sent + response_time.as_nanos() as u64,
+            format!("{order_id}-{number}"),
+            child_order,
+            venue_index,
+        ));
+    }
+    pending.sort_by_key(|pending| pending.0);
+
+    let mut reports: Vec<ExecutionReport> = Vec::new();
+    for (deadline, cl_ord_id, child_order, venue_index) in pending {
+        sim_clock.sleep_until(deadline).await;
+        let timestamp = sim_clock.now();
+        match venue_index {
+            Some(index) => {
+                let mut fx_books = fx_books_mutex.lock().unwrap();
+                let quote = fx_books.get(&order.currency_pair).and_then(|fx_book| {
+                    lp_quote(fx_book, &child_order.liquidity_provider, order.side)
+                });
+                let child_reports = venues[index].handle_child_order(
+                    &cl_ord_id,
+                    order.side,
+                    child_order,
+                    quote,
+                    timestamp,
+                );
+                // the fills are added to the positions and taken from the book as the liquidity provider reports them
+                for report in &child_reports {
+                    fx_books.add_execution_report(report);
+                }
+                reports.extend(child_reports);
+            }
+            None => {
+                let report = ExecutionReport {
+                    liquidity_provider: child_order.liquidity_provider.clone(),
+                    currency_pair: order.currency_pair.clone(),
+                    msg_seq_num: 0,
+                    cl_ord_id,
+                    exec_id: String::new(),
+                    side: order.side,
+                    ord_status: OrdStatus::Rejected,
+                    order_qty: child_order.volume,
+                    last_qty: 0,
+                    last_px: Price::default(),
+                    cum_qty: 0,
+                    leaves_qty: 0,
+                    avg_px: 0.0,
+                    text: Some(String::from("unknown liquidity provider")),
+                    timestamp,
+                };
+                info!("{report}");
+                reports.push(report);
+            }
+        }
+    }
+    Ok(reports)
+}
+
+pub async fn route_and_send(
+    venues: &mut [LpVenue],
+    order: &Order,
+    router_settings: &RouterSettings,
+    order_id: &str,
+    fx_books_mutex: &Arc<Mutex<BookManager>>,
+    sim_clock: &mut SimClock,
+) -> Result<Execution, AppError> {
+    // route the order across the liquidity providers quoting in its book and send the child orders to their venues.
+    // The execution has every fill the venues report, so any volume not routed or rejected is cancelled
+    let route_plan = {
+        let fx_books = fx_books_mutex.lock().unwrap(); // panic if can't get lock
+        let fx_book = fx_books.get(&order.currency_pair).ok_or_else(|| {
+            AppError::InvalidOrder(format!("no book for {}", order.currency_pair))
+        })?;
+        router::route_order(fx_book, order, router_settings)?
+    };
+    info!("routing {route_plan}");
+    let reports =
+        send_child_orders(venues, &route_plan, order_id, fx_books_mutex, sim_clock).await?;
+    let fills = reports
+        .iter()
+        .filter(|report| report.ord_status != OrdStatus::Rejected && report.last_qty > 0)
+        .map(|report| Fill {
+            liquidity_provider: report.liquidity_provider.clone(),
+            price: report.last_px,
+            volume: report.last_qty,
+        })
+        .collect();
+    let execution = Execution {
+        order: order.clone(),
+        fills,
+        timestamp: sim_clock.now(),
+    };
+    info!("executed {execution}");
+    Ok(execution)
+}